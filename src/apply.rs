@@ -0,0 +1,437 @@
+use crate::error::{GmuxError, Result};
+use crate::git::{get_repository_metadata, git_stdout};
+use crate::output::ApplyResult;
+use crate::utils::repository_name;
+use clap::Args;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+pub const STATUS_APPLIED: &str = "applied";
+pub const STATUS_ALREADY_APPLIED: &str = "already-applied";
+pub const STATUS_CONFLICT: &str = "conflict";
+pub const STATUS_FAILED: &str = "failed";
+
+const REASON_APPLY_CONFLICT: &str = "resolve the conflicts and `git add` the files";
+const REASON_AM_CONFLICT: &str =
+    "resolve the conflicts and run `git am --continue`, or `git am --abort`";
+const REASON_MERGE_CONFLICT: &str =
+    "resolve the conflicts and run `git merge --continue`, or `git merge --abort`";
+
+type Outcome = Result<(&'static str, Option<String>)>;
+
+#[derive(Args, Clone, Debug)]
+pub struct ApplyOptions {
+    /// Patch to apply; mbox files from `git format-patch` are applied as commits with `git am`,
+    /// and git bundles are fetched and merged
+    pub patch: PathBuf,
+}
+
+impl ApplyOptions {
+    /// Resolves the patch to an absolute path, since it is applied from inside each repository.
+    pub fn resolve_patch(&self) -> Result<PathBuf> {
+        self.patch.canonicalize().map_err(|error| {
+            GmuxError::Validation(format!(
+                "cannot read patch {}: {}",
+                self.patch.display(),
+                error
+            ))
+        })
+    }
+}
+
+/// How a patch file is applied, decided by its first line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatchKind {
+    /// A plain diff, applied with `git apply --3way`.
+    Diff,
+    /// Commits from `git format-patch`, applied with `git am --3way`.
+    Mbox,
+    /// Commits from `git bundle create`, fetched and merged.
+    Bundle,
+}
+
+impl ApplyResult {
+    fn new(path: &Path, branch: Option<String>) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            branch,
+            status: STATUS_FAILED.to_string(),
+            reason: None,
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn finish(mut self, status: &str, reason: Option<String>) -> Self {
+        self.status = status.to_string();
+        self.reason = reason;
+        self
+    }
+}
+
+/// Patches produced by `git format-patch` start with a `From <commit> <date>` mbox separator,
+/// and bundles with a `# v2 git bundle` (or v3) signature.
+pub fn patch_kind(patch: &Path) -> Result<PatchKind> {
+    let mut first_line = Vec::new();
+    BufReader::new(File::open(patch)?).read_until(b'\n', &mut first_line)?;
+    Ok(if first_line.starts_with(b"From ") {
+        PatchKind::Mbox
+    } else if first_line.starts_with(b"# v2 git bundle")
+        || first_line.starts_with(b"# v3 git bundle")
+    {
+        PatchKind::Bundle
+    } else {
+        PatchKind::Diff
+    })
+}
+
+/// Applies the patch to one repository. Conflicts are left in the working tree (and in the
+/// `git am` session or merge) for the user to resolve. Returns `None` for directories that are
+/// not git repositories.
+pub async fn apply_repository(path: &Path, patch: &Path, kind: PatchKind) -> Option<ApplyResult> {
+    let metadata = match get_repository_metadata(path).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return None,
+        Err(error) => {
+            return Some(
+                ApplyResult::new(path, None).finish(STATUS_FAILED, Some(error.format_error())),
+            )
+        }
+    };
+    let branch = Some(metadata.current_branch).filter(|branch| !branch.is_empty());
+    let mut result = ApplyResult::new(path, branch);
+    let patch = patch.to_string_lossy();
+
+    let outcome = match kind {
+        PatchKind::Diff => apply_diff(path, &patch, &mut result).await,
+        PatchKind::Mbox => apply_mbox(path, &patch, &mut result).await,
+        PatchKind::Bundle => apply_bundle(path, &patch, &mut result).await,
+    };
+    Some(match outcome {
+        Ok((status, reason)) => result.finish(status, reason),
+        Err(error) => {
+            let reason = error.format_error();
+            result.finish(STATUS_FAILED, Some(reason))
+        }
+    })
+}
+
+async fn apply_diff(path: &Path, patch: &str, result: &mut ApplyResult) -> Outcome {
+    // A patch that applies cleanly in reverse is already present.
+    if applies_in_reverse(path, &[patch]).await {
+        return Ok((STATUS_ALREADY_APPLIED, None));
+    }
+    match git_stdout(path, &["apply", "--3way", patch]).await {
+        Ok(_) => Ok((STATUS_APPLIED, None)),
+        Err(GmuxError::Git(stderr)) => {
+            Ok(failure(path, stderr, REASON_APPLY_CONFLICT, result).await)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Applies the patches of an mbox that are not in the repository yet, so a series that was
+/// partly applied before picks up where it stopped.
+async fn apply_mbox(path: &Path, patch: &str, result: &mut ApplyResult) -> Outcome {
+    let split_dir = tempfile::TempDir::new()?;
+    let output_arg = format!("-o{}", split_dir.path().display());
+    git_stdout(path, &["mailsplit", &output_arg, patch]).await?;
+    let mut patches = std::fs::read_dir(split_dir.path())?
+        .map(|entry| Ok(entry?.path().to_string_lossy().to_string()))
+        .collect::<Result<Vec<_>>>()?;
+    patches.sort();
+
+    // Applied patches form a prefix of the series, which reverts cleanly from its last patch
+    // back to its first.
+    let mut applied = patches.len();
+    while applied > 0 {
+        let prefix: Vec<&str> = patches[..applied]
+            .iter()
+            .rev()
+            .map(String::as_str)
+            .collect();
+        if applies_in_reverse(path, &prefix).await {
+            break;
+        }
+        applied -= 1;
+    }
+    if applied == patches.len() {
+        return Ok((STATUS_ALREADY_APPLIED, None));
+    }
+
+    let mut args = vec!["am", "--3way"];
+    args.extend(patches[applied..].iter().map(String::as_str));
+    match git_stdout(path, &args).await {
+        Ok(_) => Ok((
+            STATUS_APPLIED,
+            (applied > 0).then(|| {
+                format!(
+                    "{} of {} patches were already applied",
+                    applied,
+                    patches.len()
+                )
+            }),
+        )),
+        Err(GmuxError::Git(stderr)) if am_in_progress(path).await => {
+            result.conflicts = conflicted_files(path).await;
+            let reason = if result.conflicts.is_empty() {
+                format!("{}\n{}", stderr, REASON_AM_CONFLICT)
+            } else {
+                REASON_AM_CONFLICT.to_string()
+            };
+            Ok((STATUS_CONFLICT, Some(reason)))
+        }
+        Err(GmuxError::Git(stderr)) => {
+            Ok(failure(path, stderr, REASON_APPLY_CONFLICT, result).await)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Fetches the bundle's `HEAD`, or its only ref, and merges it into the current branch.
+/// `git bundle verify` fails when the repository lacks the commits the bundle builds on.
+async fn apply_bundle(path: &Path, bundle: &str, result: &mut ApplyResult) -> Outcome {
+    git_stdout(path, &["bundle", "verify", "--quiet", bundle]).await?;
+    let heads = git_stdout(path, &["bundle", "list-heads", bundle]).await?;
+    let Some(head) = bundle_head(&heads) else {
+        return Ok((
+            STATUS_FAILED,
+            Some("bundle has several refs and no HEAD to apply".to_string()),
+        ));
+    };
+    git_stdout(path, &["fetch", "--quiet", "--no-tags", bundle, head]).await?;
+    let commit = git_stdout(path, &["rev-parse", "FETCH_HEAD"]).await?;
+    let commit = commit.trim();
+
+    if git_stdout(path, &["merge-base", "--is-ancestor", commit, "HEAD"])
+        .await
+        .is_ok()
+    {
+        return Ok((STATUS_ALREADY_APPLIED, None));
+    }
+    match git_stdout(path, &["merge", "--no-edit", commit]).await {
+        Ok(_) => Ok((STATUS_APPLIED, None)),
+        Err(GmuxError::Git(stderr)) => {
+            Ok(failure(path, stderr, REASON_MERGE_CONFLICT, result).await)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// The ref to apply from `git bundle list-heads` output: `HEAD` if the bundle has it,
+/// otherwise its only ref.
+fn bundle_head(heads: &str) -> Option<&str> {
+    let refs: Vec<&str> = heads
+        .lines()
+        .filter_map(|line| line.split_once(' ').map(|(_, name)| name.trim()))
+        .collect();
+    match refs.as_slice() {
+        [only] => Some(only),
+        refs => refs.iter().copied().find(|name| *name == "HEAD"),
+    }
+}
+
+/// Whether the patches, applied in the given order, revert cleanly.
+async fn applies_in_reverse(path: &Path, patches: &[&str]) -> bool {
+    let mut args = vec!["apply", "--check", "--reverse"];
+    args.extend_from_slice(patches);
+    git_stdout(path, &args).await.is_ok()
+}
+
+/// A failed apply is a conflict when it left conflicted files behind.
+async fn failure(
+    path: &Path,
+    stderr: String,
+    conflict_reason: &str,
+    result: &mut ApplyResult,
+) -> (&'static str, Option<String>) {
+    result.conflicts = conflicted_files(path).await;
+    if result.conflicts.is_empty() {
+        (STATUS_FAILED, Some(stderr))
+    } else {
+        (STATUS_CONFLICT, Some(conflict_reason.to_string()))
+    }
+}
+
+async fn conflicted_files(path: &Path) -> Vec<String> {
+    git_stdout(path, &["diff", "--name-only", "--diff-filter=U"])
+        .await
+        .map(|stdout| stdout.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+async fn am_in_progress(path: &Path) -> bool {
+    match git_stdout(path, &["rev-parse", "--git-path", "rebase-apply"]).await {
+        Ok(state_dir) => path.join(state_dir.trim()).exists(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::TempDir;
+
+    /// A repository with `a.txt` committed on `main`, and a patch changing its second line.
+    async fn setup_repo(temp_dir: &TempDir, mbox: bool) -> (PathBuf, PathBuf) {
+        let repo_path = temp_dir.path().join("repo");
+        std::fs::create_dir(&repo_path).unwrap();
+        git(&repo_path, &["init", "-b", "main"]).await;
+        std::fs::write(repo_path.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+        git(&repo_path, &["add", "a.txt"]).await;
+        git(&repo_path, &["commit", "-m", "Initial commit"]).await;
+
+        std::fs::write(repo_path.join("a.txt"), "one\n2\nthree\n").unwrap();
+        git(&repo_path, &["commit", "-am", "Change two"]).await;
+        let patch = if mbox {
+            git(&repo_path, &["format-patch", "-1", "--stdout"]).await
+        } else {
+            git(&repo_path, &["diff", "HEAD~1"]).await
+        };
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+
+        let patch_path = temp_dir.path().join("change.patch");
+        std::fs::write(&patch_path, patch).unwrap();
+        (repo_path, patch_path)
+    }
+
+    #[tokio::test]
+    async fn test_apply_repository() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (repo_path, patch) = setup_repo(&temp_dir, false).await;
+        assert_eq!(patch_kind(&patch)?, PatchKind::Diff);
+
+        let result = apply_repository(&repo_path, &patch, PatchKind::Diff)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_APPLIED);
+        assert_eq!(
+            std::fs::read_to_string(repo_path.join("a.txt"))?,
+            "one\n2\nthree\n"
+        );
+
+        let result = apply_repository(&repo_path, &patch, PatchKind::Diff)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_ALREADY_APPLIED);
+
+        git(&repo_path, &["reset", "--hard"]).await;
+        std::fs::write(repo_path.join("a.txt"), "one\nzwei\nthree\n")?;
+        git(&repo_path, &["commit", "-am", "Conflicting change"]).await;
+        let result = apply_repository(&repo_path, &patch, PatchKind::Diff)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_CONFLICT);
+        assert_eq!(result.conflicts, vec!["a.txt"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_mbox_conflict() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (repo_path, patch) = setup_repo(&temp_dir, true).await;
+        assert_eq!(patch_kind(&patch)?, PatchKind::Mbox);
+
+        std::fs::write(repo_path.join("a.txt"), "one\nzwei\nthree\n")?;
+        git(&repo_path, &["commit", "-am", "Conflicting change"]).await;
+        let result = apply_repository(&repo_path, &patch, PatchKind::Mbox)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_CONFLICT);
+        assert_eq!(result.conflicts, vec!["a.txt"]);
+        assert!(am_in_progress(&repo_path).await);
+
+        git(&repo_path, &["am", "--abort"]).await;
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+        let result = apply_repository(&repo_path, &patch, PatchKind::Mbox)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_APPLIED);
+        let subject = git(&repo_path, &["log", "-1", "--format=%s"]).await;
+        assert_eq!(subject.trim(), "Change two");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_partly_applied_mbox() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (repo_path, _) = setup_repo(&temp_dir, true).await;
+        std::fs::write(repo_path.join("a.txt"), "one\n2\nthree\n")?;
+        git(&repo_path, &["commit", "-am", "Change two"]).await;
+        std::fs::write(repo_path.join("b.txt"), "new\n")?;
+        git(&repo_path, &["add", "b.txt"]).await;
+        git(&repo_path, &["commit", "-m", "Add b"]).await;
+        let series = git(&repo_path, &["format-patch", "-2", "--stdout"]).await;
+        let patch = temp_dir.path().join("series.patch");
+        std::fs::write(&patch, series)?;
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+
+        let result = apply_repository(&repo_path, &patch, PatchKind::Mbox)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_APPLIED);
+        assert_eq!(
+            result.reason.as_deref(),
+            Some("1 of 2 patches were already applied")
+        );
+        let subjects = git(&repo_path, &["log", "-3", "--format=%s"]).await;
+        assert_eq!(subjects, "Add b\nChange two\nInitial commit\n");
+
+        let result = apply_repository(&repo_path, &patch, PatchKind::Mbox)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_ALREADY_APPLIED);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_apply_bundle() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let (repo_path, _) = setup_repo(&temp_dir, false).await;
+        std::fs::write(repo_path.join("a.txt"), "one\n2\nthree\n")?;
+        git(&repo_path, &["commit", "-am", "Change two"]).await;
+        let bundle = temp_dir.path().join("change.bundle");
+        let bundle_arg = bundle.to_string_lossy();
+        git(
+            &repo_path,
+            &["bundle", "create", &bundle_arg, "main~1..main"],
+        )
+        .await;
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+        assert_eq!(patch_kind(&bundle)?, PatchKind::Bundle);
+
+        let result = apply_repository(&repo_path, &bundle, PatchKind::Bundle)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_APPLIED);
+        let subject = git(&repo_path, &["log", "-1", "--format=%s"]).await;
+        assert_eq!(subject.trim(), "Change two");
+
+        let result = apply_repository(&repo_path, &bundle, PatchKind::Bundle)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_ALREADY_APPLIED);
+
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+        std::fs::write(repo_path.join("a.txt"), "one\nzwei\nthree\n")?;
+        git(&repo_path, &["commit", "-am", "Conflicting change"]).await;
+        let result = apply_repository(&repo_path, &bundle, PatchKind::Bundle)
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_CONFLICT);
+        assert_eq!(result.conflicts, vec!["a.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bundle_head() {
+        assert_eq!(
+            bundle_head("abc refs/heads/main\n"),
+            Some("refs/heads/main")
+        );
+        assert_eq!(bundle_head("abc refs/heads/main\nabc HEAD\n"), Some("HEAD"));
+        assert_eq!(bundle_head("abc refs/heads/a\ndef refs/heads/b\n"), None);
+    }
+}
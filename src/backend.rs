@@ -0,0 +1,667 @@
+use crate::config::{get_config_path, load_config_file, Config};
+use crate::error::{GmuxError, Result};
+use crate::git::{
+    count_ahead_behind, get_branch_diff, get_log, get_ref_details, get_remote_url,
+    get_repository_metadata, get_working_tree_status, list_branch_refs, resolve_first_ref,
+    BranchCommit, BranchDiff, BranchRef, RefDetails, RepositoryMetadata, WorkingTreeStatus,
+};
+use async_trait::async_trait;
+use std::path::Path;
+
+pub const GIT_BACKEND_CLI: &str = "cli";
+pub const GIT_BACKEND_LIBGIT2: &str = "libgit2";
+
+/// The read-only git operations behind `inspect` and `doctor`. Commands that change
+/// repositories always run git.
+#[async_trait]
+pub trait GitBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The current and default branch, or `None` outside a git repository.
+    async fn metadata(&self, path: &Path) -> Result<Option<RepositoryMetadata>>;
+
+    async fn status(&self, path: &Path) -> Result<WorkingTreeStatus>;
+
+    /// Changes on `head` since it diverged from `base`, see `git::get_branch_diff`.
+    async fn diff(&self, path: &Path, base: &str, head: &str) -> Result<BranchDiff>;
+
+    /// The latest `limit` commits reachable from `rev`, newest first.
+    async fn log(&self, path: &Path, rev: &str, limit: usize) -> Result<Vec<BranchCommit>>;
+
+    /// Commits on `head` but not `base`, and on `base` but not `head`.
+    async fn ahead_behind(&self, path: &Path, base: &str, head: &str) -> Result<(u32, u32)>;
+
+    /// Details of the refs named exactly by `names`. Refs that do not exist are left out.
+    async fn refs(&self, path: &Path, names: &[String]) -> Result<Vec<RefDetails>>;
+
+    /// Local branches and the branches of `remote`, sorted by ref name.
+    async fn branch_refs(
+        &self,
+        path: &Path,
+        remote: &str,
+        merged_into: Option<&str>,
+    ) -> Result<Vec<BranchRef>>;
+
+    /// Returns the first of `candidates` that names an existing commit.
+    async fn resolve_ref(&self, path: &Path, candidates: &[String]) -> Result<Option<String>>;
+
+    async fn remote_url(&self, path: &Path, remote: &str) -> Result<Option<String>>;
+}
+
+/// The backend named by `git_backend` in the config.
+pub fn git_backend(config: &Config) -> Result<Box<dyn GitBackend>> {
+    match config.git_backend.as_str() {
+        GIT_BACKEND_CLI => Ok(Box::new(CliBackend)),
+        #[cfg(feature = "libgit2")]
+        GIT_BACKEND_LIBGIT2 => Ok(Box::new(libgit2::Libgit2Backend)),
+        #[cfg(not(feature = "libgit2"))]
+        GIT_BACKEND_LIBGIT2 => Err(GmuxError::config(
+            "git_backend is libgit2 but gmux was built without the `libgit2` feature",
+        )),
+        other => Err(GmuxError::config(format!(
+            "unknown git_backend `{}`, expected `{}` or `{}`",
+            other, GIT_BACKEND_CLI, GIT_BACKEND_LIBGIT2
+        ))),
+    }
+}
+
+/// The backend configured in `config.json`, which does not need a GitHub token.
+pub fn configured_git_backend() -> Result<Box<dyn GitBackend>> {
+    git_backend(&load_config_file(&get_config_path())?)
+}
+
+/// Runs git and parses its output.
+pub struct CliBackend;
+
+#[async_trait]
+impl GitBackend for CliBackend {
+    fn name(&self) -> &'static str {
+        GIT_BACKEND_CLI
+    }
+
+    async fn metadata(&self, path: &Path) -> Result<Option<RepositoryMetadata>> {
+        get_repository_metadata(path).await
+    }
+
+    async fn status(&self, path: &Path) -> Result<WorkingTreeStatus> {
+        get_working_tree_status(path).await
+    }
+
+    async fn diff(&self, path: &Path, base: &str, head: &str) -> Result<BranchDiff> {
+        get_branch_diff(path, base, head).await
+    }
+
+    async fn log(&self, path: &Path, rev: &str, limit: usize) -> Result<Vec<BranchCommit>> {
+        get_log(path, rev, limit).await
+    }
+
+    async fn ahead_behind(&self, path: &Path, base: &str, head: &str) -> Result<(u32, u32)> {
+        count_ahead_behind(path, base, head).await
+    }
+
+    async fn refs(&self, path: &Path, names: &[String]) -> Result<Vec<RefDetails>> {
+        get_ref_details(path, names).await
+    }
+
+    async fn branch_refs(
+        &self,
+        path: &Path,
+        remote: &str,
+        merged_into: Option<&str>,
+    ) -> Result<Vec<BranchRef>> {
+        list_branch_refs(path, remote, merged_into).await
+    }
+
+    async fn resolve_ref(&self, path: &Path, candidates: &[String]) -> Result<Option<String>> {
+        resolve_first_ref(path, candidates).await
+    }
+
+    async fn remote_url(&self, path: &Path, remote: &str) -> Result<Option<String>> {
+        get_remote_url(path, remote).await
+    }
+}
+
+#[cfg(feature = "libgit2")]
+mod libgit2 {
+    use super::{GitBackend, GIT_BACKEND_LIBGIT2};
+    use crate::error::{GmuxError, Result};
+    use crate::git::{
+        BranchCommit, BranchDiff, BranchRef, BranchStatus, ChangedFile, RefDetails,
+        RepositoryMetadata, WorkingTreeStatus,
+    };
+    use async_trait::async_trait;
+    use git2::{Commit, Delta, DiffFindOptions, Patch, Repository, Sort, Status, StatusOptions};
+    use std::path::Path;
+
+    /// Reads repositories in-process, without spawning git.
+    pub struct Libgit2Backend;
+
+    /// Opens the repository at `path` and runs `read` on a blocking thread.
+    async fn read_repository<T, F>(path: &Path, read: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Repository) -> std::result::Result<T, git2::Error> + Send + 'static,
+    {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || read(&Repository::open(path)?))
+            .await
+            .map_err(anyhow::Error::from)?
+            .map_err(|error| GmuxError::Git(error.message().to_string()))
+    }
+
+    #[async_trait]
+    impl GitBackend for Libgit2Backend {
+        fn name(&self) -> &'static str {
+            GIT_BACKEND_LIBGIT2
+        }
+
+        async fn metadata(&self, path: &Path) -> Result<Option<RepositoryMetadata>> {
+            if !path.join(".git").exists() {
+                return Ok(None);
+            }
+            read_repository(path, |repo| {
+                let head = repo.find_reference("HEAD")?;
+                let current_branch = head
+                    .symbolic_target()
+                    .map(|target| target.trim_start_matches("refs/heads/").to_string());
+                let remote_head = remote_head(repo);
+                let detached = current_branch.is_none();
+                let current_branch = current_branch.unwrap_or_default();
+                Ok(Some(RepositoryMetadata {
+                    has_remote_head: remote_head.is_some(),
+                    default_branch: remote_head.unwrap_or_else(|| current_branch.clone()),
+                    current_branch,
+                    detached,
+                }))
+            })
+            .await
+        }
+
+        async fn status(&self, path: &Path) -> Result<WorkingTreeStatus> {
+            read_repository(path, working_tree_status).await
+        }
+
+        async fn diff(&self, path: &Path, base: &str, head: &str) -> Result<BranchDiff> {
+            let (base, head) = (base.to_string(), head.to_string());
+            read_repository(path, move |repo| branch_diff(repo, base, head)).await
+        }
+
+        async fn log(&self, path: &Path, rev: &str, limit: usize) -> Result<Vec<BranchCommit>> {
+            let rev = rev.to_string();
+            read_repository(path, move |repo| {
+                let mut walk = repo.revwalk()?;
+                walk.set_sorting(Sort::TIME)?;
+                walk.push(repo.revparse_single(&rev)?.peel_to_commit()?.id())?;
+                walk.take(limit)
+                    .map(|oid| branch_commit(&repo.find_commit(oid?)?))
+                    .collect()
+            })
+            .await
+        }
+
+        async fn ahead_behind(&self, path: &Path, base: &str, head: &str) -> Result<(u32, u32)> {
+            let (base, head) = (base.to_string(), head.to_string());
+            read_repository(path, move |repo| {
+                let base = repo.revparse_single(&base)?.peel_to_commit()?.id();
+                let head = repo.revparse_single(&head)?.peel_to_commit()?.id();
+                let (ahead, behind) = repo.graph_ahead_behind(head, base)?;
+                Ok((ahead as u32, behind as u32))
+            })
+            .await
+        }
+
+        async fn refs(&self, path: &Path, names: &[String]) -> Result<Vec<RefDetails>> {
+            let names = names.to_vec();
+            read_repository(path, move |repo| {
+                let mut refs = Vec::new();
+                for name in names {
+                    let Ok(reference) = repo.find_reference(&name) else {
+                        continue;
+                    };
+                    // A symbolic ref to a missing branch has no commit to report.
+                    let Ok(commit) = reference.peel_to_commit() else {
+                        continue;
+                    };
+                    refs.push(RefDetails {
+                        symref: reference.symbolic_target().map(str::to_string),
+                        hash: commit.id().to_string(),
+                        short_hash: short_hash(&commit)?,
+                        subject: commit.summary().unwrap_or_default().to_string(),
+                        committed_at: format_time(commit.time()),
+                        name,
+                    });
+                }
+                refs.sort_by(|a, b| a.name.cmp(&b.name));
+                Ok(refs)
+            })
+            .await
+        }
+
+        async fn branch_refs(
+            &self,
+            path: &Path,
+            remote: &str,
+            merged_into: Option<&str>,
+        ) -> Result<Vec<BranchRef>> {
+            let remote = remote.to_string();
+            let merged_into = merged_into.map(str::to_string);
+            read_repository(path, move |repo| {
+                let base = match &merged_into {
+                    Some(base) => Some(repo.revparse_single(base)?.peel_to_commit()?.id()),
+                    None => None,
+                };
+                let remote_prefix = format!("refs/remotes/{}/", remote);
+                let mut refs = Vec::new();
+                for glob in ["refs/heads/*".to_string(), format!("{}*", remote_prefix)] {
+                    for reference in repo.references_glob(&glob)? {
+                        let reference = reference?;
+                        let Some(refname) = reference.name().map(str::to_string) else {
+                            continue;
+                        };
+                        let (branch, branch_remote) = match refname.strip_prefix("refs/heads/") {
+                            Some(branch) => (branch.to_string(), None),
+                            None => (
+                                refname.trim_start_matches(&remote_prefix).to_string(),
+                                Some(remote.clone()),
+                            ),
+                        };
+                        if branch_remote.is_some() && branch == "HEAD" {
+                            continue;
+                        }
+                        let commit = reference.peel_to_commit()?;
+                        let merged = match base {
+                            Some(base) => Some(
+                                commit.id() == base
+                                    || repo.graph_descendant_of(base, commit.id())?,
+                            ),
+                            None => None,
+                        };
+                        refs.push((
+                            refname,
+                            BranchRef {
+                                name: match &branch_remote {
+                                    Some(remote) => format!("{}/{}", remote, branch),
+                                    None => branch.clone(),
+                                },
+                                branch,
+                                remote: branch_remote,
+                                committed_at: format_time(commit.time()),
+                                committed_unix: commit.time().seconds(),
+                                merged,
+                            },
+                        ));
+                    }
+                }
+                refs.sort_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(refs.into_iter().map(|(_, branch_ref)| branch_ref).collect())
+            })
+            .await
+        }
+
+        async fn resolve_ref(&self, path: &Path, candidates: &[String]) -> Result<Option<String>> {
+            let candidates = candidates.to_vec();
+            read_repository(path, move |repo| {
+                Ok(candidates.into_iter().find(|candidate| {
+                    repo.revparse_single(candidate)
+                        .and_then(|object| object.peel_to_commit())
+                        .is_ok()
+                }))
+            })
+            .await
+        }
+
+        async fn remote_url(&self, path: &Path, remote: &str) -> Result<Option<String>> {
+            let remote = remote.to_string();
+            read_repository(path, move |repo| {
+                Ok(repo
+                    .find_remote(&remote)
+                    .ok()
+                    .and_then(|remote| remote.url().map(str::to_string))
+                    .filter(|url| !url.is_empty()))
+            })
+            .await
+        }
+    }
+
+    /// The branch `refs/remotes/origin/HEAD` points at, if it is set.
+    fn remote_head(repo: &Repository) -> Option<String> {
+        let reference = repo.find_reference("refs/remotes/origin/HEAD").ok()?;
+        let target = reference.symbolic_target()?;
+        Some(
+            target
+                .trim_start_matches("refs/remotes/origin/")
+                .to_string(),
+        )
+    }
+
+    fn branch_status(repo: &Repository) -> std::result::Result<BranchStatus, git2::Error> {
+        let head = repo.find_reference("HEAD")?;
+        let Some(target) = head.symbolic_target().map(str::to_string) else {
+            return Ok(BranchStatus {
+                oid: head.target().map(|oid| oid.to_string()),
+                ..BranchStatus::default()
+            });
+        };
+        let mut branch = BranchStatus {
+            head: Some(target.trim_start_matches("refs/heads/").to_string()),
+            ..BranchStatus::default()
+        };
+        // An unborn branch has no commit, and so no upstream to compare with.
+        let Ok(local) = repo.refname_to_id(&target) else {
+            return Ok(branch);
+        };
+        branch.oid = Some(local.to_string());
+        let Ok(upstream) = repo.branch_upstream_name(&target) else {
+            return Ok(branch);
+        };
+        let upstream = upstream.as_str().unwrap_or_default().to_string();
+        branch.upstream = Some(
+            upstream
+                .strip_prefix("refs/remotes/")
+                .or_else(|| upstream.strip_prefix("refs/heads/"))
+                .unwrap_or(&upstream)
+                .to_string(),
+        );
+        if let Ok(upstream) = repo.refname_to_id(&upstream) {
+            let (ahead, behind) = repo.graph_ahead_behind(local, upstream)?;
+            branch.ahead = Some(ahead as u32);
+            branch.behind = Some(behind as u32);
+        }
+        Ok(branch)
+    }
+
+    /// Matches `git status --porcelain=v2`: tracked paths first, then untracked ones, with
+    /// untracked directories collapsed.
+    fn working_tree_status(
+        repo: &Repository,
+    ) -> std::result::Result<WorkingTreeStatus, git2::Error> {
+        let mut status = WorkingTreeStatus {
+            branch: branch_status(repo)?,
+            ..WorkingTreeStatus::default()
+        };
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .renames_head_to_index(true)
+            .renames_index_to_workdir(false);
+        let staged = Status::INDEX_NEW
+            | Status::INDEX_MODIFIED
+            | Status::INDEX_DELETED
+            | Status::INDEX_RENAMED
+            | Status::INDEX_TYPECHANGE;
+        let unstaged =
+            Status::WT_MODIFIED | Status::WT_DELETED | Status::WT_RENAMED | Status::WT_TYPECHANGE;
+
+        for entry in repo.statuses(Some(&mut options))?.iter() {
+            let flags = entry.status();
+            let path = entry
+                .head_to_index()
+                .or_else(|| entry.index_to_workdir())
+                .and_then(|delta| delta.new_file().path().map(|path| path.to_path_buf()))
+                .map(|path| path.to_string_lossy().to_string())
+                .or_else(|| entry.path().map(str::to_string))
+                .unwrap_or_default();
+            if flags.is_conflicted() {
+                status.changed.push(path.clone());
+                status.conflicted.push(path);
+            } else if flags.is_wt_new() {
+                status.untracked.push(path);
+            } else {
+                status.changed.push(path.clone());
+                if flags.intersects(staged) {
+                    status.staged.push(path.clone());
+                }
+                if flags.intersects(unstaged) {
+                    status.unstaged.push(path);
+                }
+            }
+        }
+        status.changed.extend(status.untracked.iter().cloned());
+        Ok(status)
+    }
+
+    fn branch_diff(
+        repo: &Repository,
+        base: String,
+        head: String,
+    ) -> std::result::Result<BranchDiff, git2::Error> {
+        let base_commit = repo.revparse_single(&base)?.peel_to_commit()?;
+        let head_commit = repo.revparse_single(&head)?.peel_to_commit()?;
+        let merge_base = repo.merge_base(base_commit.id(), head_commit.id())?;
+        let mut diff = repo.diff_tree_to_tree(
+            Some(&repo.find_commit(merge_base)?.tree()?),
+            Some(&head_commit.tree()?),
+            None,
+        )?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let mut files = Vec::new();
+        for (index, delta) in diff.deltas().enumerate() {
+            let status = match delta.status() {
+                Delta::Added => "added",
+                Delta::Deleted => "deleted",
+                Delta::Renamed => "renamed",
+                Delta::Copied => "copied",
+                Delta::Typechange => "type_changed",
+                Delta::Conflicted => "unmerged",
+                _ => "modified",
+            };
+            let file_path = |file: git2::DiffFile| {
+                file.path()
+                    .map(|path| path.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+            let (insertions, deletions) = match Patch::from_diff(&diff, index)? {
+                Some(patch) if !patch.delta().flags().is_binary() => {
+                    let (_, insertions, deletions) = patch.line_stats()?;
+                    (Some(insertions as u32), Some(deletions as u32))
+                }
+                _ => (None, None),
+            };
+            files.push(ChangedFile {
+                path: file_path(delta.new_file()),
+                old_path: matches!(status, "renamed" | "copied")
+                    .then(|| file_path(delta.old_file())),
+                status: status.to_string(),
+                insertions,
+                deletions,
+            });
+        }
+
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TIME)?;
+        walk.push(head_commit.id())?;
+        walk.hide(merge_base)?;
+        let commits = walk
+            .map(|oid| branch_commit(&repo.find_commit(oid?)?))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(BranchDiff {
+            base,
+            head,
+            merge_base: merge_base.to_string(),
+            insertions: files.iter().filter_map(|file| file.insertions).sum(),
+            deletions: files.iter().filter_map(|file| file.deletions).sum(),
+            files,
+            commits,
+        })
+    }
+
+    fn branch_commit(commit: &Commit) -> std::result::Result<BranchCommit, git2::Error> {
+        Ok(BranchCommit {
+            hash: commit.id().to_string(),
+            short_hash: short_hash(commit)?,
+            subject: commit.summary().unwrap_or_default().to_string(),
+            author: commit.author().name().unwrap_or_default().to_string(),
+            committed_at: format_time(commit.time()),
+        })
+    }
+
+    fn short_hash(commit: &Commit) -> std::result::Result<String, git2::Error> {
+        Ok(commit
+            .as_object()
+            .short_id()?
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Formats a commit time like git's `iso-strict` dates, in the committer's time zone.
+    pub(super) fn format_time(time: git2::Time) -> String {
+        let offset = i64::from(time.offset_minutes());
+        let local = time.seconds() + offset * 60;
+        let seconds = local.rem_euclid(86_400);
+        // Converts days since 1970-01-01 to a civil date, after Howard Hinnant's
+        // `civil_from_days`.
+        let days = local.div_euclid(86_400) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60,
+            if offset < 0 { '-' } else { '+' },
+            offset.abs() / 60,
+            offset.abs() % 60
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "libgit2")]
+    use crate::test_support::git;
+
+    #[test]
+    fn test_git_backend() {
+        let config = |git_backend: &str| Config {
+            git_backend: git_backend.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(git_backend(&config("cli")).unwrap().name(), "cli");
+        assert_eq!(
+            git_backend(&config("libgit2")).is_ok(),
+            cfg!(feature = "libgit2")
+        );
+        assert!(git_backend(&config("jgit")).is_err());
+    }
+
+    #[cfg(feature = "libgit2")]
+    #[test]
+    fn test_format_time() {
+        assert_eq!(
+            libgit2::format_time(git2::Time::new(0, 0)),
+            "1970-01-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            libgit2::format_time(git2::Time::new(1_709_251_199, -330)),
+            "2024-02-29T18:29:59-05:30"
+        );
+    }
+
+    /// Both backends read the same repository the same way.
+    #[cfg(feature = "libgit2")]
+    #[tokio::test]
+    async fn test_backends_agree() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let origin = temp_dir.path().join("origin");
+        std::fs::create_dir(&origin)?;
+        git(&origin, &["init", "-b", "main"]).await;
+        std::fs::write(origin.join("keep.txt"), "one\ntwo\n")?;
+        std::fs::write(origin.join("old.txt"), "rename me\n")?;
+        git(&origin, &["add", "."]).await;
+        git(&origin, &["commit", "-m", "Initial commit"]).await;
+        git(temp_dir.path(), &["clone", "-q", "origin", "clone"]).await;
+
+        let path = temp_dir.path().join("clone");
+        git(
+            &path,
+            &["switch", "-c", "feature", "--track", "origin/main"],
+        )
+        .await;
+        std::fs::write(path.join("keep.txt"), "one\ntwo\nthree\n")?;
+        std::fs::write(path.join("image.bin"), [0u8, 1, 2, 0])?;
+        git(&path, &["mv", "old.txt", "new.txt"]).await;
+        git(&path, &["add", "."]).await;
+        git(&path, &["commit", "-m", "Change files\n\nWith a body."]).await;
+        std::fs::write(path.join("keep.txt"), "changed\n")?;
+        std::fs::write(path.join("staged.txt"), "staged\n")?;
+        git(&path, &["add", "staged.txt"]).await;
+        std::fs::create_dir(path.join("scratch"))?;
+        std::fs::write(path.join("scratch").join("note.txt"), "untracked\n")?;
+        std::fs::write(path.join("untracked.txt"), "untracked\n")?;
+
+        let (cli, libgit2) = (CliBackend, libgit2::Libgit2Backend);
+        assert_eq!(
+            format!("{:?}", cli.metadata(&path).await?),
+            format!("{:?}", libgit2.metadata(&path).await?)
+        );
+        assert_eq!(cli.status(&path).await?, libgit2.status(&path).await?);
+        assert_eq!(
+            serde_json::to_value(cli.diff(&path, "main", "HEAD").await?)?,
+            serde_json::to_value(libgit2.diff(&path, "main", "HEAD").await?)?
+        );
+        assert_eq!(
+            serde_json::to_value(cli.log(&path, "HEAD", 5).await?)?,
+            serde_json::to_value(libgit2.log(&path, "HEAD", 5).await?)?
+        );
+        assert_eq!(
+            cli.ahead_behind(&path, "origin/main", "feature").await?,
+            libgit2
+                .ahead_behind(&path, "origin/main", "feature")
+                .await?
+        );
+        let names = vec![
+            "refs/heads/feature".to_string(),
+            "refs/remotes/origin/HEAD".to_string(),
+            "refs/heads/missing".to_string(),
+        ];
+        assert_eq!(
+            cli.refs(&path, &names).await?,
+            libgit2.refs(&path, &names).await?
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                cli.branch_refs(&path, "origin", Some("origin/main"))
+                    .await?
+            ),
+            format!(
+                "{:?}",
+                libgit2
+                    .branch_refs(&path, "origin", Some("origin/main"))
+                    .await?
+            )
+        );
+        let candidates = vec!["missing".to_string(), "origin/main".to_string()];
+        assert_eq!(
+            cli.resolve_ref(&path, &candidates).await?,
+            libgit2.resolve_ref(&path, &candidates).await?
+        );
+        assert_eq!(
+            cli.remote_url(&path, "origin").await?,
+            libgit2.remote_url(&path, "origin").await?
+        );
+        assert_eq!(libgit2.remote_url(&path, "upstream").await?, None);
+
+        git(&path, &["checkout", "-q", "--detach"]).await;
+        assert_eq!(cli.status(&path).await?, libgit2.status(&path).await?);
+        Ok(())
+    }
+}
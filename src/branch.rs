@@ -0,0 +1,512 @@
+use crate::diagnostics::MISSING_REMOTE_HEAD;
+use crate::error::{GmuxError, Result};
+use crate::git::{
+    get_default_branch, get_repository_metadata, git_stdout, has_uncommitted_changes,
+    list_local_branches, local_branch_exists, remote_branch_exists, resolve_branch_ref,
+    resolve_first_ref, RepositoryMetadata,
+};
+use crate::output::BranchResult;
+use crate::utils::repository_name;
+use clap::{Subcommand, ValueEnum};
+use std::path::Path;
+
+pub const STATUS_CREATED: &str = "created";
+pub const STATUS_SWITCHED: &str = "switched";
+pub const STATUS_DELETED: &str = "deleted";
+pub const STATUS_RENAMED: &str = "renamed";
+pub const STATUS_LISTED: &str = "listed";
+pub const STATUS_PRUNED: &str = "pruned";
+pub const STATUS_PLANNED: &str = "planned";
+pub const STATUS_SKIPPED: &str = "skipped";
+pub const STATUS_FAILED: &str = "failed";
+
+const REASON_DIRTY: &str = "working tree has uncommitted changes";
+pub const REASON_DEFAULT_BRANCH: &str = "default branch; pass --allow-default-branch to delete it";
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum BranchAction {
+    /// Create a branch without switching to it
+    Create {
+        /// Branch name
+        name: String,
+        /// Start point (defaults to each repository's default branch)
+        #[arg(long)]
+        from: Option<String>,
+    },
+    /// Switch to a branch, creating it from the default branch where it does not exist
+    Switch {
+        /// Branch name
+        name: String,
+        /// What to do in repositories with uncommitted changes
+        #[arg(long, value_enum, default_value_t = DirtyPolicy::Skip)]
+        on_dirty: DirtyPolicy,
+    },
+    /// Delete a local branch, and optionally the branch on origin
+    Delete {
+        /// Branch name
+        name: String,
+        /// Also delete the branch on origin
+        #[arg(long)]
+        remote: bool,
+        /// Delete the local branch even if it has not been merged
+        #[arg(short = 'D', long)]
+        force: bool,
+        /// Allow deleting each repository's default branch
+        #[arg(long)]
+        allow_default_branch: bool,
+    },
+    /// Rename a local branch
+    Rename {
+        /// Current branch name
+        old: String,
+        /// New branch name
+        new: String,
+    },
+    /// List local branches
+    List,
+    /// Delete local branches already merged into the default branch
+    PruneMerged {
+        /// Report the branches that would be deleted without deleting them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+impl BranchAction {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Create { .. } => "create",
+            Self::Switch { .. } => "switch",
+            Self::Delete { .. } => "delete",
+            Self::Rename { .. } => "rename",
+            Self::List => "list",
+            Self::PruneMerged { .. } => "prune-merged",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub enum DirtyPolicy {
+    /// Leave the repository alone and report it as skipped
+    #[default]
+    Skip,
+    /// Report the repository as failed
+    Fail,
+}
+
+impl BranchResult {
+    fn new(path: &Path, metadata: &RepositoryMetadata, branch: Option<&str>) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            branch: branch.map(str::to_string),
+            current_branch: Some(metadata.current_branch.clone()).filter(|b| !b.is_empty()),
+            status: STATUS_SKIPPED.to_string(),
+            reason: None,
+            branches: Vec::new(),
+        }
+    }
+
+    fn finish(mut self, status: &str, reason: Option<String>) -> Self {
+        self.status = status.to_string();
+        self.reason = reason;
+        self
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.status == STATUS_FAILED
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.status == STATUS_SKIPPED
+    }
+}
+
+/// Runs `action` in one repository. Returns `None` for directories that are not git
+/// repositories. Git failures are reported as a `failed` result rather than an error.
+pub async fn run_branch_action(path: &Path, action: &BranchAction) -> Option<BranchResult> {
+    let metadata = match get_repository_metadata(path).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return None,
+        Err(error) => {
+            return Some(BranchResult {
+                repository: repository_name(path),
+                path: path.display().to_string(),
+                branch: None,
+                current_branch: None,
+                status: STATUS_FAILED.to_string(),
+                reason: Some(error.format_error()),
+                branches: Vec::new(),
+            })
+        }
+    };
+
+    let branch = match action {
+        BranchAction::Create { name, .. }
+        | BranchAction::Switch { name, .. }
+        | BranchAction::Delete { name, .. } => Some(name.as_str()),
+        BranchAction::Rename { new, .. } => Some(new.as_str()),
+        BranchAction::List | BranchAction::PruneMerged { .. } => None,
+    };
+    let mut result = BranchResult::new(path, &metadata, branch);
+
+    let outcome = match action {
+        BranchAction::Create { name, from } => {
+            create_branch(path, &metadata, name, from.as_deref()).await
+        }
+        BranchAction::Switch { name, on_dirty } => {
+            switch_branch(path, &metadata, name, *on_dirty, &mut result).await
+        }
+        BranchAction::Delete {
+            name,
+            remote,
+            force,
+            allow_default_branch,
+        } => {
+            let options = DeleteOptions {
+                remote: *remote,
+                force: *force,
+                allow_default_branch: *allow_default_branch,
+            };
+            delete_branch(path, &metadata, name, options, &mut result).await
+        }
+        BranchAction::Rename { old, new } => rename_branch(path, old, new).await,
+        BranchAction::List => list_local_branches(path, None).await.map(|branches| {
+            result.branches = branches;
+            (STATUS_LISTED, None)
+        }),
+        BranchAction::PruneMerged { dry_run } => {
+            prune_merged_branches(path, &metadata, *dry_run, &mut result).await
+        }
+    };
+
+    Some(match outcome {
+        Ok((status, reason)) => result.finish(status, reason),
+        Err(error) => result.finish(STATUS_FAILED, Some(error.format_error())),
+    })
+}
+
+type Outcome = Result<(&'static str, Option<String>)>;
+
+fn skipped(reason: impl Into<String>) -> Outcome {
+    Ok((STATUS_SKIPPED, Some(reason.into())))
+}
+
+/// Resolves the start point for new branches, defaulting to the repository's default branch.
+async fn start_point(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    from: Option<&str>,
+) -> Result<String> {
+    let base = from.unwrap_or(&metadata.default_branch);
+    if base.is_empty() {
+        return Err(GmuxError::Git(MISSING_REMOTE_HEAD.to_string()));
+    }
+    resolve_branch_ref(path, base)
+        .await?
+        .ok_or_else(|| GmuxError::Git(format!("start point {} not found", base)))
+}
+
+async fn create_branch(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    name: &str,
+    from: Option<&str>,
+) -> Outcome {
+    if local_branch_exists(path, name).await? {
+        return skipped(format!("{} already exists", name));
+    }
+
+    let start = start_point(path, metadata, from).await?;
+    git_stdout(path, &["branch", "--no-track", name, &start]).await?;
+    Ok((STATUS_CREATED, None))
+}
+
+/// Switches to `name`, tracking `origin/<name>` if only the remote branch exists and
+/// otherwise creating it from the default branch.
+async fn switch_branch(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    name: &str,
+    on_dirty: DirtyPolicy,
+    result: &mut BranchResult,
+) -> Outcome {
+    if metadata.current_branch == name {
+        return skipped(format!("already on {}", name));
+    }
+
+    if has_uncommitted_changes(path).await? {
+        return match on_dirty {
+            DirtyPolicy::Skip => skipped(REASON_DIRTY),
+            DirtyPolicy::Fail => Ok((STATUS_FAILED, Some(REASON_DIRTY.to_string()))),
+        };
+    }
+
+    let status = if local_branch_exists(path, name).await? {
+        git_stdout(path, &["switch", name]).await?;
+        STATUS_SWITCHED
+    } else if resolve_first_ref(path, &[format!("refs/remotes/origin/{}", name)])
+        .await?
+        .is_some()
+    {
+        git_stdout(path, &["switch", "--track", &format!("origin/{}", name)]).await?;
+        STATUS_CREATED
+    } else {
+        let start = start_point(path, metadata, None).await?;
+        git_stdout(path, &["switch", "--no-track", "-c", name, &start]).await?;
+        STATUS_CREATED
+    };
+
+    result.current_branch = Some(name.to_string());
+    Ok((status, None))
+}
+
+struct DeleteOptions {
+    remote: bool,
+    force: bool,
+    allow_default_branch: bool,
+}
+
+/// Deletes `name` locally and, with `remote`, on origin. The default branch is refused unless
+/// explicitly allowed; without `origin/HEAD` it is asked from origin.
+async fn delete_branch(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    name: &str,
+    options: DeleteOptions,
+    result: &mut BranchResult,
+) -> Outcome {
+    let DeleteOptions {
+        remote,
+        force,
+        allow_default_branch,
+    } = options;
+    if !allow_default_branch {
+        let default_branch = if metadata.has_remote_head {
+            Some(metadata.default_branch.clone())
+        } else {
+            match get_default_branch(path, "origin").await {
+                Ok(default_branch) => default_branch,
+                // Without origin there is no remote branch to delete or to protect.
+                Err(_) if !remote => None,
+                Err(error) => return Err(error),
+            }
+        };
+        if default_branch.as_deref() == Some(name) {
+            return skipped(REASON_DEFAULT_BRANCH);
+        }
+    }
+
+    if local_branch_exists(path, name).await? {
+        if metadata.current_branch == name {
+            return Ok((
+                STATUS_FAILED,
+                Some(format!("{} is checked out; switch away first", name)),
+            ));
+        }
+        git_stdout(path, &["branch", if force { "-D" } else { "-d" }, name]).await?;
+        result.branches.push(name.to_string());
+    }
+
+    if remote && remote_branch_exists(path, "origin", name).await? {
+        git_stdout(path, &["push", "origin", "--delete", name]).await?;
+        result.branches.push(format!("origin/{}", name));
+    }
+
+    if result.branches.is_empty() {
+        return skipped(format!("{} not found", name));
+    }
+    Ok((STATUS_DELETED, None))
+}
+
+async fn rename_branch(path: &Path, old: &str, new: &str) -> Outcome {
+    if !local_branch_exists(path, old).await? {
+        return skipped(format!("{} not found", old));
+    }
+    if local_branch_exists(path, new).await? {
+        return Ok((STATUS_FAILED, Some(format!("{} already exists", new))));
+    }
+
+    git_stdout(path, &["branch", "-m", old, new]).await?;
+    Ok((STATUS_RENAMED, None))
+}
+
+/// Deletes local branches reachable from the default branch, never the default or current
+/// branch. Requires `origin/HEAD`, since the current-branch fallback would prune against
+/// whatever happens to be checked out.
+async fn prune_merged_branches(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    dry_run: bool,
+    result: &mut BranchResult,
+) -> Outcome {
+    if !metadata.has_remote_head {
+        return skipped("default branch unknown; run `git remote set-head origin --auto`");
+    }
+
+    let base = start_point(path, metadata, None).await?;
+    let merged: Vec<String> = list_local_branches(path, Some(&base))
+        .await?
+        .into_iter()
+        .filter(|branch| branch != &metadata.default_branch && branch != &metadata.current_branch)
+        .collect();
+    if merged.is_empty() {
+        return skipped("no merged branches");
+    }
+
+    if dry_run {
+        result.branches = merged;
+        return Ok((STATUS_PLANNED, None));
+    }
+
+    // `-d` only accepts branches merged into HEAD or their upstream, so force deletion of
+    // branches already verified as merged into the default branch.
+    for branch in merged {
+        git_stdout(path, &["branch", "-D", &branch]).await?;
+        result.branches.push(branch);
+    }
+    Ok((STATUS_PRUNED, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, setup_cloned_repo};
+
+    fn switch(name: &str, on_dirty: DirtyPolicy) -> BranchAction {
+        BranchAction::Switch {
+            name: name.to_string(),
+            on_dirty,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_switch_branch() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        let create = BranchAction::Create {
+            name: "feature".to_string(),
+            from: None,
+        };
+
+        let result = run_branch_action(&repo_path, &create).await.unwrap();
+        assert_eq!(result.status, STATUS_CREATED);
+        let result = run_branch_action(&repo_path, &create).await.unwrap();
+        assert!(result.is_skipped());
+
+        let result = run_branch_action(&repo_path, &switch("feature", DirtyPolicy::Skip))
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_SWITCHED);
+        assert_eq!(result.current_branch.as_deref(), Some("feature"));
+
+        let result = run_branch_action(&repo_path, &switch("other", DirtyPolicy::Skip))
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_CREATED);
+        let current = git_stdout(&repo_path, &["symbolic-ref", "--short", "HEAD"]).await?;
+        assert_eq!(current.trim(), "other");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_switch_branch_named_like_a_tag() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["tag", "release"]).await;
+
+        // Only a remote branch is tracked; a tag with the same name is not one.
+        let result = run_branch_action(&repo_path, &switch("release", DirtyPolicy::Skip))
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_CREATED);
+        let upstream =
+            git_stdout(&repo_path, &["config", "--get-all", "branch.release.merge"]).await;
+        assert!(upstream.is_err());
+
+        git(
+            &repo_path,
+            &["push", "origin", "HEAD:refs/heads/remote-only"],
+        )
+        .await;
+        let result = run_branch_action(&repo_path, &switch("remote-only", DirtyPolicy::Skip))
+            .await
+            .unwrap();
+        assert_eq!(result.status, STATUS_CREATED);
+        let upstream =
+            git_stdout(&repo_path, &["rev-parse", "--abbrev-ref", "@{upstream}"]).await?;
+        assert_eq!(upstream.trim(), "origin/remote-only");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_switch_dirty_repository() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        std::fs::write(repo_path.join("README.md"), "changed\n")?;
+
+        let result = run_branch_action(&repo_path, &switch("feature", DirtyPolicy::Skip))
+            .await
+            .unwrap();
+        assert!(result.is_skipped());
+        assert_eq!(result.reason.as_deref(), Some(REASON_DIRTY));
+
+        let result = run_branch_action(&repo_path, &switch("feature", DirtyPolicy::Fail))
+            .await
+            .unwrap();
+        assert!(result.is_failed());
+        assert!(!local_branch_exists(&repo_path, "feature").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_default_branch() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["switch", "-c", "feature"]).await;
+        let delete = |allow_default_branch| BranchAction::Delete {
+            name: "main".to_string(),
+            remote: true,
+            force: false,
+            allow_default_branch,
+        };
+
+        let result = run_branch_action(&repo_path, &delete(false)).await.unwrap();
+        assert!(result.is_skipped());
+        assert_eq!(result.reason.as_deref(), Some(REASON_DEFAULT_BRANCH));
+
+        // Without `origin/HEAD`, origin is asked for its default branch.
+        git(&repo_path, &["remote", "set-head", "origin", "--delete"]).await;
+        let result = run_branch_action(&repo_path, &delete(false)).await.unwrap();
+        assert_eq!(result.reason.as_deref(), Some(REASON_DEFAULT_BRANCH));
+        assert!(local_branch_exists(&repo_path, "main").await?);
+
+        // Git itself refuses to delete the branch a remote's HEAD points at.
+        let origin = repo_path.parent().unwrap().join("origin.git");
+        git(&origin, &["config", "receive.denyDeleteCurrent", "ignore"]).await;
+        let result = run_branch_action(&repo_path, &delete(true)).await.unwrap();
+        assert_eq!(result.status, STATUS_DELETED);
+        assert!(!local_branch_exists(&repo_path, "main").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prune_merged_branches() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["branch", "merged"]).await;
+        git(&repo_path, &["switch", "-c", "unmerged"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "Work"]).await;
+        git(&repo_path, &["switch", "main"]).await;
+
+        let action = BranchAction::PruneMerged { dry_run: true };
+        let result = run_branch_action(&repo_path, &action).await.unwrap();
+        assert_eq!(result.status, STATUS_PLANNED);
+        assert_eq!(result.branches, vec!["merged"]);
+        assert!(local_branch_exists(&repo_path, "merged").await?);
+
+        let action = BranchAction::PruneMerged { dry_run: false };
+        let result = run_branch_action(&repo_path, &action).await.unwrap();
+        assert_eq!(result.status, STATUS_PRUNED);
+        assert_eq!(
+            list_local_branches(&repo_path, None).await?,
+            vec!["main", "unmerged"]
+        );
+        Ok(())
+    }
+}
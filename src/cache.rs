@@ -0,0 +1,222 @@
+use crate::config::{get_cache_dir, Config};
+use crate::error::Result;
+use crate::github::Repository;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const REPOSITORY_CACHE_DIR: &str = "repositories";
+
+/// How repository listings should interact with the on-disk cache.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CacheMode {
+    /// Serve fresh cache entries, revalidating stale ones with conditional requests.
+    #[default]
+    Default,
+    /// Ignore any cached listing and fetch everything again.
+    Refresh,
+    /// Never touch the network; fail if nothing is cached.
+    Offline,
+}
+
+impl CacheMode {
+    pub fn from_flags(refresh: bool, offline: bool) -> Self {
+        if offline {
+            Self::Offline
+        } else if refresh {
+            Self::Refresh
+        } else {
+            Self::Default
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositoryCache {
+    pub organization: String,
+    pub fetched_at: u64,
+    pub pages: Vec<CachedPage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedPage {
+    pub etag: Option<String>,
+    pub repositories: Vec<Repository>,
+}
+
+impl RepositoryCache {
+    pub fn new(organization: &str, pages: Vec<CachedPage>) -> Self {
+        Self {
+            organization: organization.to_string(),
+            fetched_at: unix_now(),
+            pages,
+        }
+    }
+
+    pub fn is_fresh(&self, ttl_secs: u64) -> bool {
+        unix_now().saturating_sub(self.fetched_at) < ttl_secs
+    }
+
+    pub fn etag_for_page(&self, index: usize) -> Option<&str> {
+        self.pages.get(index)?.etag.as_deref()
+    }
+
+    pub fn repositories(&self) -> Vec<Repository> {
+        self.pages
+            .iter()
+            .flat_map(|page| page.repositories.iter().cloned())
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
+/// The cache file for an organisation's listing. Listings depend on who the token belongs to
+/// and on the sort and paging settings, so a hash of those is part of the name; the token
+/// itself is never written to disk.
+pub fn get_repository_cache_path(org: &str, config: &Config) -> PathBuf {
+    get_cache_dir().join(REPOSITORY_CACHE_DIR).join(format!(
+        "{}-{}.json",
+        org.to_lowercase(),
+        listing_key(config)
+    ))
+}
+
+fn listing_key(config: &Config) -> String {
+    let digest = Sha256::new()
+        .chain_update(&config.github_token)
+        .chain_update([0])
+        .chain_update(&config.sort)
+        .chain_update([0])
+        .chain_update(&config.direction)
+        .chain_update([0])
+        .chain_update([config.per_page])
+        .finalize();
+    digest[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Loads a cached listing. Unreadable or outdated cache files are treated as a miss.
+pub fn load_repository_cache(path: &Path) -> Option<RepositoryCache> {
+    let content = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+/// All cached listings, used for shell completion.
+pub fn load_all_repository_caches() -> Vec<RepositoryCache> {
+    let Ok(entries) = fs::read_dir(get_cache_dir().join(REPOSITORY_CACHE_DIR)) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| load_repository_cache(&entry.path()))
+        .collect()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn repository(name: &str) -> Repository {
+        Repository {
+            name: name.to_string(),
+            private: false,
+            topics: Vec::new(),
+            language: None,
+        }
+    }
+
+    #[test]
+    fn test_cache_mode_from_flags() {
+        assert_eq!(CacheMode::from_flags(false, false), CacheMode::Default);
+        assert_eq!(CacheMode::from_flags(true, false), CacheMode::Refresh);
+        assert_eq!(CacheMode::from_flags(false, true), CacheMode::Offline);
+    }
+
+    #[test]
+    fn test_cache_freshness() {
+        let mut cache = RepositoryCache::new("org", Vec::new());
+        assert!(cache.is_fresh(60));
+        assert!(!cache.is_fresh(0));
+
+        cache.fetched_at = cache.fetched_at.saturating_sub(120);
+        assert!(!cache.is_fresh(60));
+    }
+
+    #[test]
+    fn test_save_load_cache() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let path = temp_dir.path().join("nested").join("org.json");
+
+        let cache = RepositoryCache::new(
+            "org",
+            vec![
+                CachedPage {
+                    etag: Some("\"abc\"".to_string()),
+                    repositories: vec![repository("one"), repository("two")],
+                },
+                CachedPage {
+                    etag: None,
+                    repositories: vec![repository("three")],
+                },
+            ],
+        );
+        cache.save(&path)?;
+
+        let loaded = load_repository_cache(&path).unwrap();
+        assert_eq!(loaded.organization, "org");
+        assert_eq!(loaded.etag_for_page(0), Some("\"abc\""));
+        assert_eq!(loaded.etag_for_page(1), None);
+        assert_eq!(loaded.etag_for_page(2), None);
+        let names: Vec<_> = loaded.repositories().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, vec!["one", "two", "three"]);
+
+        fs::write(&path, "not json")?;
+        assert!(load_repository_cache(&path).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_repository_cache_path() {
+        let config = Config {
+            github_token: "token-a".to_string(),
+            ..Default::default()
+        };
+        let path = get_repository_cache_path("Org", &config);
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with("org-") && name.ends_with(".json"));
+        assert!(!name.contains("token-a"));
+        assert_eq!(path, get_repository_cache_path("org", &config));
+
+        let other_token = Config {
+            github_token: "token-b".to_string(),
+            ..config.clone()
+        };
+        let other_sort = Config {
+            sort: "created".to_string(),
+            ..config.clone()
+        };
+        assert_ne!(path, get_repository_cache_path("org", &other_token));
+        assert_ne!(path, get_repository_cache_path("org", &other_sort));
+    }
+}
@@ -1,25 +1,50 @@
+use crate::apply::{self, ApplyOptions};
+use crate::backend::{configured_git_backend, CliBackend, GitBackend};
+use crate::branch::{self, BranchAction};
+use crate::cache::CacheMode;
+use crate::commit::{self, CommitOptions};
 use crate::config::{
-    get_config_dir, get_config_path, load_config, load_config_for_setup,
+    get_config_dir, get_config_path, load_config, load_config_for_setup, load_workspace_config,
     save_github_token_to_secure_store, Config,
 };
+use crate::doctor;
 use crate::error::{GmuxError, Result};
-use crate::git::{get_diff_file_names, get_repository_metadata};
+use crate::git::{
+    get_lfs_status, get_repository_metadata, get_submodule_status, operation_in_progress,
+    parse_github_remote, BranchDiff, GitDirs, RepositoryMetadata, WorkingTreeStatus,
+};
 use crate::github::GitHubClient;
+use crate::grep::{self, GrepOptions};
 use crate::output::{
-    print_json, CloneBatchResult, CloneResult, CommandBatchResult, InspectCommitResult,
+    print_json, print_json_line, print_output, print_query_output, tag_record, ApplyBatchResult,
+    ApplyResult, BranchBatchResult, BranchResult, CloneBatchResult, CloneResult,
+    CommandBatchResult, CommitBatchResult, CommitResult, DoctorCheck, DoctorResult,
+    GrepBatchResult, GrepRepositoryResult, InitResult, InspectBranchResult, InspectCommitResult,
     InspectRepositoryResult, InspectWorkspaceResult, OutputFormat, PullRequestBatchResult,
-    PullRequestPlan, RepositoryCommandResult, RepositoryErrorResult,
+    PullRequestPlan, PushBatchResult, PushResult, ReplaceBatchResult, ReplaceRepositoryResult,
+    RepositoryCommandResult, RepositoryErrorResult, RepositoryListResult, RepositorySkippedResult,
+    SetupResult, SnapshotBatchResult, SnapshotListResult, SnapshotRepositoryResult,
+    SnapshotSummary,
 };
+use crate::pr;
+use crate::push::{self, PushOptions};
+use crate::query::QueryOptions;
+use crate::replace::{self, ReplaceOptions};
+use crate::report::ReportOptions;
+use crate::schema::{self, SchemaCommand};
+use crate::snapshot::{self, Snapshot, SnapshotAction};
 use crate::utils::{
-    for_each_repository, get_template_content, repository_paths, run_command_capture,
+    for_each_repository, get_template_content, repository_name, repository_paths,
+    run_command_capture, RepositoryOutcome, RepositoryStatus,
 };
 use colored::Colorize;
 use futures::stream::{self, StreamExt};
 use indicatif::{ProgressBar, ProgressStyle};
 use regex::Regex;
+use serde::Serialize;
+use std::collections::HashMap;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use tokio::process::Command;
 
 pub async fn init(directory: Option<String>, output: OutputFormat) -> Result<()> {
     let dir = directory.map_or_else(
@@ -31,12 +56,15 @@ pub async fn init(directory: Option<String>, output: OutputFormat) -> Result<()>
     if !template_path.exists() {
         std::fs::write(&template_path, crate::config::DEFAULT_PR_TEMPLATE)?;
     }
-    if output == OutputFormat::Json {
-        return print_json(&serde_json::json!({
-            "directory": dir,
-            "template_path": template_path,
-            "status": "initialized"
-        }));
+    if output != OutputFormat::Text {
+        return print_output(
+            &InitResult {
+                directory: dir.display().to_string(),
+                template_path: template_path.display().to_string(),
+                status: "initialized".to_string(),
+            },
+            output,
+        );
     }
     println!("{}", "✨ gmux successfully initialised! ✨".green());
     println!(
@@ -46,7 +74,47 @@ pub async fn init(directory: Option<String>, output: OutputFormat) -> Result<()>
     Ok(())
 }
 
-pub async fn inspect(filter: Option<String>, all: bool, output: OutputFormat) -> Result<()> {
+/// What `inspect` collects beyond the basic repository state.
+struct InspectOptions {
+    diff: bool,
+    branches: bool,
+    stale_days: u64,
+    /// Used to look up open pull requests for the branch report.
+    client: Option<GitHubClient>,
+    backend: Box<dyn GitBackend>,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn inspect(
+    filter: Option<String>,
+    all: bool,
+    diff: bool,
+    branches: bool,
+    stale_days: u64,
+    concurrency: usize,
+    query: QueryOptions,
+    output: OutputFormat,
+) -> Result<()> {
+    query.validate()?;
+    // Open pull requests are best-effort; the report works offline without a token.
+    let client = if branches {
+        load_config(&get_config_path())
+            .and_then(GitHubClient::new)
+            .ok()
+    } else {
+        None
+    };
+    let options = InspectOptions {
+        diff,
+        branches,
+        stale_days,
+        client,
+        backend: configured_git_backend().unwrap_or_else(|error| {
+            eprintln!("{}", "warning: using the git CLI backend".yellow());
+            eprintln!("{}", error.format_error());
+            Box::new(CliBackend)
+        }),
+    };
     let workspace = std::env::current_dir()?;
     let mut paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
     if crate::git::is_git_directory(&workspace).await
@@ -54,22 +122,51 @@ pub async fn inspect(filter: Option<String>, all: bool, output: OutputFormat) ->
     {
         paths.insert(0, workspace.clone().into_boxed_path());
     }
+    let inspect = |path: Box<Path>| {
+        let options = &options;
+        async move { inspect_repository(path.as_ref(), options).await }
+    };
+    let selected = |inspected: &InspectRepositoryResult| -> Result<bool> {
+        Ok((all || inspected.is_git) && query.matches(inspected)?)
+    };
+
+    // ndjson records are printed as each repository finishes, in whatever order that is.
+    if output == OutputFormat::Ndjson {
+        let mut inspected = stream::iter(paths)
+            .map(inspect)
+            .buffer_unordered(concurrency);
+        while let Some(inspected) = inspected.next().await {
+            if selected(&inspected)? {
+                print_json_line(&query.project(&serde_json::to_value(&inspected)?))?;
+            }
+        }
+        return Ok(());
+    }
+
+    // `buffered` yields results in input order, so the report stays stable.
+    let inspected: Vec<InspectRepositoryResult> = stream::iter(paths)
+        .map(inspect)
+        .buffered(concurrency)
+        .collect()
+        .await;
     let mut repositories = Vec::new();
 
-    for path in paths {
-        let inspected = inspect_repository(path.as_ref()).await;
-        if !all && !inspected.is_git {
-            continue;
+    for inspected in inspected {
+        if selected(&inspected)? {
+            repositories.push(inspected);
         }
-        repositories.push(inspected);
     }
 
-    if output == OutputFormat::Json {
-        return print_json(&InspectWorkspaceResult {
-            workspace: workspace.display().to_string(),
-            count: repositories.len(),
-            repositories,
-        });
+    if output != OutputFormat::Text || !query.fields.is_empty() {
+        return print_query_output(
+            &InspectWorkspaceResult {
+                workspace: workspace.display().to_string(),
+                count: repositories.len(),
+                repositories,
+            },
+            &query,
+            output,
+        );
     }
 
     println!(
@@ -94,7 +191,11 @@ pub async fn inspect(filter: Option<String>, all: bool, output: OutputFormat) ->
             continue;
         }
 
-        let branch = repo.current_branch.as_deref().unwrap_or("-");
+        let branch = match (repo.detached, repo.current_branch.as_deref()) {
+            (Some(true), _) => "detached",
+            (_, Some(branch)) => branch,
+            _ => "-",
+        };
         let dirty = if repo.dirty.unwrap_or(false) {
             "dirty".yellow()
         } else {
@@ -114,11 +215,85 @@ pub async fn inspect(filter: Option<String>, all: bool, output: OutputFormat) ->
             ahead_behind.dimmed()
         );
 
-        if !repo.changed_files.is_empty() {
+        for (label, files) in [
+            ("conflicted:".red(), &repo.conflicted_files),
+            ("staged:".green(), &repo.staged_files),
+            ("unstaged:".yellow(), &repo.unstaged_files),
+            ("untracked:".dimmed(), &repo.untracked_files),
+        ] {
+            if !files.is_empty() {
+                println!("  {} {}", label, files.join(", "));
+            }
+        }
+
+        let mut notes = Vec::new();
+        if let Some(operation) = &repo.operation_in_progress {
+            notes.push(format!("{} in progress", operation).red().to_string());
+        }
+        match repo.stash_count {
+            Some(1) => notes.push("1 stash".to_string()),
+            Some(count) if count > 1 => notes.push(format!("{} stashes", count)),
+            _ => {}
+        }
+        if repo.shallow == Some(true) {
+            notes.push("shallow".to_string());
+        }
+        let outdated_submodules = repo
+            .submodules
+            .iter()
+            .filter(|submodule| submodule.status != "current")
+            .count();
+        match (repo.submodules.len(), outdated_submodules) {
+            (0, _) => {}
+            (count, 0) => notes.push(format!("{} submodules", count)),
+            (count, outdated) => {
+                notes.push(format!("{} submodules, {} not current", count, outdated))
+            }
+        }
+        match &repo.lfs {
+            Some(lfs) if !lfs.installed => notes.push("LFS (git-lfs not installed)".to_string()),
+            Some(lfs) => notes.push(match lfs.missing {
+                Some(missing) if missing > 0 => format!("LFS, {} files not downloaded", missing),
+                _ => "LFS".to_string(),
+            }),
+            None => {}
+        }
+        if !notes.is_empty() {
+            println!("  {}", notes.join(", "));
+        }
+
+        if let Some(diff) = &repo.diff {
             println!(
-                "  {} {}",
-                "changed:".yellow(),
-                repo.changed_files.join(", ")
+                "  {} +{} -{} in {} files, {} commits since {}",
+                "diff:".cyan(),
+                diff.insertions,
+                diff.deletions,
+                diff.files.len(),
+                diff.commits.len(),
+                diff.base
+            );
+        }
+
+        for branch in repo.branches.iter().flatten() {
+            let marker = if branch.current { "*" } else { " " };
+            let status = match branch.status.as_str() {
+                BRANCH_STATUS_MERGED => branch.status.green(),
+                BRANCH_STATUS_OPEN_PR => branch.status.cyan(),
+                BRANCH_STATUS_STALE => branch.status.yellow(),
+                _ => branch.status.normal(),
+            };
+            let ahead_behind = match (branch.ahead, branch.behind) {
+                (Some(ahead), Some(behind)) => format!("+{ahead} -{behind}"),
+                _ => "-".to_string(),
+            };
+            println!(
+                "  {} {:<40} {:<8} {:>10} {:>5}d {}",
+                marker,
+                branch.name,
+                status,
+                ahead_behind.dimmed(),
+                branch.age_days,
+                branch.pull_request.as_deref().unwrap_or_default().dimmed()
             );
         }
 
@@ -144,399 +319,1297 @@ pub async fn cmd(
     command: Vec<String>,
     filter: Option<String>,
     concurrency: usize,
+    query: QueryOptions,
+    report: ReportOptions,
     output: OutputFormat,
 ) -> Result<()> {
-    let command_str = command.join(" ");
-    if output == OutputFormat::Json {
-        let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
-        let results: Vec<std::result::Result<RepositoryCommandResult, RepositoryErrorResult>> =
-            stream::iter(paths)
-                .map(|path| {
-                    let command_str = command_str.clone();
-                    async move { run_shell_command_for_json(path.as_ref(), &command_str).await }
+    query.validate()?;
+    let command_label = command.join(" ");
+    let command_str = command_label.clone();
+    let outcomes = run_in_repositories(
+        &command_label,
+        move |path| {
+            let command_str = command_str.clone();
+            Box::pin(async move {
+                let output = run_command_capture(&["sh", "-c", &command_str], &path).await?;
+                Ok(RepositoryStatus::Completed {
+                    command: command_str,
+                    output,
                 })
-                .buffer_unordered(concurrency)
-                .collect()
-                .await;
-
-        let mut command_results = Vec::new();
-        let mut errors = Vec::new();
-        for result in results {
-            match result {
-                Ok(result) => command_results.push(result),
-                Err(error) => errors.push(error),
+            })
+        },
+        filter.as_deref(),
+        concurrency,
+        &query,
+        output,
+    )
+    .await?;
+    render_command_outcomes(command_label, outcomes, &query, &report, output)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn pr(
+    title: Option<String>,
+    yes: bool,
+    no_input: bool,
+    dry_run: bool,
+    base: Option<String>,
+    head: Option<String>,
+    api: bool,
+    upstream: bool,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let title = if let Some(title) = title {
+        title
+    } else if no_input || output != OutputFormat::Text {
+        return Err(GmuxError::Validation(
+            "PR title is required in non-interactive mode".to_string(),
+        ));
+    } else {
+        print!("{}", "Enter PR title: ".bright_white());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        input.trim().to_string()
+    };
+
+    let Some(template_content) = get_template_content().await? else {
+        return Err(GmuxError::Validation(
+            "PR template not found. Run 'gmux init' first.".to_string(),
+        ));
+    };
+
+    if output == OutputFormat::Text {
+        println!(
+            "🚀 Starting PR command with title: {}",
+            title.bright_white().bold()
+        );
+    }
+
+    let workspace_config = load_workspace_config(&std::env::current_dir()?)?;
+    // Without --api or --upstream the token is optional: it is only used to find default
+    // branches for repositories without `origin/HEAD`.
+    let client = if api || upstream {
+        Some(GitHubClient::new(load_config(&get_config_path())?)?)
+    } else {
+        load_config(&get_config_path())
+            .ok()
+            .and_then(|config| GitHubClient::new(config).ok())
+    };
+    let options = pr::PlanOptions {
+        title: title.clone(),
+        template_content,
+        base,
+        head,
+        base_overrides: workspace_config.base_branches,
+        upstream,
+        client,
+    };
+
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let (mut plans, mut errors) = pr::plan_pull_requests(paths, &options, concurrency).await;
+
+    if dry_run {
+        pr::mark_planned(&mut plans);
+    } else {
+        let interactive = !yes && !no_input && output == OutputFormat::Text;
+        if interactive {
+            // The review is only shown once planning has finished for every repository.
+            let stdin = io::stdin();
+            let confirmed = pr::review_pull_requests(
+                &mut plans,
+                &mut stdin.lock(),
+                &mut io::stdout(),
+                pr::edit_in_editor,
+            )?;
+            if !confirmed {
+                println!("{}", "Review aborted; nothing was pushed.".yellow());
+                return Ok(());
+            }
+        } else if !yes {
+            for plan in plans.iter_mut().filter(|plan| plan.needs_push()) {
+                plan.skip(pr::REASON_NOT_PUSHED);
             }
         }
 
-        let succeeded = command_results.iter().filter(|r| r.exit_code == 0).count();
-        let failed = command_results.len().saturating_sub(succeeded) + errors.len();
-        return print_json(&CommandBatchResult {
-            command: command_str,
-            succeeded,
-            failed,
-            results: command_results,
-            errors,
-        });
+        pr::push_pending_branches(&mut plans, &mut errors, concurrency).await;
+        if let (true, Some(client)) = (api, &options.client) {
+            pr::create_pull_requests(&mut plans, &mut errors, client, concurrency).await;
+        } else if output == OutputFormat::Text {
+            pr::open_pull_requests(&mut plans);
+        }
     }
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
-    pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    if output != OutputFormat::Text {
+        return print_output(
+            &PullRequestBatchResult {
+                title,
+                dry_run,
+                plans,
+                errors,
+            },
+            output,
+        );
+    }
 
-    for_each_repository(
+    render_pull_request_plans(&plans, &errors);
+    Ok(())
+}
+
+fn render_pull_request_plans(plans: &[PullRequestPlan], errors: &[RepositoryErrorResult]) {
+    for plan in plans {
+        println!("\n{}", "─".repeat(80).dimmed());
+        println!(
+            "📦 Processing repository: {}",
+            plan.path.bright_white().bold()
+        );
+
+        if plan.status == pr::STATUS_SKIPPED {
+            println!(
+                "⏭️  Skipping {}: {}",
+                plan.repository.dimmed(),
+                plan.reason.as_deref().unwrap_or_default()
+            );
+            continue;
+        }
+
+        let head = plan.head.as_deref().unwrap_or_default();
+        if plan.head_pushed {
+            println!("✅ Branch on remote: {}", head.green().bold());
+        } else {
+            println!(
+                "⏭️  Dry run: branch {} has not been pushed",
+                head.bright_yellow().bold()
+            );
+        }
+
+        let owner = plan.owner.as_deref().unwrap_or_default();
+        let repo = plan.repo.as_deref().unwrap_or_default();
+        if plan.status == pr::STATUS_CREATED {
+            println!(
+                "{} Created PR for {}/{}: {}",
+                "🌐".cyan().bold(),
+                owner.bright_white().bold(),
+                repo.bright_white().bold(),
+                plan.url.as_deref().unwrap_or_default()
+            );
+        } else if plan.status == pr::STATUS_OPENED {
+            println!(
+                "{} Opening PR creation link for {}/{}\n{}",
+                "🌐".cyan().bold(),
+                owner.bright_white().bold(),
+                repo.bright_white().bold(),
+                "(A browser window will open with your PR draft)".dimmed()
+            );
+        } else if let Some(url) = &plan.url {
+            println!(
+                "{} PR creation link for {}/{}\n{}",
+                "🌐".cyan().bold(),
+                owner.bright_white().bold(),
+                repo.bright_white().bold(),
+                url
+            );
+        }
+    }
+
+    for error in errors {
+        println!("\n{}", "─".repeat(80).dimmed());
+        println!(
+            "❌ {} ({}): {}",
+            error.repository.red().bold(),
+            error.path.dimmed(),
+            error.error
+        );
+    }
+    println!("{}", "─".repeat(80).dimmed());
+}
+pub async fn git(
+    command: Vec<String>,
+    filter: Option<String>,
+    concurrency: usize,
+    query: QueryOptions,
+    report: ReportOptions,
+    output: OutputFormat,
+) -> Result<()> {
+    query.validate()?;
+    let command_label = format!("git {}", command.join(" "));
+    let outcomes = run_in_repositories(
+        &command_label,
         move |path| {
-            let command_str = command_str.clone();
-            let pb = pb.clone();
+            let command = command.clone();
             Box::pin(async move {
-                let repo_name = path.file_name().unwrap().to_string_lossy().bright_white();
-                let cmd_line = command_str.bright_white();
-                pb.set_message(format!("Running command in {}", repo_name));
+                let Some(metadata) = get_repository_metadata(&path).await? else {
+                    return Ok(RepositoryStatus::Skipped(
+                        "not a git repository".to_string(),
+                    ));
+                };
+                let mut cmd = command;
+                for arg in &mut cmd {
+                    *arg = arg.replace("@default", &metadata.default_branch);
+                    *arg = arg.replace("@current", &metadata.current_branch);
+                }
+                let mut full_cmd = vec!["git"];
+                full_cmd.extend(cmd.iter().map(|s| s.as_str()));
+                let output = run_command_capture(&full_cmd, &path).await?;
+                Ok(RepositoryStatus::Completed {
+                    command: format!("git {}", cmd.join(" ")),
+                    output,
+                })
+            })
+        },
+        filter.as_deref(),
+        concurrency,
+        &query,
+        output,
+    )
+    .await?;
+    render_command_outcomes(command_label, outcomes, &query, &report, output)
+}
+
+/// Runs `f` in each repository, with a spinner in text mode, and returns the outcomes that
+/// match the `--where` predicates. Text output and `ndjson` records are printed as each
+/// repository finishes.
+async fn run_in_repositories<F>(
+    command: &str,
+    f: F,
+    filter: Option<&str>,
+    concurrency: usize,
+    query: &QueryOptions,
+    output: OutputFormat,
+) -> Result<Vec<RepositoryOutcome>>
+where
+    F: Fn(
+        Box<Path>,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = anyhow::Result<RepositoryStatus>> + Send + 'static>,
+    >,
+{
+    let pb = ProgressBar::new_spinner();
+    if output == OutputFormat::Text {
+        pb.set_style(
+            ProgressStyle::default_spinner()
+                .template("{spinner:.green} {msg}")
+                .unwrap(),
+        );
+        pb.set_message(format!("Running {}", command.bright_white()));
+        pb.enable_steady_tick(std::time::Duration::from_millis(120));
+    } else {
+        pb.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    }
+
+    // Text output and ndjson records stream as repositories finish; the other formats are
+    // rendered from every outcome afterwards.
+    let stream_text = output == OutputFormat::Text && query.fields.is_empty();
+    let outcomes = for_each_repository(f, filter, concurrency, |outcome| {
+        let record = command_record(outcome);
+        if !query.matches(&record)? {
+            return Ok(());
+        }
+        if stream_text {
+            pb.suspend(|| print_command_outcome(outcome));
+        } else if output == OutputFormat::Ndjson {
+            let projected = query.project(&serde_json::to_value(&record)?);
+            print_json_line(&tag_record(record.kind(), projected))?;
+        }
+        Ok(())
+    })
+    .await;
+    pb.finish_and_clear();
+
+    let mut selected = Vec::new();
+    for outcome in outcomes.map_err(GmuxError::from)? {
+        if query.matches(&command_record(&outcome))? {
+            selected.push(outcome);
+        }
+    }
+    Ok(selected)
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum CommandRecord {
+    Result(RepositoryCommandResult),
+    Skipped(RepositorySkippedResult),
+    Error(RepositoryErrorResult),
+}
+
+impl CommandRecord {
+    /// The `CommandBatchResult` array the record belongs to.
+    fn kind(&self) -> &'static str {
+        match self {
+            CommandRecord::Result(_) => "results",
+            CommandRecord::Skipped(_) => "skipped",
+            CommandRecord::Error(_) => "errors",
+        }
+    }
+}
+
+fn command_record(outcome: &RepositoryOutcome) -> CommandRecord {
+    let path = &outcome.path;
+    match &outcome.status {
+        RepositoryStatus::Completed { command, output } => {
+            CommandRecord::Result(RepositoryCommandResult {
+                repository: repository_name(path),
+                path: path.display().to_string(),
+                command: command.clone(),
+                exit_code: output.exit_code,
+                stdout: output.stdout.clone(),
+                stderr: output.stderr.clone(),
+                duration_ms: outcome.duration.as_millis(),
+            })
+        }
+        RepositoryStatus::Skipped(reason) => {
+            CommandRecord::Skipped(RepositorySkippedResult::new(path, reason))
+        }
+        RepositoryStatus::Failed(error) => {
+            CommandRecord::Error(RepositoryErrorResult::new(path, error))
+        }
+    }
+}
+
+/// Renders the outcomes of `cmd` and `git` in any output format. Commands that exit non-zero
+/// are reported but do not fail gmux; repositories where the command could not run do.
+fn render_command_outcomes(
+    command: String,
+    outcomes: Vec<RepositoryOutcome>,
+    query: &QueryOptions,
+    report: &ReportOptions,
+    output: OutputFormat,
+) -> Result<()> {
+    let succeeded = outcomes.iter().filter(|o| o.is_success()).count();
+    let skipped = outcomes
+        .iter()
+        .filter(|o| matches!(o.status, RepositoryStatus::Skipped(_)))
+        .count();
+    let errored = outcomes
+        .iter()
+        .filter(|o| matches!(o.status, RepositoryStatus::Failed(_)))
+        .count();
+    let failed = outcomes.len() - succeeded - skipped;
+
+    let mut batch = CommandBatchResult {
+        command,
+        succeeded,
+        failed,
+        results: Vec::new(),
+        skipped: Vec::new(),
+        errors: Vec::new(),
+    };
+    for outcome in &outcomes {
+        match command_record(outcome) {
+            CommandRecord::Result(result) => batch.results.push(result),
+            CommandRecord::Skipped(skipped) => batch.skipped.push(skipped),
+            CommandRecord::Error(error) => batch.errors.push(error),
+        }
+    }
+    report.write(&batch)?;
+
+    // Each repository's output was printed by `run_in_repositories` as it finished.
+    if output == OutputFormat::Text && query.fields.is_empty() {
+        render_command_summary(&outcomes);
+        println!(
+            "{} succeeded, {} failed, {} skipped",
+            succeeded.to_string().green(),
+            failed.to_string().red(),
+            skipped.to_string().yellow()
+        );
+    } else if output != OutputFormat::Ndjson {
+        print_query_output(&batch, query, output)?;
+    }
+
+    if errored > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "command could not be run in {} repositories",
+            errored
+        )));
+    }
+    Ok(())
+}
+
+fn print_command_outcome(outcome: &RepositoryOutcome) {
+    let RepositoryStatus::Completed { command, output } = &outcome.status else {
+        return;
+    };
+    println!(
+        "\n{} {} ({})",
+        "📦".yellow(),
+        repository_name(&outcome.path).bright_white(),
+        outcome.path.display().to_string().dimmed()
+    );
+    println!("{} {}", "⚡".blue(), command.bright_white());
+    if !output.stdout.trim().is_empty() {
+        println!("{}", output.stdout.trim());
+    }
+    if !output.stderr.trim().is_empty() {
+        eprintln!("{}", output.stderr.trim().red());
+    }
+}
+
+fn render_command_summary(outcomes: &[RepositoryOutcome]) {
+    let names: Vec<String> = outcomes.iter().map(|o| repository_name(&o.path)).collect();
+    let width = names
+        .iter()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(10);
 
-                let output = run_command_capture(&["sh", "-c", &command_str], &path)
-                    .await
-                    .map_err(GmuxError::from)?;
+    println!("\n{}", "─".repeat(80).dimmed());
+    println!(
+        "  {:<width$} {:<9} {:>5} {:>9}  Reason",
+        "Repository", "Status", "Exit", "Duration"
+    );
+    for (name, outcome) in names.iter().zip(outcomes) {
+        let (status, reason) = match &outcome.status {
+            RepositoryStatus::Completed { .. } if outcome.is_success() => ("success".green(), ""),
+            RepositoryStatus::Completed { .. } => ("failed".red(), ""),
+            RepositoryStatus::Skipped(reason) => ("skipped".yellow(), reason.as_str()),
+            RepositoryStatus::Failed(error) => ("error".red(), error.as_str()),
+        };
+        let exit_code = outcome
+            .exit_code()
+            .map_or_else(|| "-".to_string(), |code| code.to_string());
+        println!(
+            "  {:<width$} {:<9} {:>5} {:>8.2}s  {}",
+            name,
+            status,
+            exit_code,
+            outcome.duration.as_secs_f64(),
+            reason.dimmed()
+        );
+    }
+    println!("{}", "─".repeat(80).dimmed());
+}
+
+pub async fn branch(
+    action: BranchAction,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let results: Vec<BranchResult> = stream::iter(paths)
+        .map(|path| {
+            let action = &action;
+            async move { branch::run_branch_action(&path, action).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let failed = results.iter().filter(|result| result.is_failed()).count();
+    let skipped = results.iter().filter(|result| result.is_skipped()).count();
+    let succeeded = results.len() - failed - skipped;
+
+    if output != OutputFormat::Text {
+        print_output(
+            &BranchBatchResult {
+                action: action.label().to_string(),
+                succeeded,
+                skipped,
+                failed,
+                results,
+            },
+            output,
+        )?;
+    } else {
+        render_branch_results(&results);
+        println!(
+            "{} succeeded, {} skipped, {} failed",
+            succeeded.to_string().green(),
+            skipped.to_string().yellow(),
+            failed.to_string().red()
+        );
+        println!("{}", "─".repeat(80).dimmed());
+    }
 
-                pb.finish_and_clear();
+    if failed > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "branch {} failed in {} repositories",
+            action.label(),
+            failed
+        )));
+    }
+    Ok(())
+}
+
+fn render_branch_results(results: &[BranchResult]) {
+    println!("{}", "─".repeat(80).dimmed());
+    for result in results {
+        let current = result.current_branch.as_deref().unwrap_or("detached");
+        let line = format!(
+            "{} ({}): {}{}{}",
+            result.repository,
+            current,
+            result.status,
+            result
+                .branch
+                .as_deref()
+                .map(|branch| format!(" {}", branch))
+                .unwrap_or_default(),
+            result
+                .reason
+                .as_deref()
+                .map(|reason| format!(" - {}", reason))
+                .unwrap_or_default()
+        );
+        if result.is_failed() {
+            println!("{} {}", "✗".red(), line.red());
+        } else if result.is_skipped() {
+            println!("{} {}", "-".yellow(), line.dimmed());
+        } else {
+            println!("{} {}", "✓".green(), line);
+        }
+
+        for name in &result.branches {
+            if result.current_branch.as_deref() == Some(name.as_str()) {
+                println!("    {} {}", "*".green(), name.green());
+            } else {
+                println!("      {}", name);
+            }
+        }
+    }
+    println!("{}", "─".repeat(80).dimmed());
+}
+
+pub async fn commit(
+    options: CommitOptions,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let results: Vec<CommitResult> = stream::iter(paths)
+        .map(|path| {
+            let options = &options;
+            async move { commit::commit_repository(&path, options).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let failed = results.iter().filter(|result| result.is_failed()).count();
+    let skipped = results.iter().filter(|result| result.is_skipped()).count();
+    let committed = results.len() - failed - skipped;
+
+    if output != OutputFormat::Text {
+        print_output(
+            &CommitBatchResult {
+                committed,
+                skipped,
+                failed,
+                results,
+            },
+            output,
+        )?;
+    } else {
+        println!("{}", "─".repeat(80).dimmed());
+        for result in &results {
+            let branch = result.branch.as_deref().unwrap_or("detached");
+            if result.is_failed() {
+                println!(
+                    "{} {} ({}): {}",
+                    "✗".red(),
+                    result.repository.red().bold(),
+                    branch,
+                    result.reason.as_deref().unwrap_or_default()
+                );
+            } else if result.is_skipped() {
+                println!(
+                    "{} {} ({}): {}",
+                    "-".yellow(),
+                    result.repository.dimmed(),
+                    branch,
+                    result.reason.as_deref().unwrap_or_default().dimmed()
+                );
+            } else {
                 println!(
-                    "{} {} ({})",
-                    "📦".yellow(),
-                    repo_name,
-                    path.display().to_string().dimmed()
+                    "{} {} ({}): {} {} {}",
+                    "✓".green(),
+                    result.repository.bright_white().bold(),
+                    branch,
+                    result.status,
+                    result.commit.as_deref().unwrap_or_default().cyan(),
+                    result
+                        .message
+                        .as_deref()
+                        .and_then(|message| message.lines().next())
+                        .unwrap_or_default()
                 );
-                println!("{} {}", "⚡".blue(), cmd_line);
+            }
+        }
+        println!("{}", "─".repeat(80).dimmed());
+        println!(
+            "{} committed, {} skipped, {} failed",
+            committed.to_string().green(),
+            skipped.to_string().yellow(),
+            failed.to_string().red()
+        );
+    }
 
-                if !output.stdout.trim().is_empty() {
-                    println!("{}", output.stdout.trim());
-                }
-                if !output.stderr.trim().is_empty() {
-                    eprintln!("{}", output.stderr.trim().red());
-                }
+    if failed > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "commit failed in {} repositories",
+            failed
+        )));
+    }
+    Ok(())
+}
 
-                let status = if output.exit_code == 0 {
-                    format!("✓ Success ({}s)", output.exit_code).green()
-                } else {
-                    format!("✗ Failed (exit code: {})", output.exit_code).red()
-                };
-                println!("{}\n", status);
+pub async fn push(
+    options: PushOptions,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let results: Vec<PushResult> = stream::iter(paths)
+        .map(|path| {
+            let options = &options;
+            async move { push::push_repository(&path, options).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let count = |status: &str| results.iter().filter(|r| r.status == status).count();
+    let pushed = count(push::STATUS_PUSHED);
+    let up_to_date = count(push::STATUS_UP_TO_DATE);
+    let rejected = count(push::STATUS_REJECTED);
+    let skipped = count(push::STATUS_SKIPPED);
+    let failed = count(push::STATUS_FAILED);
+
+    if output != OutputFormat::Text {
+        print_output(
+            &PushBatchResult {
+                pushed,
+                up_to_date,
+                rejected,
+                skipped,
+                failed,
+                results,
+            },
+            output,
+        )?;
+    } else {
+        println!("{}", "─".repeat(80).dimmed());
+        for result in &results {
+            let branch = result.branch.as_deref().unwrap_or("-");
+            let label = format!("{} ({} → {})", result.repository, branch, result.remote);
+            let status = match result.status.as_str() {
+                push::STATUS_PUSHED => format!("✓ {}", label).green(),
+                push::STATUS_UP_TO_DATE => format!("= {}", label).normal(),
+                push::STATUS_SKIPPED => format!("- {}", label).dimmed(),
+                _ => format!("✗ {}", label).red(),
+            };
+            println!("{}: {}", status, result.status);
+            for line in result.reason.iter().flat_map(|reason| reason.lines()) {
+                println!("    {}", line.dimmed());
+            }
+        }
+        println!("{}", "─".repeat(80).dimmed());
+        println!(
+            "{} pushed, {} up-to-date, {} rejected, {} skipped, {} failed",
+            pushed.to_string().green(),
+            up_to_date,
+            rejected.to_string().red(),
+            skipped.to_string().yellow(),
+            failed.to_string().red()
+        );
+    }
+
+    if rejected + failed > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "push was rejected or failed in {} repositories",
+            rejected + failed
+        )));
+    }
+    Ok(())
+}
+
+pub async fn snapshot(
+    action: SnapshotAction,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    match action {
+        SnapshotAction::Save { name, force } => {
+            snapshot_save(name, force, filter, concurrency, output).await
+        }
+        SnapshotAction::Restore { name } => {
+            snapshot_restore(name, filter, concurrency, output).await
+        }
+        SnapshotAction::List => snapshot_list(output),
+    }
+}
+
+async fn snapshot_save(
+    name: String,
+    force: bool,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let snapshot_path = snapshot::get_snapshot_path(&name)?;
+    if snapshot_path.exists() && !force {
+        return Err(GmuxError::Validation(format!(
+            "Snapshot {} already exists; restore it first or pass --force",
+            name
+        )));
+    }
+
+    let workspace = std::env::current_dir()?;
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let saved: Vec<_> = stream::iter(paths)
+        .map(|path| {
+            let name = &name;
+            async move { snapshot::save_repository(&path, name).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let (results, repositories): (Vec<_>, Vec<_>) = saved.into_iter().unzip();
+    Snapshot::new(
+        &name,
+        &workspace,
+        repositories.into_iter().flatten().collect(),
+    )
+    .save(&snapshot_path)?;
+
+    render_snapshot_results(&name, "save", results, output)
+}
+
+async fn snapshot_restore(
+    name: String,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut snapshot = snapshot::load_snapshot(&name)?;
+    let filter_regex = filter
+        .as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|error| GmuxError::Validation(format!("Invalid regex pattern: {}", error)))?;
+    let (selected, remaining): (Vec<_>, Vec<_>) =
+        snapshot.repositories.into_iter().partition(|repository| {
+            filter_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(&repository.repository))
+        });
+
+    let results: Vec<SnapshotRepositoryResult> = stream::iter(&selected)
+        .map(|repository| snapshot::restore_repository(repository, &name))
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    // Keep entries that did not restore so they can be retried; drop the snapshot once empty.
+    snapshot.repositories = remaining;
+    for (repository, result) in selected.into_iter().zip(&results) {
+        if result.is_failed() {
+            snapshot.repositories.push(repository);
+        }
+    }
+    let snapshot_path = snapshot::get_snapshot_path(&name)?;
+    if snapshot.repositories.is_empty() {
+        std::fs::remove_file(&snapshot_path)?;
+    } else {
+        snapshot.save(&snapshot_path)?;
+    }
+
+    render_snapshot_results(&name, "restore", results, output)
+}
+
+fn render_snapshot_results(
+    name: &str,
+    action: &str,
+    results: Vec<SnapshotRepositoryResult>,
+    output: OutputFormat,
+) -> Result<()> {
+    let failed = results.iter().filter(|result| result.is_failed()).count();
+    let succeeded = results.len() - failed;
+
+    if output != OutputFormat::Text {
+        print_output(
+            &SnapshotBatchResult {
+                name: name.to_string(),
+                action: action.to_string(),
+                succeeded,
+                failed,
+                results,
+            },
+            output,
+        )?;
+    } else {
+        println!("{}", "─".repeat(80).dimmed());
+        for result in &results {
+            let label = format!(
+                "{} ({})",
+                result.repository,
+                result.branch.as_deref().unwrap_or("detached")
+            );
+            if result.is_failed() {
+                println!("{} {}: {}", "✗".red(), label.red().bold(), result.status);
+            } else {
+                println!(
+                    "{} {}: {}",
+                    "✓".green(),
+                    label.bright_white(),
+                    result.status
+                );
+            }
+            for line in result.reason.iter().flat_map(|reason| reason.lines()) {
+                println!("    {}", line.dimmed());
+            }
+        }
+        println!("{}", "─".repeat(80).dimmed());
+        println!(
+            "Snapshot {}: {} succeeded, {} failed",
+            name.bright_white(),
+            succeeded.to_string().green(),
+            failed.to_string().red()
+        );
+    }
+
+    if failed > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "snapshot {} failed in {} repositories",
+            action, failed
+        )));
+    }
+    Ok(())
+}
+
+fn snapshot_list(output: OutputFormat) -> Result<()> {
+    let summaries: Vec<SnapshotSummary> = snapshot::load_all_snapshots()
+        .into_iter()
+        .map(|snapshot| SnapshotSummary {
+            stashes: snapshot
+                .repositories
+                .iter()
+                .filter(|repository| repository.stash.is_some())
+                .count(),
+            repositories: snapshot.repositories.len(),
+            name: snapshot.name,
+            workspace: snapshot.workspace,
+            created_at: snapshot.created_at,
+        })
+        .collect();
+
+    if output != OutputFormat::Text {
+        return print_output(
+            &SnapshotListResult {
+                snapshots: summaries,
+            },
+            output,
+        );
+    }
+
+    if summaries.is_empty() {
+        println!("{}", "No snapshots saved".dimmed());
+    }
+    for summary in summaries {
+        println!(
+            "{} {} repositories, {} stashes {}",
+            summary.name.bright_white().bold(),
+            summary.repositories,
+            summary.stashes,
+            summary.workspace.dimmed()
+        );
+    }
+    Ok(())
+}
+
+pub async fn grep(
+    options: GrepOptions,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let searched: Vec<_> = stream::iter(paths)
+        .map(|path| {
+            let options = &options;
+            async move { grep::grep_repository(&path, options).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let total = searched.len();
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for result in searched {
+        match result {
+            Ok(result) if result.count > 0 => results.push(result),
+            Ok(_) => {}
+            Err(error) => errors.push(error),
+        }
+    }
+    let count = results.iter().map(|result| result.count).sum();
+    let file_count = results.iter().map(|result| result.file_count).sum();
+
+    let errored = errors.len();
+    if output != OutputFormat::Text {
+        print_output(
+            &GrepBatchResult {
+                pattern: options.pattern,
+                searched: total,
+                count,
+                file_count,
+                results,
+                errors,
+            },
+            output,
+        )?;
+    } else {
+        print_grep_results(&results, &errors, count, file_count, total);
+    }
+
+    if errored > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "grep failed in {} repositories",
+            errored
+        )));
+    }
+    Ok(())
+}
+
+fn print_grep_results(
+    results: &[GrepRepositoryResult],
+    errors: &[RepositoryErrorResult],
+    count: usize,
+    file_count: usize,
+    total: usize,
+) {
+    for result in results {
+        println!(
+            "\n{} {} ({} matches)",
+            "📦".yellow(),
+            result.repository.bright_white().bold(),
+            result.count
+        );
+        for grep_match in &result.matches {
+            println!(
+                "{}:{}:{}",
+                grep_match.path.magenta(),
+                grep_match.line.to_string().green(),
+                grep_match.text
+            );
+        }
+    }
+    for error in errors {
+        eprintln!(
+            "{} {}: {}",
+            "✗".red(),
+            error.repository.red().bold(),
+            error.error
+        );
+    }
+    println!("\n{}", "─".repeat(80).dimmed());
+    println!(
+        "{} matches in {} files across {} of {} repositories",
+        count.to_string().bright_white(),
+        file_count,
+        results.len(),
+        total
+    );
+}
+
+pub async fn replace(
+    options: ReplaceOptions,
+    filter: Option<String>,
+    concurrency: usize,
+    output: OutputFormat,
+) -> Result<()> {
+    let regex = options.regex()?;
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let replaced: Vec<_> = stream::iter(paths)
+        .map(|path| {
+            let (regex, options) = (&regex, &options);
+            async move { replace::replace_in_repository(&path, regex, options).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let mut results = Vec::new();
+    let mut errors = Vec::new();
+    for result in replaced {
+        match result {
+            Ok(result) if !result.files.is_empty() => results.push(result),
+            Ok(_) => {}
+            Err(error) => errors.push(error),
+        }
+    }
+    let replacements = results.iter().map(|result| result.replacements).sum();
+    let file_count = results.iter().map(|result| result.files.len()).sum();
+
+    let errored = errors.len();
+    if output != OutputFormat::Text {
+        print_output(
+            &ReplaceBatchResult {
+                pattern: options.pattern,
+                replacement: options.replacement,
+                dry_run: options.dry_run,
+                replacements,
+                file_count,
+                results,
+                errors,
+            },
+            output,
+        )?;
+    } else {
+        print_replace_results(&results, &errors, replacements, file_count, options.dry_run);
+    }
+
+    // A codemod that failed partway is left half-applied, which scripts need to notice.
+    if errored > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "replace failed in {} repositories",
+            errored
+        )));
+    }
+    Ok(())
+}
+
+fn print_replace_results(
+    results: &[ReplaceRepositoryResult],
+    errors: &[RepositoryErrorResult],
+    replacements: usize,
+    file_count: usize,
+    dry_run: bool,
+) {
+    for result in results {
+        println!(
+            "\n{} {} ({} replacements)",
+            "📦".yellow(),
+            result.repository.bright_white().bold(),
+            result.replacements
+        );
+        for file in &result.files {
+            match &file.diff {
+                Some(diff) => print_colored_diff(diff),
+                None => println!("  {} ({})", file.path.magenta(), file.replacements),
+            }
+        }
+    }
+    for error in errors {
+        eprintln!(
+            "{} {}: {}",
+            "✗".red(),
+            error.repository.red().bold(),
+            error.error
+        );
+    }
+    println!("\n{}", "─".repeat(80).dimmed());
+    let verb = if dry_run { "Would make" } else { "Made" };
+    println!(
+        "{} {} replacements in {} files across {} repositories",
+        verb,
+        replacements.to_string().bright_white(),
+        file_count,
+        results.len()
+    );
+}
 
-                Ok(())
-            })
-        },
-        filter.as_deref(),
-        concurrency,
-    )
-    .await
-    .map_err(GmuxError::from)
+fn print_colored_diff(diff: &str) {
+    for line in diff.lines() {
+        if line.starts_with("+++") || line.starts_with("---") {
+            println!("{}", line.bold());
+        } else if line.starts_with('+') {
+            println!("{}", line.green());
+        } else if line.starts_with('-') {
+            println!("{}", line.red());
+        } else if line.starts_with("@@") {
+            println!("{}", line.cyan());
+        } else {
+            println!("{}", line);
+        }
+    }
 }
 
-pub async fn pr(
-    title: Option<String>,
-    yes: bool,
-    no_input: bool,
-    dry_run: bool,
+pub async fn apply(
+    options: ApplyOptions,
     filter: Option<String>,
     concurrency: usize,
     output: OutputFormat,
 ) -> Result<()> {
-    let title = if let Some(title) = title {
-        title
-    } else if no_input || output == OutputFormat::Json {
-        return Err(GmuxError::Validation(
-            "PR title is required in non-interactive mode".to_string(),
-        ));
+    let patch = options.resolve_patch()?;
+    let kind = apply::patch_kind(&patch)?;
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let results: Vec<ApplyResult> = stream::iter(paths)
+        .map(|path| {
+            let patch = &patch;
+            async move { apply::apply_repository(&path, patch, kind).await }
+        })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+
+    let count = |status: &str| results.iter().filter(|r| r.status == status).count();
+    let applied = count(apply::STATUS_APPLIED);
+    let already_applied = count(apply::STATUS_ALREADY_APPLIED);
+    let conflict = count(apply::STATUS_CONFLICT);
+    let failed = count(apply::STATUS_FAILED);
+
+    if output != OutputFormat::Text {
+        print_output(
+            &ApplyBatchResult {
+                patch: patch.display().to_string(),
+                applied,
+                already_applied,
+                conflict,
+                failed,
+                results,
+            },
+            output,
+        )?;
     } else {
-        print!("{}", "Enter PR title: ".bright_white());
-        io::stdout().flush()?;
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-        input.trim().to_string()
-    };
-
-    if output == OutputFormat::Json {
-        return pr_json(title, yes, dry_run, filter, concurrency).await;
-    }
-
-    println!(
-        "🚀 Starting PR command with title: {}",
-        title.bright_white().bold()
-    );
-    let template_content = get_template_content().await?;
-    if template_content.is_none() {
+        println!("{}", "─".repeat(80).dimmed());
+        for result in &results {
+            let label = format!(
+                "{} ({})",
+                result.repository,
+                result.branch.as_deref().unwrap_or("-")
+            );
+            let status = match result.status.as_str() {
+                apply::STATUS_APPLIED => format!("✓ {}", label).green(),
+                apply::STATUS_ALREADY_APPLIED => format!("= {}", label).normal(),
+                apply::STATUS_CONFLICT => format!("! {}", label).yellow(),
+                _ => format!("✗ {}", label).red(),
+            };
+            println!("{}: {}", status, result.status);
+            for file in &result.conflicts {
+                println!("    {} {}", "U".red(), file);
+            }
+            for line in result.reason.iter().flat_map(|reason| reason.lines()) {
+                println!("    {}", line.dimmed());
+            }
+        }
+        println!("{}", "─".repeat(80).dimmed());
         println!(
-            "{}",
-            "⚠️  PR template not found. Run 'gmux init' first.".yellow()
+            "{} applied, {} already applied, {} conflict, {} failed",
+            applied.to_string().green(),
+            already_applied,
+            conflict.to_string().yellow(),
+            failed.to_string().red()
         );
-        return Ok(());
     }
 
-    let template_content = template_content.unwrap();
-    let title = title.clone();
+    if conflict + failed > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "patch did not apply cleanly in {} repositories",
+            conflict + failed
+        )));
+    }
+    Ok(())
+}
 
-    for_each_repository(
-        move |path| {
-            let template_content = template_content.clone();
-            let title = title.clone();
-            Box::pin(async move {
-                println!("\n{}", "─".repeat(80).dimmed());
-                println!(
-                    "📦 Processing repository: {}",
-                    path.display().to_string().bright_white().bold()
-                );
-                if !crate::git::is_git_directory(&path).await {
-                    println!(
-                        "⏭️  Skipping non-git directory: {}",
-                        path.display().to_string().dimmed()
-                    );
-                    return Ok(());
-                }
-                if let Some(metadata) = get_repository_metadata(&path)
-                    .await
-                    .map_err(GmuxError::from)?
-                {
-                    let diff_files = get_diff_file_names(&path, &metadata.default_branch)
-                        .await
-                        .map_err(GmuxError::from)?;
-
-                    if diff_files.is_empty() {
-                        println!(
-                            "ℹ️  No changes found in {}",
-                            path.display().to_string().dimmed()
-                        );
-                        return Ok(());
-                    }
-
-                    let pr_content = template_content
-                        .replace("{{ title }}", &title)
-                        .replace(
-                            "{{ repository_name }}",
-                            &path.file_name().unwrap().to_string_lossy(),
-                        )
-                        .replace(
-                            "{% for file in diff_files %}\n- {{ file }}\n{% endfor %}",
-                            &diff_files
-                                .iter()
-                                .map(|f| format!("- {}", f))
-                                .collect::<Vec<_>>()
-                                .join("\n"),
-                        );
-
-                    // Check if the current branch has been pushed to the remote
-                    let output = tokio::process::Command::new("git")
-                        .args(["ls-remote", "--heads", "origin", &metadata.current_branch])
-                        .current_dir(&path)
-                        .output()
-                        .await
-                        .map_err(GmuxError::from)?;
-
-                    let branch_exists = !String::from_utf8_lossy(&output.stdout).trim().is_empty();
-
-                    if !branch_exists {
-                        if dry_run {
-                            println!(
-                                "⏭️  Dry run: branch {} has not been pushed",
-                                metadata.current_branch.bright_yellow().bold()
-                            );
-                            return Ok(());
-                        }
-                        let should_push = if yes {
-                            true
-                        } else if no_input {
-                            false
-                        } else {
-                            println!(
-                                "❗ Branch {} has not been pushed to the remote.\n❓ Do you want to push it? ( {} )",
-                                metadata.current_branch.bright_yellow().bold(),
-                                "y/n".bright_white().bold()
-                            );
-                            let mut input = String::new();
-                            std::io::stdin()
-                                .read_line(&mut input)
-                                .map_err(GmuxError::from)?;
-                            input.trim().to_lowercase() == "y"
-                        };
-                        if should_push {
-                            let push_output = tokio::process::Command::new("git")
-                                .args(["push", "-u", "origin", &metadata.current_branch])
-                                .current_dir(&path)
-                                .output()
-                                .await
-                                .map_err(GmuxError::from)?;
-                            if !push_output.status.success() {
-                                println!(
-                                    "❌ Failed to push branch {}: {}",
-                                    metadata.current_branch.red().bold(),
-                                    String::from_utf8_lossy(&push_output.stderr)
-                                );
-                                return Ok(());
-                            } else {
-                                println!(
-                                    "✅ Branch pushed: {}",
-                                    metadata.current_branch.green().bold()
-                                );
-                            }
-                        } else {
-                            println!(
-                                "⏭️  Skipping PR creation for {}",
-                                path.display().to_string().dimmed()
-                            );
-                            return Ok(());
-                        }
-                    } else {
-                        println!(
-                            "✅ Branch already exists on remote: {}",
-                            metadata.current_branch.green().bold()
-                        );
-                    }
-
-                    // Get the remote URL to determine owner and repo
-                    let output = tokio::process::Command::new("git")
-                        .args(["remote", "get-url", "origin"])
-                        .current_dir(&path)
-                        .output()
-                        .await
-                        .map_err(GmuxError::from)?;
-                    let remote_url = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                    // Parse the remote URL to extract owner and repo
-                    // Example URL: https://github.com/owner/repo.git
-                    let parts: Vec<&str> = remote_url.split('/').collect();
-                    let repo = parts.last().unwrap().replace(".git", "");
-                    let owner = parts[parts.len() - 2];
-
-                    println!(
-                        "{} Opening PR creation link for {}/{}\n{}",
-                        "🌐".cyan().bold(),
-                        owner.bright_white().bold(),
-                        repo.bright_white().bold(),
-                        "(A browser window will open with your PR draft)".dimmed()
-                    );
-                    let url = format!(
-                        "https://github.com/{}/{}/compare/{}...{}?expand=1&title={}&body={}",
-                        owner,
-                        repo,
-                        metadata.default_branch,
-                        metadata.current_branch,
-                        urlencoding::encode(&title),
-                        urlencoding::encode(&pr_content)
-                    );
-                    if dry_run {
-                        println!("{}", url);
-                    } else {
-                        let _ = open::that(url);
-                    }
-                }
-                println!("{}", "─".repeat(80).dimmed());
-                Ok(())
-            })
-        },
-        filter.as_deref(),
-        concurrency,
-    )
-    .await
-    .map_err(GmuxError::from)
+pub fn schema(command: SchemaCommand) -> Result<()> {
+    print_json(&schema::output_schema(command))
 }
 
-pub async fn git(
-    command: Vec<String>,
+pub async fn doctor(
     filter: Option<String>,
     concurrency: usize,
     output: OutputFormat,
 ) -> Result<()> {
-    if output == OutputFormat::Json {
-        let command_label = format!("git {}", command.join(" "));
-        let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
-        let results: Vec<std::result::Result<RepositoryCommandResult, RepositoryErrorResult>> =
-            stream::iter(paths)
-                .map(|path| {
-                    let command = command.clone();
-                    async move { run_git_command_for_json(path.as_ref(), command).await }
-                })
-                .buffer_unordered(concurrency)
-                .collect()
-                .await;
-
-        let mut command_results = Vec::new();
-        let mut errors = Vec::new();
-        for result in results {
-            match result {
-                Ok(result) => command_results.push(result),
-                Err(error) => errors.push(error),
+    let checks = doctor::check_environment().await;
+    // A misconfigured backend is reported above; fall back to running git for the rest.
+    let backend = configured_git_backend().unwrap_or_else(|_| Box::new(CliBackend));
+    let backend = backend.as_ref();
+    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
+    let repositories: Vec<_> = stream::iter(paths)
+        .map(|path| async move { doctor::check_repository(&path, backend).await })
+        .buffered(concurrency)
+        .filter_map(std::future::ready)
+        .collect()
+        .await;
+    let result = DoctorResult::new(checks, repositories);
+
+    if output != OutputFormat::Text {
+        print_output(&result, output)?;
+    } else {
+        println!("{}", "Environment".bold());
+        for check in &result.checks {
+            print_doctor_check(check, "  ");
+        }
+        if !result.repositories.is_empty() {
+            println!("\n{}", "Repositories".bold());
+        }
+        for repository in &result.repositories {
+            println!(
+                "  {} {}",
+                doctor_status_symbol(&repository.status),
+                repository.repository.bright_white()
+            );
+            for check in repository
+                .checks
+                .iter()
+                .filter(|check| check.status != doctor::STATUS_PASS)
+            {
+                print_doctor_check(check, "      ");
             }
         }
 
-        let succeeded = command_results.iter().filter(|r| r.exit_code == 0).count();
-        let failed = command_results.len().saturating_sub(succeeded) + errors.len();
-        return print_json(&CommandBatchResult {
-            command: command_label,
-            succeeded,
-            failed,
-            results: command_results,
-            errors,
-        });
+        println!("{}", "─".repeat(80).dimmed());
+        println!(
+            "{} passed, {} warnings, {} failed",
+            result.passed.to_string().green(),
+            result.warned.to_string().yellow(),
+            result.failed.to_string().red()
+        );
     }
 
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {msg}")
-            .unwrap(),
-    );
+    if result.failed > 0 {
+        return Err(GmuxError::RepositoriesFailed(format!(
+            "{} doctor checks failed",
+            result.failed
+        )));
+    }
+    Ok(())
+}
 
-    for_each_repository(
-        move |path| {
-            let command = command.clone();
-            Box::pin(async move {
-                if let Some(metadata) = get_repository_metadata(&path)
-                    .await
-                    .map_err(GmuxError::from)?
-                {
-                    let mut cmd = command.clone();
-                    for arg in &mut cmd {
-                        *arg = arg.replace("@default", &metadata.default_branch);
-                        *arg = arg.replace("@current", &metadata.current_branch);
-                    }
-                    let mut full_cmd = vec!["git"];
-                    full_cmd.extend(cmd.iter().map(|s| s.as_str()));
-                    let repo_name = path.file_name().unwrap().to_string_lossy().bright_white();
-                    let branch = metadata.current_branch.bright_white();
-                    let cmd_line = cmd.join(" ").bright_white();
-
-                    let start = std::time::Instant::now();
-                    let output = run_command_capture(&full_cmd, &path)
-                        .await
-                        .map_err(GmuxError::from)?;
-                    let elapsed = start.elapsed();
-
-                    println!("\n{} {} ({})", "📦".yellow(), repo_name, branch);
-                    println!("{} {}", "⚡".blue(), cmd_line);
-
-                    if !output.stdout.trim().is_empty() {
-                        println!("{}", output.stdout.trim());
-                    }
-                    if !output.stderr.trim().is_empty() {
-                        eprintln!("{}", output.stderr.trim().red());
-                    }
-
-                    let status = if output.exit_code == 0 {
-                        format!("✓ Success ({:.2}s)", elapsed.as_secs_f64()).green()
-                    } else {
-                        format!("✗ Failed (exit code: {})", output.exit_code).red()
-                    };
-                    println!("{}\n", status);
-                }
-                Ok(())
-            })
-        },
-        filter.as_deref(),
-        concurrency,
-    )
-    .await
-    .map_err(GmuxError::from)
+fn doctor_status_symbol(status: &str) -> colored::ColoredString {
+    match status {
+        doctor::STATUS_PASS => "✓".green(),
+        doctor::STATUS_WARN => "!".yellow(),
+        _ => "✗".red(),
+    }
+}
+
+fn print_doctor_check(check: &DoctorCheck, indent: &str) {
+    println!(
+        "{}{} {:<12} {}",
+        indent,
+        doctor_status_symbol(&check.status),
+        check.name,
+        check.message
+    );
+    if let Some(hint) = &check.hint {
+        println!(
+            "{}  {:<12} {}",
+            indent,
+            "",
+            format!("hint: {}", hint).dimmed()
+        );
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn clone(
     org: Option<String>,
     org_pos: Option<String>,
@@ -544,16 +1617,17 @@ pub async fn clone(
     topics: Option<Vec<String>>,
     visibility: Option<String>,
     language: Option<String>,
+    fork: bool,
+    cache_mode: CacheMode,
     output: OutputFormat,
 ) -> Result<()> {
     let org = org.or(org_pos).ok_or_else(|| {
-        GmuxError::Config(
-            "Organization or user must be specified via --org or as a positional argument"
-                .to_string(),
+        GmuxError::config(
+            "Organization or user must be specified via --org or as a positional argument",
         )
     })?;
-    let client = GitHubClient::new(load_config(&get_config_path())?)?;
-    let repositories = client.get_repositories(&org).await?;
+    let client = github_client(cache_mode)?;
+    let repositories = client.get_repositories(&org, cache_mode).await?;
 
     // Apply filter BEFORE showing count and progress bar
     let filtered_repositories: Vec<_> = {
@@ -614,7 +1688,7 @@ pub async fn clone(
         );
     }
 
-    if output == OutputFormat::Json {
+    if output != OutputFormat::Text {
         let matched = filtered_repositories.len();
         let mut results = Vec::new();
         let mut cloned = 0;
@@ -633,7 +1707,7 @@ pub async fn clone(
                 continue;
             }
 
-            match client.clone_repository(&org, &repository.name).await {
+            match clone_or_fork(&client, &org, &repository.name, fork).await {
                 Ok(_) => {
                     cloned += 1;
                     results.push(CloneResult {
@@ -647,20 +1721,23 @@ pub async fn clone(
                     results.push(CloneResult {
                         repository: repository.name,
                         status: "failed".to_string(),
-                        error: Some(error.to_string()),
+                        error: Some(error.format_error()),
                     });
                 }
             }
         }
 
-        return print_json(&CloneBatchResult {
-            organization: org,
-            matched,
-            cloned,
-            skipped,
-            failed,
-            results,
-        });
+        return print_output(
+            &CloneBatchResult {
+                organization: org,
+                matched,
+                cloned,
+                skipped,
+                failed,
+                results,
+            },
+            output,
+        );
     }
 
     let pb = ProgressBar::new(filtered_repositories.len() as u64);
@@ -686,9 +1763,18 @@ pub async fn clone(
         }
 
         pb.set_message(format!("Cloning {}/{}", org, repository.name));
-        match client.clone_repository(&org, &repository.name).await {
+        match clone_or_fork(&client, &org, &repository.name, fork).await {
             Ok(_) => successful += 1,
-            Err(_) => failed += 1,
+            Err(error) => {
+                failed += 1;
+                pb.println(format!(
+                    "{} {}/{}: {}",
+                    "✗".red(),
+                    org,
+                    repository.name,
+                    error.format_error()
+                ));
+            }
         }
         pb.inc(1);
     }
@@ -710,309 +1796,36 @@ pub async fn clone(
     Ok(())
 }
 
-async fn run_shell_command_for_json(
-    path: &Path,
-    command: &str,
-) -> std::result::Result<RepositoryCommandResult, RepositoryErrorResult> {
-    let repository = repository_name(path);
-    let start = std::time::Instant::now();
-    match run_command_capture(&["sh", "-c", command], path).await {
-        Ok(output) => Ok(RepositoryCommandResult {
-            repository,
-            path: path.display().to_string(),
-            command: command.to_string(),
-            exit_code: output.exit_code,
-            stdout: output.stdout,
-            stderr: output.stderr,
-            duration_ms: start.elapsed().as_millis(),
-        }),
-        Err(error) => Err(RepositoryErrorResult {
-            repository,
-            path: path.display().to_string(),
-            error: error.to_string(),
-        }),
-    }
-}
-
-async fn run_git_command_for_json(
-    path: &Path,
-    command: Vec<String>,
-) -> std::result::Result<RepositoryCommandResult, RepositoryErrorResult> {
-    let repository = repository_name(path);
-    let metadata = match get_repository_metadata(path).await {
-        Ok(Some(metadata)) => metadata,
-        Ok(None) => {
-            return Err(RepositoryErrorResult {
-                repository,
-                path: path.display().to_string(),
-                error: "not a git repository".to_string(),
-            });
-        }
-        Err(error) => {
-            return Err(RepositoryErrorResult {
-                repository,
-                path: path.display().to_string(),
-                error: error.to_string(),
-            });
-        }
-    };
-
-    let mut cmd = command;
-    for arg in &mut cmd {
-        *arg = arg.replace("@default", &metadata.default_branch);
-        *arg = arg.replace("@current", &metadata.current_branch);
-    }
-
-    let mut full_cmd = vec!["git"];
-    full_cmd.extend(cmd.iter().map(|s| s.as_str()));
-    let command_label = format!("git {}", cmd.join(" "));
-    let start = std::time::Instant::now();
-    match run_command_capture(&full_cmd, path).await {
-        Ok(output) => Ok(RepositoryCommandResult {
-            repository,
-            path: path.display().to_string(),
-            command: command_label,
-            exit_code: output.exit_code,
-            stdout: output.stdout,
-            stderr: output.stderr,
-            duration_ms: start.elapsed().as_millis(),
-        }),
-        Err(error) => Err(RepositoryErrorResult {
-            repository,
-            path: path.display().to_string(),
-            error: error.to_string(),
-        }),
-    }
-}
-
-async fn pr_json(
-    title: String,
-    yes: bool,
-    dry_run: bool,
-    filter: Option<String>,
-    concurrency: usize,
-) -> Result<()> {
-    let template_content = get_template_content().await?;
-    let Some(template_content) = template_content else {
-        return Err(GmuxError::Validation(
-            "PR template not found. Run 'gmux init' first.".to_string(),
-        ));
-    };
-
-    let paths = repository_paths(filter.as_deref()).map_err(GmuxError::from)?;
-    let results: Vec<std::result::Result<PullRequestPlan, RepositoryErrorResult>> =
-        stream::iter(paths)
-            .map(|path| {
-                let template_content = template_content.clone();
-                let title = title.clone();
-                async move {
-                    pr_plan_for_json(path.as_ref(), &title, &template_content, yes, dry_run).await
-                }
-            })
-            .buffer_unordered(concurrency)
-            .collect()
-            .await;
-
-    let mut plans = Vec::new();
-    let mut errors = Vec::new();
-    for result in results {
-        match result {
-            Ok(plan) => plans.push(plan),
-            Err(error) => errors.push(error),
-        }
-    }
-
-    print_json(&PullRequestBatchResult {
-        title,
-        dry_run,
-        plans,
-        errors,
-    })
-}
-
-async fn pr_plan_for_json(
-    path: &Path,
-    title: &str,
-    template_content: &str,
-    yes: bool,
-    dry_run: bool,
-) -> std::result::Result<PullRequestPlan, RepositoryErrorResult> {
-    let repository = repository_name(path);
-    if !crate::git::is_git_directory(path).await {
-        return Ok(PullRequestPlan {
-            repository,
-            path: path.display().to_string(),
-            owner: None,
-            repo: None,
-            base: None,
-            head: None,
-            title: title.to_string(),
-            body: None,
-            url: None,
-            status: "skipped".to_string(),
-            reason: Some("not a git repository".to_string()),
-        });
-    }
-
-    let metadata = get_repository_metadata(path)
-        .await
-        .map_err(|error| repo_error(path, error.to_string()))?
-        .ok_or_else(|| repo_error(path, "not a git repository".to_string()))?;
-    let diff_files = get_diff_file_names(path, &metadata.default_branch)
-        .await
-        .map_err(|error| repo_error(path, error.to_string()))?;
-
-    if diff_files.is_empty() {
-        return Ok(PullRequestPlan {
-            repository,
-            path: path.display().to_string(),
-            owner: None,
-            repo: None,
-            base: Some(metadata.default_branch),
-            head: Some(metadata.current_branch),
-            title: title.to_string(),
-            body: None,
-            url: None,
-            status: "skipped".to_string(),
-            reason: Some("no changes found".to_string()),
-        });
-    }
-
-    let branch_exists_output = tokio::process::Command::new("git")
-        .args(["ls-remote", "--heads", "origin", &metadata.current_branch])
-        .current_dir(path)
-        .output()
-        .await
-        .map_err(|error| repo_error(path, error.to_string()))?;
-    let branch_exists = !String::from_utf8_lossy(&branch_exists_output.stdout)
-        .trim()
-        .is_empty();
-
-    if !branch_exists && !dry_run {
-        if !yes {
-            return Ok(PullRequestPlan {
-                repository,
-                path: path.display().to_string(),
-                owner: None,
-                repo: None,
-                base: Some(metadata.default_branch),
-                head: Some(metadata.current_branch),
-                title: title.to_string(),
-                body: None,
-                url: None,
-                status: "skipped".to_string(),
-                reason: Some("branch has not been pushed; pass --yes to push".to_string()),
-            });
-        }
-
-        let push_output = tokio::process::Command::new("git")
-            .args(["push", "-u", "origin", &metadata.current_branch])
-            .current_dir(path)
-            .output()
-            .await
-            .map_err(|error| repo_error(path, error.to_string()))?;
-        if !push_output.status.success() {
-            return Err(repo_error(
-                path,
-                String::from_utf8_lossy(&push_output.stderr).to_string(),
-            ));
-        }
-    }
-
-    let remote_output = tokio::process::Command::new("git")
-        .args(["remote", "get-url", "origin"])
-        .current_dir(path)
-        .output()
-        .await
-        .map_err(|error| repo_error(path, error.to_string()))?;
-    let remote_url = String::from_utf8_lossy(&remote_output.stdout)
-        .trim()
-        .to_string();
-    let (owner, repo) = parse_github_remote(&remote_url).ok_or_else(|| {
-        repo_error(
-            path,
-            format!("could not parse GitHub remote URL: {}", remote_url),
-        )
-    })?;
-
-    let body = render_pr_body(template_content, title, &repository, &diff_files);
-    let url = format!(
-        "https://github.com/{}/{}/compare/{}...{}?expand=1&title={}&body={}",
-        owner,
-        repo,
-        metadata.default_branch,
-        metadata.current_branch,
-        urlencoding::encode(title),
-        urlencoding::encode(&body)
-    );
-
-    Ok(PullRequestPlan {
-        repository,
-        path: path.display().to_string(),
-        owner: Some(owner),
-        repo: Some(repo),
-        base: Some(metadata.default_branch),
-        head: Some(metadata.current_branch),
-        title: title.to_string(),
-        body: Some(body),
-        url: Some(url),
-        status: if dry_run { "planned" } else { "ready" }.to_string(),
-        reason: None,
-    })
-}
-
-fn render_pr_body(
-    template_content: &str,
-    title: &str,
+async fn clone_or_fork(
+    client: &GitHubClient,
+    org: &str,
     repository: &str,
-    diff_files: &[String],
-) -> String {
-    template_content
-        .replace("{{ title }}", title)
-        .replace("{{ repository_name }}", repository)
-        .replace(
-            "{% for file in diff_files %}\n- {{ file }}\n{% endfor %}",
-            &diff_files
-                .iter()
-                .map(|f| format!("- {}", f))
-                .collect::<Vec<_>>()
-                .join("\n"),
-        )
-}
-
-fn parse_github_remote(remote_url: &str) -> Option<(String, String)> {
-    let trimmed = remote_url.trim_end_matches(".git");
-    if let Some(path) = trimmed.strip_prefix("git@github.com:") {
-        let mut parts = path.split('/');
-        return Some((parts.next()?.to_string(), parts.next()?.to_string()));
-    }
-
-    let parts: Vec<&str> = trimmed.split('/').collect();
-    if parts.len() >= 2 {
-        return Some((
-            parts[parts.len() - 2].to_string(),
-            parts[parts.len() - 1].to_string(),
-        ));
+    fork: bool,
+) -> Result<()> {
+    if fork {
+        client.fork_and_clone_repository(org, repository).await
+    } else {
+        client.clone_repository(org, repository).await
     }
-
-    None
 }
 
-fn repository_name(path: &Path) -> String {
-    path.file_name()
-        .map(|name| name.to_string_lossy().to_string())
-        .unwrap_or_else(|| path.display().to_string())
+/// Offline listings are served from the cache, so they do not require a token.
+fn github_client(cache_mode: CacheMode) -> Result<GitHubClient> {
+    let config_path = get_config_path();
+    let config = if cache_mode == CacheMode::Offline {
+        load_config_for_setup(&config_path)?
+    } else {
+        load_config(&config_path)?
+    };
+    GitHubClient::new(config)
 }
 
-fn repo_error(path: &Path, error: String) -> RepositoryErrorResult {
-    RepositoryErrorResult {
-        repository: repository_name(path),
-        path: path.display().to_string(),
-        error,
-    }
-}
+const ORIGIN_HEAD_REF: &str = "refs/remotes/origin/HEAD";
 
-async fn inspect_repository(path: &Path) -> InspectRepositoryResult {
+/// Inspects one repository with three git processes: `status`, `for-each-ref` and
+/// `remote get-url`. Detached heads, submodules and LFS cost one more each. The libgit2
+/// backend needs processes only for submodules and LFS.
+async fn inspect_repository(path: &Path, options: &InspectOptions) -> InspectRepositoryResult {
     let repository = repository_name(path);
     let is_git = crate::git::is_git_directory(path).await;
 
@@ -1029,148 +1842,274 @@ async fn inspect_repository(path: &Path) -> InspectRepositoryResult {
             behind: None,
             dirty: None,
             changed_files: Vec::new(),
+            staged_files: Vec::new(),
+            unstaged_files: Vec::new(),
+            untracked_files: Vec::new(),
+            conflicted_files: Vec::new(),
+            stash_count: None,
+            operation_in_progress: None,
+            detached: None,
+            shallow: None,
+            submodules: Vec::new(),
+            lfs: None,
             last_commit: None,
+            diff: None,
+            branches: None,
             error: None,
         };
     }
 
     let mut error = None;
-    let metadata = match get_repository_metadata(path).await {
-        Ok(metadata) => metadata,
+    let backend = options.backend.as_ref();
+    let status = match backend.status(path).await {
+        Ok(status) => status,
         Err(err) => {
             error = Some(err.to_string());
-            None
+            WorkingTreeStatus::default()
         }
     };
-    let current_branch = metadata.as_ref().map(|m| m.current_branch.clone());
-    let default_branch = metadata.as_ref().map(|m| m.default_branch.clone());
+    let detached = status.branch.head.is_none() && error.is_none();
+    let current_branch = status.branch.head.clone().unwrap_or_default();
 
-    let remote_url = optional_git_output(path, &["remote", "get-url", "origin"]).await;
-    let upstream = optional_git_output(
-        path,
-        &[
-            "rev-parse",
-            "--abbrev-ref",
-            "--symbolic-full-name",
-            "@{upstream}",
-        ],
-    )
-    .await;
-    let (ahead, behind) = inspect_ahead_behind(path).await.unwrap_or((None, None));
-    let changed_files = inspect_changed_files(path).await.unwrap_or_default();
-    let dirty = Some(!changed_files.is_empty());
-    let last_commit = inspect_last_commit(path).await;
+    // One `for-each-ref` for the default branch and the current branch's last commit.
+    let mut ref_names = vec![ORIGIN_HEAD_REF.to_string()];
+    if !detached {
+        ref_names.push(format!("refs/heads/{}", current_branch));
+    }
+    let refs = backend.refs(path, &ref_names).await.unwrap_or_default();
+    let remote_head = refs
+        .iter()
+        .find(|details| details.name == ORIGIN_HEAD_REF)
+        .and_then(|details| details.symref.as_deref())
+        .map(|symref| {
+            symref
+                .trim_start_matches("refs/remotes/origin/")
+                .to_string()
+        });
+    // Without `origin/HEAD` the default branch is just the current branch.
+    let has_remote_head = remote_head.is_some();
+    let metadata = error.is_none().then(|| RepositoryMetadata {
+        default_branch: remote_head.unwrap_or_else(|| current_branch.clone()),
+        current_branch,
+        has_remote_head,
+        detached,
+    });
+    let last_commit = match refs.iter().find(|details| details.name != ORIGIN_HEAD_REF) {
+        Some(details) => Some(InspectCommitResult {
+            hash: details.hash.clone(),
+            short_hash: details.short_hash.clone(),
+            subject: details.subject.clone(),
+            committed_at: details.committed_at.clone(),
+        }),
+        None if detached => backend
+            .log(path, "HEAD", 1)
+            .await
+            .ok()
+            .and_then(|commits| commits.into_iter().next())
+            .map(|commit| InspectCommitResult {
+                hash: commit.hash,
+                short_hash: commit.short_hash,
+                subject: commit.subject,
+                committed_at: commit.committed_at,
+            }),
+        None => None,
+    };
+
+    let remote_url = backend.remote_url(path, "origin").await.ok().flatten();
+    let dirty = Some(!status.changed.is_empty());
+    let git_dirs = GitDirs::find(path);
+    let stash_count = git_dirs.as_ref().map(GitDirs::count_stashes);
+    let operation_in_progress = git_dirs
+        .as_ref()
+        .and_then(|dirs| operation_in_progress(&dirs.git_dir))
+        .map(str::to_string);
+    let shallow = git_dirs.as_ref().map(GitDirs::is_shallow);
+    let submodules = match get_submodule_status(path).await {
+        Ok(submodules) => submodules,
+        Err(err) => {
+            error.get_or_insert(err.to_string());
+            Vec::new()
+        }
+    };
+    let lfs = get_lfs_status(path).await;
+    let default_branch = metadata.as_ref().map(|m| m.default_branch.clone());
+    let diff = match (&default_branch, options.diff && has_remote_head) {
+        (Some(default_branch), true) => inspect_branch_diff(path, backend, default_branch).await,
+        _ => None,
+    };
+    let branches = match (&metadata, options.branches) {
+        (Some(metadata), true) => match inspect_branches(path, metadata, options).await {
+            Ok(branches) => Some(branches),
+            Err(err) => {
+                error.get_or_insert(err.to_string());
+                None
+            }
+        },
+        _ => None,
+    };
 
     InspectRepositoryResult {
         repository,
         path: path.display().to_string(),
         is_git,
-        current_branch,
+        current_branch: metadata.as_ref().map(|m| m.current_branch.clone()),
         default_branch,
         remote_url,
-        upstream,
-        ahead,
-        behind,
+        upstream: status.branch.upstream,
+        ahead: status.branch.ahead,
+        behind: status.branch.behind,
         dirty,
-        changed_files,
+        changed_files: status.changed,
+        staged_files: status.staged,
+        unstaged_files: status.unstaged,
+        untracked_files: status.untracked,
+        conflicted_files: status.conflicted,
+        stash_count,
+        operation_in_progress,
+        detached: metadata.as_ref().map(|m| m.detached),
+        shallow,
+        submodules,
+        lfs,
         last_commit,
+        diff,
+        branches,
         error,
     }
 }
 
-async fn optional_git_output(path: &Path, args: &[&str]) -> Option<String> {
-    let output = Command::new("git")
-        .args(args)
-        .current_dir(path)
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
-    }
+const BRANCH_STATUS_MERGED: &str = "merged";
+const BRANCH_STATUS_OPEN_PR: &str = "open-pr";
+const BRANCH_STATUS_STALE: &str = "stale";
+const BRANCH_STATUS_ACTIVE: &str = "active";
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
 
-    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if value.is_empty() {
-        None
+/// Local and `origin` branches other than the default branch, compared against the default
+/// branch. Merge state and ahead/behind counts need `origin/HEAD`.
+async fn inspect_branches(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    options: &InspectOptions,
+) -> Result<Vec<InspectBranchResult>> {
+    let backend = options.backend.as_ref();
+    let base_ref = if metadata.has_remote_head {
+        let default_branch = &metadata.default_branch;
+        backend
+            .resolve_ref(
+                path,
+                &[format!("origin/{}", default_branch), default_branch.clone()],
+            )
+            .await?
     } else {
-        Some(value)
-    }
-}
+        None
+    };
+    let refs = backend
+        .branch_refs(path, "origin", base_ref.as_deref())
+        .await?;
+    let (owner, pull_requests) = match &options.client {
+        Some(client) => open_pull_requests_by_head(path, backend, client).await,
+        None => (None, HashMap::new()),
+    };
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default();
+
+    let mut branches = Vec::new();
+    for branch_ref in refs {
+        if metadata.has_remote_head && branch_ref.branch == metadata.default_branch {
+            continue;
+        }
 
-async fn inspect_ahead_behind(path: &Path) -> std::result::Result<(Option<u32>, Option<u32>), ()> {
-    let output = Command::new("git")
-        .args(["rev-list", "--left-right", "--count", "@{upstream}...HEAD"])
-        .current_dir(path)
-        .output()
-        .await
-        .map_err(|_| ())?;
+        let (ahead, behind) = match &base_ref {
+            Some(base_ref) => {
+                let (ahead, behind) = backend
+                    .ahead_behind(path, base_ref, &branch_ref.name)
+                    .await?;
+                (Some(ahead), Some(behind))
+            }
+            None => (None, None),
+        };
+        let age_days = (now - branch_ref.committed_unix).max(0) / SECONDS_PER_DAY;
+        let stale = age_days as u64 >= options.stale_days;
+        let pull_request = owner.as_ref().and_then(|owner| {
+            pull_requests
+                .get(&format!("{}:{}", owner, branch_ref.branch))
+                .cloned()
+        });
+        let status = if branch_ref.merged == Some(true) {
+            BRANCH_STATUS_MERGED
+        } else if pull_request.is_some() {
+            BRANCH_STATUS_OPEN_PR
+        } else if stale {
+            BRANCH_STATUS_STALE
+        } else {
+            BRANCH_STATUS_ACTIVE
+        };
 
-    if !output.status.success() {
-        return Ok((None, None));
+        branches.push(InspectBranchResult {
+            current: branch_ref.remote.is_none() && branch_ref.branch == metadata.current_branch,
+            name: branch_ref.name,
+            remote: branch_ref.remote,
+            last_commit_at: branch_ref.committed_at,
+            age_days: age_days as u64,
+            ahead,
+            behind,
+            merged: branch_ref.merged,
+            pull_request,
+            stale,
+            status: status.to_string(),
+        });
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut parts = stdout.split_whitespace();
-    let behind = parts.next().and_then(|value| value.parse().ok());
-    let ahead = parts.next().and_then(|value| value.parse().ok());
-
-    Ok((ahead, behind))
+    Ok(branches)
 }
 
-async fn inspect_changed_files(path: &Path) -> std::result::Result<Vec<String>, ()> {
-    let output = Command::new("git")
-        .args(["status", "--porcelain=v1"])
-        .current_dir(path)
-        .output()
-        .await
-        .map_err(|_| ())?;
+/// The `origin` owner and its open pull requests keyed by `owner:branch`, looked up on
+/// `origin` and, for forks, on `upstream`. Lookup failures leave the map empty.
+async fn open_pull_requests_by_head(
+    path: &Path,
+    backend: &dyn GitBackend,
+    client: &GitHubClient,
+) -> (Option<String>, HashMap<String, String>) {
+    let mut pull_requests = HashMap::new();
+    let Some((owner, _)) = remote_repository(path, backend, "origin").await else {
+        return (None, pull_requests);
+    };
 
-    if !output.status.success() {
-        return Ok(Vec::new());
+    for remote in ["origin", "upstream"] {
+        let Some((remote_owner, repo)) = remote_repository(path, backend, remote).await else {
+            continue;
+        };
+        if let Ok(open) = client.list_open_pull_requests(&remote_owner, &repo).await {
+            pull_requests.extend(open);
+        }
     }
 
-    let files = String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .filter_map(parse_porcelain_file)
-        .collect();
-
-    Ok(files)
+    (Some(owner), pull_requests)
 }
 
-fn parse_porcelain_file(line: &str) -> Option<String> {
-    if line.len() < 4 {
-        return None;
-    }
-
-    let path = &line[3..];
-    if let Some((_, new_path)) = path.split_once(" -> ") {
-        Some(new_path.to_string())
-    } else {
-        Some(path.to_string())
-    }
+async fn remote_repository(
+    path: &Path,
+    backend: &dyn GitBackend,
+    remote: &str,
+) -> Option<(String, String)> {
+    let url = backend.remote_url(path, remote).await.ok()??;
+    parse_github_remote(&url)
 }
 
-async fn inspect_last_commit(path: &Path) -> Option<InspectCommitResult> {
-    let output = Command::new("git")
-        .args(["log", "-1", "--format=%H%x00%h%x00%s%x00%cI"])
-        .current_dir(path)
-        .output()
-        .await
-        .ok()?;
-
-    if !output.status.success() {
-        return None;
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let mut parts = stdout.trim_end().split('\0');
-    Some(InspectCommitResult {
-        hash: parts.next()?.to_string(),
-        short_hash: parts.next()?.to_string(),
-        subject: parts.next()?.to_string(),
-        committed_at: parts.next()?.to_string(),
-    })
+async fn inspect_branch_diff(
+    path: &Path,
+    backend: &dyn GitBackend,
+    default_branch: &str,
+) -> Option<BranchDiff> {
+    let candidates = [
+        default_branch.to_string(),
+        format!("origin/{}", default_branch),
+    ];
+    let base_ref = backend.resolve_ref(path, &candidates).await.ok()??;
+    let mut diff = backend.diff(path, &base_ref, "HEAD").await.ok()?;
+    diff.base = default_branch.to_string();
+    Some(diff)
 }
 
 pub async fn setup(token: Option<String>, org: Option<String>, output: OutputFormat) -> Result<()> {
@@ -1233,13 +2172,16 @@ pub async fn setup(token: Option<String>, org: Option<String>, output: OutputFor
     // Save non-secret config only. The token is stored in the OS credential store.
     config.save(&config_path)?;
 
-    if output == OutputFormat::Json {
-        print_json(&serde_json::json!({
-            "config_path": config_path,
-            "default_org": config.default_org,
-            "credential_store": "os",
-            "status": "saved"
-        }))?;
+    if output != OutputFormat::Text {
+        print_output(
+            &SetupResult {
+                config_path: config_path.display().to_string(),
+                default_org: config.default_org.clone(),
+                credential_store: "os".to_string(),
+                status: "saved".to_string(),
+            },
+            output,
+        )?;
     } else {
         println!("\n{}", "Configuration saved successfully!".green());
         println!("Config location: {}", config_path.display());
@@ -1253,16 +2195,19 @@ pub async fn setup(token: Option<String>, org: Option<String>, output: OutputFor
     Ok(())
 }
 
-pub async fn list(org: String, output: OutputFormat) -> Result<()> {
-    let client = GitHubClient::new(load_config(&get_config_path())?)?;
-    let repositories = client.get_repositories(&org).await?;
-
-    if output == OutputFormat::Json {
-        return print_json(&serde_json::json!({
-            "organization": org,
-            "count": repositories.len(),
-            "repositories": repositories
-        }));
+pub async fn list(org: String, cache_mode: CacheMode, output: OutputFormat) -> Result<()> {
+    let client = github_client(cache_mode)?;
+    let repositories = client.get_repositories(&org, cache_mode).await?;
+
+    if output != OutputFormat::Text {
+        return print_output(
+            &RepositoryListResult {
+                organization: org,
+                count: repositories.len(),
+                repositories,
+            },
+            output,
+        );
     }
 
     println!("Fetching repositories...");
@@ -0,0 +1,381 @@
+use crate::error::{GmuxError, Result};
+use crate::git::{
+    get_branch_diff, get_repository_metadata, get_staged_files, git_stdout, preview_staged_files,
+    resolve_branch_ref, BranchDiff, ChangedFile, RepositoryMetadata,
+};
+use crate::output::CommitResult;
+use crate::template::{render_template, TemplateContext};
+use crate::utils::repository_name;
+use clap::Args;
+use regex::Regex;
+use std::path::Path;
+
+pub const STATUS_COMMITTED: &str = "committed";
+pub const STATUS_AMENDED: &str = "amended";
+pub const STATUS_SKIPPED: &str = "skipped";
+pub const STATUS_FAILED: &str = "failed";
+
+const REASON_NOTHING_TO_COMMIT: &str = "nothing to commit";
+
+const CONVENTIONAL_COMMIT_TYPES: &[&str] = &[
+    "build", "chore", "ci", "docs", "feat", "fix", "perf", "refactor", "revert", "style", "test",
+];
+
+#[derive(Args, Clone, Debug)]
+pub struct CommitOptions {
+    /// Commit message template, rendered per repository with the PR template variables
+    #[arg(short, long, required_unless_present = "amend")]
+    pub message: Option<String>,
+    /// Paths to stage before committing
+    pub pathspec: Vec<String>,
+    /// Stage changes to tracked files before committing, like `git commit --all`
+    #[arg(short, long, conflicts_with = "pathspec")]
+    pub all: bool,
+    /// Amend the previous commit, keeping its message unless --message is given
+    #[arg(long)]
+    pub amend: bool,
+    /// Add a Signed-off-by trailer
+    #[arg(short = 's', long)]
+    pub signoff: bool,
+    /// Sign the commit, optionally with a specific key
+    #[arg(
+        short = 'S',
+        long,
+        value_name = "KEYID",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ""
+    )]
+    pub gpg_sign: Option<String>,
+    /// Do not sign the commit, even if commit.gpgSign is set
+    #[arg(long, conflicts_with = "gpg_sign")]
+    pub no_gpg_sign: bool,
+    /// Signature format to sign with (openpgp, ssh or x509), passed to git as gpg.format
+    #[arg(long, value_name = "FORMAT")]
+    pub signing_format: Option<String>,
+    /// Require messages to follow the Conventional Commits format
+    #[arg(long)]
+    pub conventional: bool,
+}
+
+impl CommitResult {
+    fn new(path: &Path, branch: Option<String>) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            branch,
+            status: STATUS_SKIPPED.to_string(),
+            reason: None,
+            commit: None,
+            message: None,
+            files: Vec::new(),
+        }
+    }
+
+    fn fail(mut self, reason: impl Into<String>) -> Self {
+        self.status = STATUS_FAILED.to_string();
+        self.reason = Some(reason.into());
+        self
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.status == STATUS_FAILED
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.status == STATUS_SKIPPED
+    }
+}
+
+/// Stages and commits changes in one repository. Returns `None` for directories that are not
+/// git repositories; git failures are reported as a `failed` result.
+pub async fn commit_repository(path: &Path, options: &CommitOptions) -> Option<CommitResult> {
+    let metadata = match get_repository_metadata(path).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return None,
+        Err(error) => return Some(CommitResult::new(path, None).fail(error.format_error())),
+    };
+    let branch = Some(metadata.current_branch.clone()).filter(|branch| !branch.is_empty());
+    let mut result = CommitResult::new(path, branch);
+
+    Some(match commit(path, &metadata, options, &mut result).await {
+        Ok(()) => result,
+        Err(error) => result.fail(error.format_error()),
+    })
+}
+
+async fn commit(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    options: &CommitOptions,
+    result: &mut CommitResult,
+) -> Result<()> {
+    let add_args = stage_args(path, options).await?;
+    let staged = match &add_args {
+        Some(add_args) => {
+            let add_args: Vec<&str> = add_args.iter().map(String::as_str).collect();
+            preview_staged_files(path, &add_args).await?
+        }
+        None => get_staged_files(path).await?,
+    };
+    if staged.is_empty() && !options.amend {
+        result.reason = Some(REASON_NOTHING_TO_COMMIT.to_string());
+        return Ok(());
+    }
+    result.files = staged.iter().map(|file| file.path.clone()).collect();
+
+    // The message is rendered and validated first so that a rejected message leaves the index
+    // as it was.
+    let message = match &options.message {
+        Some(template) => {
+            let diff = commit_diff(path, metadata, staged).await;
+            let rendered = render_template(
+                template,
+                &TemplateContext::new("", &result.repository, &diff),
+            )?;
+            let message = rendered.trim().to_string();
+            if message.is_empty() {
+                return Err(GmuxError::Validation("empty commit message".to_string()));
+            }
+            if options.conventional {
+                validate_conventional_commit(&message)?;
+            }
+            Some(message)
+        }
+        None => None,
+    };
+
+    if let Some(add_args) = add_args {
+        let mut args = vec!["add"];
+        args.extend(add_args.iter().map(String::as_str));
+        git_stdout(path, &args).await?;
+    }
+
+    let mut args = Vec::new();
+    if let Some(format) = &options.signing_format {
+        args.push("-c".to_string());
+        args.push(format!("gpg.format={}", format));
+    }
+    args.push("commit".to_string());
+    if options.amend {
+        args.push("--amend".to_string());
+    }
+    if options.signoff {
+        args.push("--signoff".to_string());
+    }
+    match options.gpg_sign.as_deref() {
+        Some("") => args.push("--gpg-sign".to_string()),
+        Some(key) => args.push(format!("--gpg-sign={}", key)),
+        None if options.no_gpg_sign => args.push("--no-gpg-sign".to_string()),
+        None => {}
+    }
+    match &message {
+        Some(message) => {
+            args.push("--message".to_string());
+            args.push(message.clone());
+        }
+        None => args.push("--no-edit".to_string()),
+    }
+
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    git_stdout(path, &args).await?;
+    let commit = git_stdout(path, &["rev-parse", "--short", "HEAD"]).await?;
+
+    result.status = if options.amend {
+        STATUS_AMENDED
+    } else {
+        STATUS_COMMITTED
+    }
+    .to_string();
+    result.commit = Some(commit.trim().to_string());
+    result.message = message;
+    Ok(())
+}
+
+/// Arguments for the `git add` that stages the commit, or `None` to commit the index as is.
+/// `--all` stages changes to tracked files only, like `git commit --all`.
+async fn stage_args(path: &Path, options: &CommitOptions) -> Result<Option<Vec<String>>> {
+    if options.all {
+        return Ok(Some(vec!["--update".to_string()]));
+    }
+    if options.pathspec.is_empty() {
+        return Ok(None);
+    }
+
+    // `git add` fails on pathspecs that match nothing, which is expected in some repos.
+    let mut status_args = vec!["status", "--porcelain", "--"];
+    status_args.extend(options.pathspec.iter().map(String::as_str));
+    if git_stdout(path, &status_args).await?.trim().is_empty() {
+        return Ok(None);
+    }
+    let mut args = vec!["--all".to_string(), "--".to_string()];
+    args.extend(options.pathspec.iter().cloned());
+    Ok(Some(args))
+}
+
+/// Template variables for a commit: the files are the staged changes, while the commits are
+/// those on the current branch since it diverged from the default branch.
+async fn commit_diff(
+    path: &Path,
+    metadata: &RepositoryMetadata,
+    staged: Vec<ChangedFile>,
+) -> BranchDiff {
+    let branch_diff = if metadata.has_remote_head {
+        match resolve_branch_ref(path, &metadata.default_branch).await {
+            Ok(Some(base_ref)) => get_branch_diff(path, &base_ref, "HEAD").await.ok(),
+            _ => None,
+        }
+    } else {
+        None
+    };
+    let (merge_base, commits) = branch_diff
+        .map(|diff| (diff.merge_base, diff.commits))
+        .unwrap_or_default();
+
+    BranchDiff {
+        base: metadata.default_branch.clone(),
+        head: metadata.current_branch.clone(),
+        merge_base,
+        insertions: staged.iter().filter_map(|file| file.insertions).sum(),
+        deletions: staged.iter().filter_map(|file| file.deletions).sum(),
+        files: staged,
+        commits,
+    }
+}
+
+/// Checks the header against `type(scope)!: description` and requires a blank line before
+/// any body.
+pub fn validate_conventional_commit(message: &str) -> Result<()> {
+    let pattern = format!(
+        r"^({})(\([^()\s]+\))?!?: \S",
+        CONVENTIONAL_COMMIT_TYPES.join("|")
+    );
+    let regex = Regex::new(&pattern).expect("valid conventional commit pattern");
+
+    let mut lines = message.lines();
+    let header = lines.next().unwrap_or_default();
+    if !regex.is_match(header) {
+        return Err(GmuxError::Validation(format!(
+            "\"{}\" is not a Conventional Commit header; expected `<type>[(scope)][!]: <description>` with type one of {}",
+            header,
+            CONVENTIONAL_COMMIT_TYPES.join(", ")
+        )));
+    }
+    if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+        return Err(GmuxError::Validation(
+            "the commit body must be separated from the header by a blank line".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::TempDir;
+
+    fn options(message: &str) -> CommitOptions {
+        CommitOptions {
+            message: Some(message.to_string()),
+            pathspec: vec![".".to_string()],
+            all: false,
+            amend: false,
+            signoff: false,
+            gpg_sign: None,
+            no_gpg_sign: false,
+            signing_format: None,
+            conventional: false,
+        }
+    }
+
+    #[test]
+    fn test_validate_conventional_commit() {
+        assert!(validate_conventional_commit("feat: add branch command").is_ok());
+        assert!(validate_conventional_commit("fix(cli)!: handle empty output").is_ok());
+        assert!(validate_conventional_commit("docs: readme\n\nMore detail").is_ok());
+        assert!(validate_conventional_commit("Add branch command").is_err());
+        assert!(validate_conventional_commit("feature: add branch command").is_err());
+        assert!(validate_conventional_commit("feat:missing space").is_err());
+        assert!(validate_conventional_commit("feat: header\nbody").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_commit_repository() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        git(repo_path, &["init", "-b", "main"]).await;
+        git(
+            repo_path,
+            &["commit", "--allow-empty", "-m", "Initial commit"],
+        )
+        .await;
+
+        let options =
+            options("chore: update {{ repository_name }} ({{ diff_files|length }} files)");
+        let result = commit_repository(repo_path, &options).await.unwrap();
+        assert!(result.is_skipped());
+        assert_eq!(result.reason.as_deref(), Some(REASON_NOTHING_TO_COMMIT));
+
+        std::fs::write(repo_path.join("a.txt"), "a\n")?;
+        std::fs::write(repo_path.join("b.txt"), "b\n")?;
+        let result = commit_repository(repo_path, &options).await.unwrap();
+        assert_eq!(result.status, STATUS_COMMITTED);
+        assert_eq!(result.files, vec!["a.txt", "b.txt"]);
+
+        let subject = git_stdout(repo_path, &["log", "-1", "--format=%s"]).await?;
+        let expected = format!("chore: update {} (2 files)", repository_name(repo_path));
+        assert_eq!(subject.trim(), expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_commit_rejects_invalid_conventional_message() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        git(repo_path, &["init", "-b", "main"]).await;
+        git(
+            repo_path,
+            &["commit", "--allow-empty", "-m", "Initial commit"],
+        )
+        .await;
+        std::fs::write(repo_path.join("a.txt"), "a\n")?;
+
+        let mut options = options("Update files");
+        options.conventional = true;
+        let result = commit_repository(repo_path, &options).await.unwrap();
+        assert!(result.is_failed());
+
+        let count = git_stdout(repo_path, &["rev-list", "--count", "HEAD"]).await?;
+        assert_eq!(count.trim(), "1");
+        assert!(get_staged_files(repo_path).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_commit_all_skips_untracked_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        git(repo_path, &["init", "-b", "main"]).await;
+        std::fs::write(repo_path.join("tracked.txt"), "a\n")?;
+        git(repo_path, &["add", "tracked.txt"]).await;
+        git(repo_path, &["commit", "-m", "Initial commit"]).await;
+
+        std::fs::write(repo_path.join("untracked.txt"), "b\n")?;
+        let mut options = options("chore: update {{ diff_files|join(', ') }}");
+        options.pathspec.clear();
+        options.all = true;
+        let result = commit_repository(repo_path, &options).await.unwrap();
+        assert!(result.is_skipped());
+
+        std::fs::write(repo_path.join("tracked.txt"), "b\n")?;
+        let result = commit_repository(repo_path, &options).await.unwrap();
+        assert_eq!(result.status, STATUS_COMMITTED);
+        assert_eq!(result.files, vec!["tracked.txt"]);
+        assert_eq!(result.message.as_deref(), Some("chore: update tracked.txt"));
+
+        let untracked = git_stdout(repo_path, &["ls-files", "--others"]).await?;
+        assert_eq!(untracked.trim(), "untracked.txt");
+        Ok(())
+    }
+}
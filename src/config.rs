@@ -1,12 +1,16 @@
-use crate::error::{GmuxError, Result};
+use crate::error::{ConfigErrorKind, GmuxError, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub const DEFAULT_PR_TEMPLATE_NAME: &str = "pr_template.md";
 pub const DEFAULT_CONFIG_DIR: &str = ".gmux";
 pub const DEFAULT_CONFIG_FILE: &str = "config.json";
+pub const DEFAULT_CACHE_DIR: &str = "cache";
+pub const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+pub const DEFAULT_WORKSPACE_CONFIG_FILE: &str = "gmux.json";
 pub const GITHUB_TOKEN_ENV_VAR: &str = "GMUX_GITHUB_TOKEN";
 #[cfg(any(target_os = "macos", target_os = "windows"))]
 const KEYRING_SERVICE: &str = "gmux";
@@ -36,6 +40,11 @@ pub struct Config {
     pub sort: String,
     #[serde(default = "default_direction")]
     pub direction: String,
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// How repositories are read: `cli` runs git, `libgit2` reads them in-process.
+    #[serde(default = "default_git_backend")]
+    pub git_backend: String,
 }
 
 impl fmt::Debug for Config {
@@ -53,6 +62,8 @@ impl fmt::Debug for Config {
             .field("per_page", &self.per_page)
             .field("sort", &self.sort)
             .field("direction", &self.direction)
+            .field("cache_ttl_secs", &self.cache_ttl_secs)
+            .field("git_backend", &self.git_backend)
             .finish()
     }
 }
@@ -65,6 +76,8 @@ impl Default for Config {
             per_page: default_per_page(),
             sort: default_sort(),
             direction: default_direction(),
+            cache_ttl_secs: default_cache_ttl_secs(),
+            git_backend: default_git_backend(),
         }
     }
 }
@@ -78,11 +91,21 @@ fn default_sort() -> String {
 fn default_direction() -> String {
     "desc".to_string()
 }
+fn default_cache_ttl_secs() -> u64 {
+    3600
+}
+fn default_git_backend() -> String {
+    if cfg!(feature = "libgit2") {
+        crate::backend::GIT_BACKEND_LIBGIT2.to_string()
+    } else {
+        crate::backend::GIT_BACKEND_CLI.to_string()
+    }
+}
 
 impl Config {
     pub fn validate(&self) -> Result<()> {
         if self.github_token.is_empty() {
-            return Err(GmuxError::Config("GitHub token is required".into()));
+            return Err(GmuxError::missing_token());
         }
         Ok(())
     }
@@ -94,6 +117,74 @@ impl Config {
     }
 }
 
+/// Per-workspace settings, read from `gmux.json` in the workspace directory.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WorkspaceConfig {
+    /// Base branch for `pr`, keyed by repository directory name.
+    #[serde(default)]
+    pub base_branches: HashMap<String, String>,
+}
+
+pub fn load_workspace_config(dir: &Path) -> Result<WorkspaceConfig> {
+    let path = dir.join(DEFAULT_WORKSPACE_CONFIG_FILE);
+    if !path.exists() {
+        return Ok(WorkspaceConfig::default());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content).map_err(|error| {
+        GmuxError::Config(
+            ConfigErrorKind::Invalid,
+            format!("Invalid {}: {}", path.display(), error),
+        )
+    })
+}
+
+/// Where the GitHub token was found.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TokenSource {
+    /// A legacy token in the config file
+    ConfigFile,
+    Environment,
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    CredentialStore,
+}
+
+impl TokenSource {
+    pub fn label(self) -> String {
+        match self {
+            TokenSource::ConfigFile => "config file".to_string(),
+            TokenSource::Environment => GITHUB_TOKEN_ENV_VAR.to_string(),
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            TokenSource::CredentialStore => "OS credential store".to_string(),
+        }
+    }
+}
+
+/// Finds the GitHub token the way `load_config` does, reporting where it came from.
+pub fn locate_github_token(config: &Config) -> Result<Option<(String, TokenSource)>> {
+    if !config.github_token.is_empty() {
+        return Ok(Some((config.github_token.clone(), TokenSource::ConfigFile)));
+    }
+    if let Ok(token) = std::env::var(GITHUB_TOKEN_ENV_VAR) {
+        if !token.trim().is_empty() {
+            return Ok(Some((token, TokenSource::Environment)));
+        }
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    {
+        return Ok(
+            load_github_token_from_keyring()?.map(|token| (token, TokenSource::CredentialStore))
+        );
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    {
+        Ok(None)
+    }
+}
+
 pub fn load_github_token_from_secure_store() -> Result<Option<String>> {
     if let Ok(token) = std::env::var(GITHUB_TOKEN_ENV_VAR) {
         if !token.trim().is_empty() {
@@ -131,20 +222,24 @@ pub fn save_github_token_to_secure_store(token: &str) -> Result<()> {
 
 #[cfg(not(any(target_os = "macos", target_os = "windows")))]
 pub fn save_github_token_to_secure_store(_token: &str) -> Result<()> {
-    Err(GmuxError::Config(format!(
+    Err(GmuxError::config(format!(
         "OS credential store is not configured for this platform. Set {} instead.",
         GITHUB_TOKEN_ENV_VAR
     )))
 }
 
-fn load_config_file(path: &PathBuf) -> Result<Config> {
+pub fn load_config_file(path: &PathBuf) -> Result<Config> {
     if !path.exists() {
         return Ok(Config::default());
     }
 
     let content = fs::read_to_string(path)?;
-    let config: Config = serde_json::from_str(&content)?;
-    Ok(config)
+    serde_json::from_str(&content).map_err(|error| {
+        GmuxError::Config(
+            ConfigErrorKind::Invalid,
+            format!("Invalid {}: {}", path.display(), error),
+        )
+    })
 }
 
 pub fn load_config_for_setup(path: &PathBuf) -> Result<Config> {
@@ -171,6 +266,14 @@ pub fn get_config_path() -> PathBuf {
     get_config_dir().join(DEFAULT_CONFIG_FILE)
 }
 
+pub fn get_cache_dir() -> PathBuf {
+    get_config_dir().join(DEFAULT_CACHE_DIR)
+}
+
+pub fn get_snapshot_dir() -> PathBuf {
+    get_config_dir().join(DEFAULT_SNAPSHOT_DIR)
+}
+
 pub fn get_template_path() -> PathBuf {
     get_config_dir().join(DEFAULT_PR_TEMPLATE_NAME)
 }
@@ -222,6 +325,7 @@ mod tests {
         assert_eq!(loaded_config.per_page, 100);
         assert_eq!(loaded_config.sort, "updated");
         assert_eq!(loaded_config.direction, "desc");
+        assert_eq!(loaded_config.cache_ttl_secs, 3600);
 
         Ok(())
     }
@@ -245,4 +349,26 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_load_workspace_config() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let config = load_workspace_config(temp_dir.path())?;
+        assert!(config.base_branches.is_empty());
+
+        fs::write(
+            temp_dir.path().join(DEFAULT_WORKSPACE_CONFIG_FILE),
+            r#"{"base_branches": {"service-a": "develop"}}"#,
+        )?;
+        let config = load_workspace_config(temp_dir.path())?;
+        assert_eq!(
+            config.base_branches.get("service-a").map(String::as_str),
+            Some("develop")
+        );
+
+        fs::write(temp_dir.path().join(DEFAULT_WORKSPACE_CONFIG_FILE), "{")?;
+        assert!(load_workspace_config(temp_dir.path()).is_err());
+
+        Ok(())
+    }
 }
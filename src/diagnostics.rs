@@ -0,0 +1,153 @@
+/// The reason given when a command needs a branch but HEAD is detached.
+pub const DETACHED_HEAD: &str = "HEAD is detached";
+/// The reason given when a command needs the default branch but `origin/HEAD` is not set.
+pub const MISSING_REMOTE_HEAD: &str = "could not determine the default branch";
+
+/// A common failure recognized from an error message or git's stderr.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Diagnosis {
+    GitAuthentication,
+    SsoRequired,
+    ProtectedBranch,
+    MissingRemoteHead,
+    DetachedHead,
+    GitNotInstalled,
+}
+
+/// Lowercase fragments of the messages each failure produces, checked in order. SSO and
+/// protected-branch rejections come first because their output also reads as an
+/// authentication or push failure.
+const PATTERNS: &[(&str, Diagnosis)] = &[
+    // GitHub's own wording, since a bare "saml" also matches names like `saml-service`.
+    ("organization saml enforcement", Diagnosis::SsoRequired),
+    ("saml sso", Diagnosis::SsoRequired),
+    ("protected branch", Diagnosis::ProtectedBranch),
+    ("gh006", Diagnosis::ProtectedBranch),
+    ("authentication failed", Diagnosis::GitAuthentication),
+    ("could not read username", Diagnosis::GitAuthentication),
+    ("invalid username or password", Diagnosis::GitAuthentication),
+    ("terminal prompts disabled", Diagnosis::GitAuthentication),
+    (
+        "permission denied (publickey)",
+        Diagnosis::GitAuthentication,
+    ),
+    (
+        "could not determine the default branch",
+        Diagnosis::MissingRemoteHead,
+    ),
+    ("refs/remotes/origin/head", Diagnosis::MissingRemoteHead),
+    ("head is detached", Diagnosis::DetachedHead),
+    ("not currently on a branch", Diagnosis::DetachedHead),
+];
+
+impl Diagnosis {
+    /// The stable error code reported in JSON errors.
+    pub fn code(self) -> &'static str {
+        match self {
+            Diagnosis::GitAuthentication => "git.auth_failed",
+            Diagnosis::SsoRequired => "github.sso_required",
+            Diagnosis::ProtectedBranch => "git.protected_branch",
+            Diagnosis::MissingRemoteHead => "git.missing_remote_head",
+            Diagnosis::DetachedHead => "git.detached_head",
+            Diagnosis::GitNotInstalled => "git.not_installed",
+        }
+    }
+
+    pub fn hint(self) -> &'static str {
+        match self {
+            Diagnosis::GitAuthentication => {
+                "git could not authenticate with the remote; set up a credential helper (e.g. `gh auth setup-git`) or an SSH key"
+            }
+            Diagnosis::SsoRequired => {
+                "the organization enforces SAML SSO; authorize your token or SSH key for it under GitHub Settings > Developer settings > Personal access tokens > Configure SSO"
+            }
+            Diagnosis::ProtectedBranch => {
+                "the branch is protected; push a feature branch and open a pull request with `gmux pr`"
+            }
+            Diagnosis::MissingRemoteHead => {
+                "origin/HEAD is not set; run `git remote set-head origin --auto`, or `gmux git remote set-head origin --auto` for every repository"
+            }
+            Diagnosis::DetachedHead => "check out a branch with `git switch <branch>`",
+            Diagnosis::GitNotInstalled => "install git and make sure it is on your PATH",
+        }
+    }
+}
+
+/// Recognizes a failure from an error message or git's stderr.
+pub fn diagnose(message: &str) -> Option<Diagnosis> {
+    let message = message.to_lowercase();
+    PATTERNS
+        .iter()
+        .find(|(pattern, _)| message.contains(pattern))
+        .map(|(_, diagnosis)| *diagnosis)
+}
+
+/// Appends the hint for a recognized failure, for per-repository reasons.
+pub fn with_hint(message: impl Into<String>) -> String {
+    let message = message.into();
+    match diagnose(&message) {
+        Some(diagnosis) => format!("{}\nhint: {}", message, diagnosis.hint()),
+        None => message,
+    }
+}
+
+/// Whether a `git` executable is on the PATH. Spawning git fails with a bare "No such file or
+/// directory", so this tells a missing git apart from other missing files.
+pub fn git_installed() -> bool {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return false;
+    };
+    std::env::split_paths(&paths).any(|directory| {
+        ["git", "git.exe"]
+            .iter()
+            .any(|name| directory.join(name).is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose() {
+        assert_eq!(
+            diagnose("remote: Invalid username or password.\nfatal: Authentication failed for 'https://github.com/acme/api.git/'"),
+            Some(Diagnosis::GitAuthentication)
+        );
+        assert_eq!(
+            diagnose("git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository."),
+            Some(Diagnosis::GitAuthentication)
+        );
+        assert_eq!(
+            diagnose("remote: The 'acme' organization has enabled or enforced SAML SSO. To access this repository, you must re-authorize the OAuth Application.\nfatal: Authentication failed"),
+            Some(Diagnosis::SsoRequired)
+        );
+        assert_eq!(
+            diagnose("Resource protected by organization SAML enforcement. You must grant your Personal Access token access to this organization."),
+            Some(Diagnosis::SsoRequired)
+        );
+        assert_eq!(
+            diagnose("remote: error: GH006: Protected branch update failed for refs/heads/main.\n ! [remote rejected] main -> main (protected branch hook declined)"),
+            Some(Diagnosis::ProtectedBranch)
+        );
+        assert_eq!(
+            diagnose("fatal: ref refs/remotes/origin/HEAD is not a symbolic ref"),
+            Some(Diagnosis::MissingRemoteHead)
+        );
+        assert_eq!(diagnose(DETACHED_HEAD), Some(Diagnosis::DetachedHead));
+        assert_eq!(
+            diagnose("fatal: repository 'https://github.com/samlader/saml-service.git/' not found"),
+            None
+        );
+        assert_eq!(diagnose("fatal: bad revision 'main'"), None);
+    }
+
+    #[test]
+    fn test_with_hint() {
+        assert_eq!(
+            with_hint(DETACHED_HEAD),
+            "HEAD is detached\nhint: check out a branch with `git switch <branch>`"
+        );
+        assert_eq!(with_hint("nothing to commit"), "nothing to commit");
+    }
+}
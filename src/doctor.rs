@@ -0,0 +1,409 @@
+use crate::backend::{git_backend, GitBackend, GIT_BACKEND_CLI};
+use crate::config::{get_config_path, load_config_file, locate_github_token, Config};
+use crate::diagnostics::{Diagnosis, DETACHED_HEAD};
+use crate::error::GmuxError;
+use crate::git::{git_stdout, parse_github_remote};
+use crate::github::{check_api_reachable, GitHubClient};
+use crate::output::{DoctorCheck, DoctorRepositoryResult, DoctorResult};
+use crate::utils::{find_template_path, repository_name};
+use std::path::Path;
+use tokio::process::Command;
+
+pub const STATUS_PASS: &str = "pass";
+pub const STATUS_WARN: &str = "warn";
+pub const STATUS_FAIL: &str = "fail";
+
+/// The classic token scopes `gmux setup` asks for, with the broader scopes that imply them.
+const RECOMMENDED_SCOPES: &[(&str, &[&str])] =
+    &[("repo", &[]), ("read:org", &["write:org", "admin:org"])];
+
+impl DoctorCheck {
+    fn new(name: &str, status: &str, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: status.to_string(),
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self::new(name, STATUS_PASS, message)
+    }
+
+    fn warn(name: &str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self::new(name, STATUS_WARN, message).with_hint(hint)
+    }
+
+    fn fail(name: &str, message: impl Into<String>, hint: Option<String>) -> Self {
+        let mut check = Self::new(name, STATUS_FAIL, message);
+        check.hint = hint;
+        check
+    }
+
+    fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+}
+
+impl DoctorResult {
+    pub fn new(checks: Vec<DoctorCheck>, repositories: Vec<DoctorRepositoryResult>) -> Self {
+        let statuses: Vec<&str> = checks
+            .iter()
+            .chain(repositories.iter().flat_map(|result| &result.checks))
+            .map(|check| check.status.as_str())
+            .collect();
+        let count = |status: &str| statuses.iter().filter(|s| **s == status).count();
+        Self {
+            passed: count(STATUS_PASS),
+            warned: count(STATUS_WARN),
+            failed: count(STATUS_FAIL),
+            checks,
+            repositories,
+        }
+    }
+}
+
+/// The worst of the statuses, `pass` when there are none.
+fn worst_status(checks: &[DoctorCheck]) -> &'static str {
+    if checks.iter().any(|check| check.status == STATUS_FAIL) {
+        STATUS_FAIL
+    } else if checks.iter().any(|check| check.status == STATUS_WARN) {
+        STATUS_WARN
+    } else {
+        STATUS_PASS
+    }
+}
+
+/// Checks git, the config file and git backend, the GitHub token and API, and the PR template.
+pub async fn check_environment() -> Vec<DoctorCheck> {
+    let mut checks = vec![check_git().await];
+    let (config_check, config) = check_config();
+    let config = config.unwrap_or_default();
+    checks.push(config_check);
+    checks.push(check_git_backend(&config));
+    checks.extend(check_github(&config).await);
+    checks.push(check_template());
+    checks
+}
+
+async fn check_git() -> DoctorCheck {
+    match Command::new("git").arg("--version").output().await {
+        Ok(output) if output.status.success() => DoctorCheck::pass(
+            "git",
+            String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        ),
+        Ok(output) => DoctorCheck::fail(
+            "git",
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            None,
+        ),
+        Err(error) => DoctorCheck::fail(
+            "git",
+            format!("git could not be run: {}", error),
+            Some(Diagnosis::GitNotInstalled.hint().to_string()),
+        ),
+    }
+}
+
+fn check_git_backend(config: &Config) -> DoctorCheck {
+    match git_backend(config) {
+        Ok(backend) => DoctorCheck::pass("git backend", backend.name()),
+        Err(error) => DoctorCheck::fail(
+            "git backend",
+            error.message(),
+            Some(format!(
+                "set git_backend to `{}` in {}",
+                GIT_BACKEND_CLI,
+                get_config_path().display()
+            )),
+        ),
+    }
+}
+
+fn check_config() -> (DoctorCheck, Option<Config>) {
+    let path = get_config_path();
+    if !path.exists() {
+        return (
+            DoctorCheck::warn(
+                "config",
+                format!("{} not found", path.display()),
+                "run `gmux setup`",
+            ),
+            None,
+        );
+    }
+    match load_config_file(&path) {
+        Ok(config) => (
+            DoctorCheck::pass("config", path.display().to_string()),
+            Some(config),
+        ),
+        Err(error) => (
+            DoctorCheck::fail(
+                "config",
+                format!("{}: {}", path.display(), error),
+                Some("fix the file or run `gmux setup` to rewrite it".to_string()),
+            ),
+            None,
+        ),
+    }
+}
+
+/// Checks the token, its scopes and that the API is reachable, with a single request when
+/// there is a token.
+async fn check_github(config: &Config) -> Vec<DoctorCheck> {
+    let (token, source) = match locate_github_token(config) {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            let error = GmuxError::missing_token();
+            return vec![
+                DoctorCheck::fail("token", "no GitHub token found", error.hint()),
+                check_api_without_token().await,
+            ];
+        }
+        Err(error) => {
+            return vec![
+                DoctorCheck::fail("token", error.message(), error.hint()),
+                check_api_without_token().await,
+            ]
+        }
+    };
+
+    let client = match GitHubClient::new(Config {
+        github_token: token,
+        ..config.clone()
+    }) {
+        Ok(client) => client,
+        Err(error) => return vec![DoctorCheck::fail("api", error.message(), error.hint())],
+    };
+    match client.token_info().await {
+        Ok(info) => vec![
+            DoctorCheck::pass(
+                "token",
+                format!("from {}, authenticated as {}", source.label(), info.login),
+            ),
+            check_scopes(info.scopes.as_deref()),
+            DoctorCheck::pass("api", "api.github.com is reachable"),
+        ],
+        Err(error) if error.code() == "github.unreachable" => vec![
+            DoctorCheck::warn(
+                "token",
+                format!("from {}, not verified", source.label()),
+                "the API could not be reached",
+            ),
+            DoctorCheck::fail("api", error.message(), error.hint()),
+        ],
+        Err(error) => vec![
+            DoctorCheck::fail(
+                "token",
+                format!("from {}: {}", source.label(), error.message()),
+                error.hint(),
+            ),
+            DoctorCheck::pass("api", "api.github.com is reachable"),
+        ],
+    }
+}
+
+async fn check_api_without_token() -> DoctorCheck {
+    match check_api_reachable().await {
+        Ok(()) => DoctorCheck::pass("api", "api.github.com is reachable"),
+        Err(error) => DoctorCheck::fail("api", error.message(), error.hint()),
+    }
+}
+
+/// Fine-grained tokens report no scopes, so they can only be checked by using them.
+fn check_scopes(scopes: Option<&[String]>) -> DoctorCheck {
+    let Some(scopes) = scopes else {
+        return DoctorCheck::warn(
+            "scopes",
+            "the token reports no scopes, as fine-grained tokens do",
+            "make sure it can read the repositories and write pull requests",
+        );
+    };
+    let missing: Vec<&str> = RECOMMENDED_SCOPES
+        .iter()
+        .filter(|(scope, implied_by)| {
+            !scopes
+                .iter()
+                .any(|granted| granted == scope || implied_by.contains(&granted.as_str()))
+        })
+        .map(|(scope, _)| *scope)
+        .collect();
+    if missing.is_empty() {
+        DoctorCheck::pass("scopes", scopes.join(", "))
+    } else {
+        DoctorCheck::warn(
+            "scopes",
+            format!("missing {}", missing.join(", ")),
+            "create a token with the repo and read:org scopes and run `gmux setup --token <token>`",
+        )
+    }
+}
+
+fn check_template() -> DoctorCheck {
+    match find_template_path() {
+        Ok(Some(path)) => DoctorCheck::pass("template", path.display().to_string()),
+        Ok(None) => DoctorCheck::warn(
+            "template",
+            "no PR template found",
+            "run `gmux init` before `gmux pr`",
+        ),
+        Err(error) => DoctorCheck::fail("template", error.to_string(), None),
+    }
+}
+
+/// Checks one repository's remote, `origin/HEAD`, HEAD and history. Returns `None` for
+/// directories that are not git repositories.
+pub async fn check_repository(
+    path: &Path,
+    backend: &dyn GitBackend,
+) -> Option<DoctorRepositoryResult> {
+    let checks = match backend.metadata(path).await {
+        Ok(Some(metadata)) => {
+            let mut checks = Vec::new();
+            match backend.remote_url(path, "origin").await {
+                Ok(Some(url)) => {
+                    checks.push(match parse_github_remote(&url) {
+                        Some((owner, repo)) => {
+                            DoctorCheck::pass("remote", format!("{}/{}", owner, repo))
+                        }
+                        None => DoctorCheck::warn(
+                            "remote",
+                            format!("origin {} is not a GitHub remote", url),
+                            "`gmux pr` needs an origin on GitHub",
+                        ),
+                    });
+                    checks.push(if metadata.has_remote_head {
+                        DoctorCheck::pass(
+                            "origin/HEAD",
+                            format!("default branch {}", metadata.default_branch),
+                        )
+                    } else {
+                        DoctorCheck::warn(
+                            "origin/HEAD",
+                            "origin/HEAD is not set",
+                            Diagnosis::MissingRemoteHead.hint(),
+                        )
+                    });
+                }
+                Ok(None) => checks.push(DoctorCheck::warn(
+                    "remote",
+                    "no origin remote",
+                    "add one with `git remote add origin <url>`",
+                )),
+                Err(error) => checks.push(DoctorCheck::fail("remote", error.message(), None)),
+            }
+            checks.push(if metadata.detached {
+                DoctorCheck::warn("HEAD", DETACHED_HEAD, Diagnosis::DetachedHead.hint())
+            } else {
+                DoctorCheck::pass("HEAD", format!("on {}", metadata.current_branch))
+            });
+            checks.push(
+                match git_stdout(path, &["rev-parse", "--is-shallow-repository"]).await {
+                    Ok(shallow) if shallow.trim() == "true" => DoctorCheck::warn(
+                        "shallow",
+                        "shallow clone",
+                        "run `git fetch --unshallow` for full history; merge bases and branch diffs may be missing",
+                    ),
+                    Ok(_) => DoctorCheck::pass("shallow", "full history"),
+                    Err(error) => DoctorCheck::fail("shallow", error.message(), None),
+                },
+            );
+            checks
+        }
+        Ok(None) => return None,
+        Err(error) => vec![DoctorCheck::fail("metadata", error.message(), error.hint())],
+    };
+
+    Some(DoctorRepositoryResult {
+        repository: repository_name(path),
+        path: path.display().to_string(),
+        status: worst_status(&checks).to_string(),
+        checks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::CliBackend;
+    use crate::test_support::git;
+    use tempfile::TempDir;
+
+    fn status<'a>(result: &'a DoctorRepositoryResult, name: &str) -> &'a str {
+        result
+            .checks
+            .iter()
+            .find(|check| check.name == name)
+            .map_or("missing", |check| check.status.as_str())
+    }
+
+    #[tokio::test]
+    async fn test_check_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let seed = temp_dir.path().join("seed");
+        std::fs::create_dir(&seed).unwrap();
+        git(&seed, &["init", "-b", "main"]).await;
+        git(&seed, &["commit", "--allow-empty", "-m", "Initial commit"]).await;
+        assert!(check_repository(temp_dir.path(), &CliBackend)
+            .await
+            .is_none());
+
+        let result = check_repository(&seed, &CliBackend).await.unwrap();
+        assert_eq!(status(&result, "remote"), STATUS_WARN);
+        assert_eq!(status(&result, "origin/HEAD"), "missing");
+        assert_eq!(status(&result, "HEAD"), STATUS_PASS);
+        assert_eq!(status(&result, "shallow"), STATUS_PASS);
+        assert_eq!(result.status, STATUS_WARN);
+
+        git(
+            &seed,
+            &["remote", "add", "origin", "git@github.com:acme/api.git"],
+        )
+        .await;
+        git(&seed, &["checkout", "--detach"]).await;
+        let result = check_repository(&seed, &CliBackend).await.unwrap();
+        assert_eq!(status(&result, "remote"), STATUS_PASS);
+        assert_eq!(status(&result, "origin/HEAD"), STATUS_WARN);
+        assert_eq!(status(&result, "HEAD"), STATUS_WARN);
+    }
+
+    #[test]
+    fn test_check_scopes() {
+        let scopes = |scopes: &[&str]| {
+            check_scopes(Some(
+                &scopes.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            ))
+        };
+        assert_eq!(scopes(&["repo", "read:org"]).status, STATUS_PASS);
+        assert_eq!(scopes(&["admin:org", "repo", "gist"]).status, STATUS_PASS);
+        let check = scopes(&["read:org"]);
+        assert_eq!(check.status, STATUS_WARN);
+        assert_eq!(check.message, "missing repo");
+        assert_eq!(check_scopes(None).status, STATUS_WARN);
+    }
+
+    #[test]
+    fn test_doctor_result_counts() {
+        let repository = DoctorRepositoryResult {
+            repository: "api".to_string(),
+            path: "/ws/api".to_string(),
+            status: STATUS_FAIL.to_string(),
+            checks: vec![
+                DoctorCheck::pass("HEAD", "on main"),
+                DoctorCheck::fail("shallow", "boom", None),
+            ],
+        };
+        let result = DoctorResult::new(
+            vec![DoctorCheck::warn(
+                "template",
+                "no PR template found",
+                "run `gmux init`",
+            )],
+            vec![repository],
+        );
+        assert_eq!((result.passed, result.warned, result.failed), (1, 1, 1));
+        assert_eq!(worst_status(&result.checks), STATUS_WARN);
+    }
+}
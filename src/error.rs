@@ -1,3 +1,6 @@
+use crate::diagnostics::{diagnose, git_installed, Diagnosis};
+use crate::output::ErrorResult;
+use serde_json::{json, Value};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -8,8 +11,8 @@ pub enum GmuxError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
 
-    #[error("Configuration error: {0}")]
-    Config(String),
+    #[error("Configuration error: {1}")]
+    Config(ConfigErrorKind, String),
 
     #[error("Git error: {0}")]
     Git(String),
@@ -25,30 +28,296 @@ pub enum GmuxError {
     CredentialStore(#[from] keyring::Error),
 
     #[error("Anyhow error: {0}")]
-    Anyhow(#[from] anyhow::Error),
+    Anyhow(anyhow::Error),
+
+    /// A command ran but some of its repositories or checks failed. Its results have already
+    /// been printed.
+    #[error("{0}")]
+    RepositoriesFailed(String),
+}
+
+/// What a `GmuxError::Config` is about, which decides its code and hint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigErrorKind {
+    /// No GitHub token was found.
+    MissingToken,
+    /// `config.json` or `gmux.json` could not be parsed.
+    Invalid,
+    /// `--offline` was given without a cached repository listing.
+    MissingCache,
+    /// No snapshot has the given name.
+    MissingSnapshot,
+    /// A saved snapshot could not be parsed.
+    InvalidSnapshot,
+    /// Any other configuration problem, reported as `config.error`.
+    Other,
+}
+
+impl From<anyhow::Error> for GmuxError {
+    /// An invalid regex, such as a `--filter`, is a bad argument rather than an internal error.
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<regex::Error>() {
+            Ok(error) => GmuxError::Validation(format!("Invalid regex pattern: {}", error)),
+            Err(error) => GmuxError::Anyhow(error),
+        }
+    }
 }
 
 impl GmuxError {
+    pub fn config(message: impl Into<String>) -> Self {
+        GmuxError::Config(ConfigErrorKind::Other, message.into())
+    }
+
+    pub fn missing_token() -> Self {
+        GmuxError::Config(
+            ConfigErrorKind::MissingToken,
+            "GitHub token is required".to_string(),
+        )
+    }
+
     pub fn format_error(&self) -> String {
-        match self {
+        let message = match self {
+            GmuxError::GitHubApi(octocrab::Error::GitHub { source, .. }) => {
+                format!("GitHub API Error: {}", source.message)
+            }
             GmuxError::GitHubApi(error) => {
-                if let octocrab::Error::GitHub { source, .. } = error {
-                    if let Some(docs_url) = &source.documentation_url {
-                        format!(
-                            "GitHub API Error: {}\n\nNext steps:\n1. Visit {} to resolve the issue\n2. Ensure your token has the required permissions",
-                            source.message,
-                            docs_url
-                        )
-                    } else {
-                        format!("GitHub API Error: {}", source.message)
-                    }
+                format!(
+                    "GitHub API Error: {}",
+                    without_backtrace(&error.to_string())
+                )
+            }
+            _ => self.message(),
+        };
+        if let GmuxError::GitHubApi(octocrab::Error::GitHub { source, .. }) = self {
+            if let (None, Some(docs_url)) = (self.diagnosis(), &source.documentation_url) {
+                return format!(
+                    "{}\n\nNext steps:\n1. Visit {} to resolve the issue\n2. Ensure your token has the required permissions",
+                    message, docs_url
+                );
+            }
+        }
+        match self.hint() {
+            Some(hint) => format!("{}\nhint: {}", message, hint),
+            None => message,
+        }
+    }
+
+    /// The error without the backtrace octocrab appends to transport errors.
+    pub fn message(&self) -> String {
+        without_backtrace(&self.to_string()).to_string()
+    }
+
+    /// A common failure recognized from the error, see `crate::diagnostics`.
+    pub fn diagnosis(&self) -> Option<Diagnosis> {
+        match self {
+            GmuxError::GitHubApi(octocrab::Error::GitHub { source, .. }) => {
+                diagnose(&source.message)
+            }
+            GmuxError::Git(message) => diagnose(message),
+            GmuxError::Io(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                (!git_installed()).then_some(Diagnosis::GitNotInstalled)
+            }
+            _ => None,
+        }
+    }
+
+    /// The process exit code, one per variant. 2 matches clap's code for invalid arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            GmuxError::Anyhow(_) => 1,
+            GmuxError::Validation(_) => 2,
+            GmuxError::Config(..) => 3,
+            GmuxError::Git(_) => 4,
+            GmuxError::GitHubApi(_) => 5,
+            GmuxError::Io(_) => 6,
+            GmuxError::Json(_) => 7,
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            GmuxError::CredentialStore(_) => 8,
+            GmuxError::RepositoriesFailed(_) => 9,
+        }
+    }
+
+    /// A stable, machine-readable identifier for the error, `<area>.<reason>`.
+    pub fn code(&self) -> &'static str {
+        if let Some(diagnosis) = self.diagnosis() {
+            return diagnosis.code();
+        }
+        match self {
+            GmuxError::GitHubApi(octocrab::Error::GitHub { source, .. }) => {
+                let message = source.message.to_lowercase();
+                if message.contains("rate limit") {
+                    "github.rate_limited"
+                } else if message.contains("bad credentials") {
+                    "github.bad_credentials"
+                } else if message == "not found" {
+                    "github.not_found"
                 } else {
-                    format!("GitHub API Error: {}", error)
+                    "github.api_error"
                 }
             }
-            _ => self.to_string(),
+            GmuxError::GitHubApi(octocrab::Error::Http { .. })
+            | GmuxError::GitHubApi(octocrab::Error::Hyper { .. })
+            | GmuxError::GitHubApi(octocrab::Error::Service { .. }) => "github.unreachable",
+            GmuxError::GitHubApi(_) => "github.api_error",
+            GmuxError::Io(error) => match error.kind() {
+                std::io::ErrorKind::NotFound => "io.not_found",
+                std::io::ErrorKind::PermissionDenied => "io.permission_denied",
+                _ => "io.error",
+            },
+            GmuxError::Config(kind, _) => match kind {
+                ConfigErrorKind::MissingToken => "config.missing_token",
+                ConfigErrorKind::Invalid => "config.invalid",
+                ConfigErrorKind::MissingCache => "config.missing_cache",
+                ConfigErrorKind::MissingSnapshot => "config.missing_snapshot",
+                ConfigErrorKind::InvalidSnapshot => "config.invalid_snapshot",
+                ConfigErrorKind::Other => "config.error",
+            },
+            GmuxError::Git(message) => {
+                if message.contains("not a git repository") {
+                    "git.not_a_repo"
+                } else {
+                    "git.failed"
+                }
+            }
+            GmuxError::Validation(_) => "validation.invalid_argument",
+            GmuxError::Json(_) => "json.invalid",
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            GmuxError::CredentialStore(_) => "credential_store.error",
+            GmuxError::Anyhow(_) => "internal.error",
+            GmuxError::RepositoriesFailed(_) => "repositories.failed",
+        }
+    }
+
+    /// What the user can do about the error, when there is something specific.
+    pub fn hint(&self) -> Option<String> {
+        if let Some(diagnosis) = self.diagnosis() {
+            return Some(diagnosis.hint().to_string());
+        }
+        let hint = match self.code() {
+            "config.missing_token" => format!(
+                "run `gmux setup` or set {}",
+                crate::config::GITHUB_TOKEN_ENV_VAR
+            ),
+            "config.invalid" => {
+                "fix the file, or run `gmux setup` to rewrite config.json".to_string()
+            }
+            "config.missing_cache" => "run the command once without --offline".to_string(),
+            "config.missing_snapshot" => {
+                "run `gmux snapshot list` to see the saved snapshots".to_string()
+            }
+            "config.invalid_snapshot" => format!(
+                "fix or delete the snapshot file in {}",
+                crate::config::get_snapshot_dir().display()
+            ),
+            "github.rate_limited" => {
+                "wait for the rate limit to reset, or use a token with a higher limit".to_string()
+            }
+            "github.bad_credentials" => {
+                "the token is invalid or expired; run `gmux setup` with a new token".to_string()
+            }
+            "github.not_found" => {
+                "check the name, and that the token can access the organization".to_string()
+            }
+            "github.unreachable" => "check the network connection to api.github.com".to_string(),
+            "git.not_a_repo" => "run the command from inside a git repository".to_string(),
+            _ => match self {
+                GmuxError::GitHubApi(octocrab::Error::GitHub { source, .. }) => {
+                    let docs_url = source.documentation_url.as_ref()?;
+                    format!("see {}", docs_url)
+                }
+                _ => return None,
+            },
+        };
+        Some(hint)
+    }
+
+    /// Structured context for the error, such as GitHub's field errors.
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            GmuxError::GitHubApi(octocrab::Error::GitHub { source, .. }) => Some(json!({
+                "documentation_url": source.documentation_url,
+                "errors": source.errors,
+            })),
+            GmuxError::Io(error) => Some(json!({ "kind": error.kind().to_string() })),
+            GmuxError::Json(error) => Some(json!({
+                "line": error.line(),
+                "column": error.column(),
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn to_result(&self) -> ErrorResult {
+        ErrorResult {
+            code: self.code().to_string(),
+            message: self.message(),
+            hint: self.hint(),
+            details: self.details(),
+            exit_code: self.exit_code(),
         }
     }
 }
 
 pub type Result<T> = std::result::Result<T, GmuxError>;
+
+fn without_backtrace(message: &str) -> &str {
+    message
+        .split_once("\n\nFound at ")
+        .map_or(message, |(message, _)| message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes() {
+        let error = GmuxError::missing_token();
+        assert_eq!(error.code(), "config.missing_token");
+        assert_eq!(error.exit_code(), 3);
+        assert!(error.hint().unwrap().contains("gmux setup"));
+
+        let error = GmuxError::Git(
+            "fatal: not a git repository (or any of the parent directories): .git".into(),
+        );
+        assert_eq!(error.code(), "git.not_a_repo");
+        assert_eq!(error.exit_code(), 4);
+
+        let error = GmuxError::Io(std::io::Error::new(std::io::ErrorKind::NotFound, "gone"));
+        assert_eq!(error.code(), "io.not_found");
+        assert_eq!(error.details().unwrap()["kind"], "entity not found");
+
+        let error = GmuxError::Config(ConfigErrorKind::InvalidSnapshot, "Invalid snapshot".into());
+        assert_eq!(error.code(), "config.invalid_snapshot");
+        assert!(!error.hint().unwrap().contains("gmux setup"));
+
+        let error = GmuxError::from(crate::utils::repository_paths(Some("(")).unwrap_err());
+        assert_eq!(error.code(), "validation.invalid_argument");
+        assert_eq!(error.exit_code(), 2);
+        assert!(error.message().contains("Invalid regex pattern"));
+
+        let error = GmuxError::Validation("--fields contains an empty field name".into());
+        let result = serde_json::to_value(error.to_result()).unwrap();
+        assert_eq!(result["code"], "validation.invalid_argument");
+        assert_eq!(result["exit_code"], 2);
+        assert_eq!(result["hint"], Value::Null);
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            GmuxError::Anyhow(anyhow::anyhow!("boom")),
+            GmuxError::Validation(String::new()),
+            GmuxError::config(String::new()),
+            GmuxError::Git(String::new()),
+            GmuxError::Io(std::io::Error::other("boom")),
+            GmuxError::Json(serde_json::from_str::<Value>("{").unwrap_err()),
+            GmuxError::RepositoriesFailed(String::new()),
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(GmuxError::exit_code).collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0));
+    }
+}
@@ -1,11 +1,19 @@
-use crate::error::Result;
-use std::path::Path;
+use crate::error::{GmuxError, Result};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use tokio::process::Command;
 
 #[derive(Debug)]
 pub struct RepositoryMetadata {
     pub current_branch: String,
     pub default_branch: String,
+    /// Whether `default_branch` came from `origin/HEAD` rather than the current-branch fallback.
+    pub has_remote_head: bool,
+    /// Whether HEAD points at a commit rather than a branch, leaving `current_branch` empty.
+    pub detached: bool,
 }
 
 pub async fn is_git_directory(path: &Path) -> bool {
@@ -22,72 +30,898 @@ pub async fn get_repository_metadata(path: &Path) -> Result<Option<RepositoryMet
         .current_dir(path)
         .output()
         .await?;
+    // `symbolic-ref` fails when HEAD is detached.
+    let detached = !current_branch.status.success();
     let current_branch = String::from_utf8_lossy(&current_branch.stdout)
         .trim()
         .to_string();
 
     // Try to get the default branch from the remote HEAD, else fall back to current branch
-    let default_branch_output = Command::new("git")
-        .args(["symbolic-ref", "refs/remotes/origin/HEAD"])
-        .current_dir(path)
-        .output()
-        .await?;
-    let default_branch = if default_branch_output.status.success() {
-        String::from_utf8_lossy(&default_branch_output.stdout)
-            .trim()
-            .replace("refs/remotes/origin/", "")
-    } else {
-        current_branch.clone()
-    };
+    let remote_head = get_remote_head(path, "origin").await?;
+    let has_remote_head = remote_head.is_some();
+    let default_branch = remote_head.unwrap_or_else(|| current_branch.clone());
 
     Ok(Some(RepositoryMetadata {
         current_branch,
         default_branch,
+        has_remote_head,
+        detached,
     }))
 }
 
-pub async fn get_diff_file_names(path: &Path, base_branch: &str) -> Result<Vec<String>> {
+/// The branch `refs/remotes/<remote>/HEAD` points at, if it is set.
+pub async fn get_remote_head(path: &Path, remote: &str) -> Result<Option<String>> {
     let output = Command::new("git")
-        .args(["diff", "--name-only", base_branch])
+        .args(["symbolic-ref", &format!("refs/remotes/{}/HEAD", remote)])
         .current_dir(path)
         .output()
         .await?;
+    if !output.status.success() {
+        return Ok(None);
+    }
 
-    let files = String::from_utf8_lossy(&output.stdout)
-        .lines()
-        .map(|s| s.to_string())
-        .collect();
+    let prefix = format!("refs/remotes/{}/", remote);
+    let reference = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok(Some(
+        reference
+            .strip_prefix(&prefix)
+            .unwrap_or(&reference)
+            .to_string(),
+    ))
+}
+
+/// The branch `remote`'s HEAD points at according to the remote itself, for repositories
+/// where `refs/remotes/<remote>/HEAD` was never set. `None` for an empty remote.
+pub async fn query_remote_head(path: &Path, remote: &str) -> Result<Option<String>> {
+    let output = git_stdout(path, &["ls-remote", "--symref", remote, "HEAD"]).await?;
+    Ok(parse_ls_remote_symref(&output))
+}
+
+fn parse_ls_remote_symref(output: &str) -> Option<String> {
+    output.lines().find_map(|line| {
+        let target = line.strip_prefix("ref: ")?.strip_suffix("\tHEAD")?;
+        Some(target.trim_start_matches("refs/heads/").to_string())
+    })
+}
+
+/// The default branch of `remote`, from `refs/remotes/<remote>/HEAD` or, when that is not
+/// set, from the remote.
+pub async fn get_default_branch(path: &Path, remote: &str) -> Result<Option<String>> {
+    match get_remote_head(path, remote).await? {
+        Some(branch) => Ok(Some(branch)),
+        None => query_remote_head(path, remote).await,
+    }
+}
+
+/// Resolves a branch name to a ref that exists locally, preferring the local branch over
+/// its `origin/` remote-tracking counterpart.
+pub async fn resolve_branch_ref(path: &Path, branch: &str) -> Result<Option<String>> {
+    resolve_first_ref(path, &[branch.to_string(), format!("origin/{}", branch)]).await
+}
+
+/// Returns the first of `candidates` that names an existing commit.
+pub async fn resolve_first_ref(path: &Path, candidates: &[String]) -> Result<Option<String>> {
+    for candidate in candidates {
+        let output = Command::new("git")
+            .args([
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{}^{{commit}}", candidate),
+            ])
+            .current_dir(path)
+            .output()
+            .await?;
+        if output.status.success() {
+            return Ok(Some(candidate.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// A file changed between the merge base and the head of a branch.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ChangedFile {
+    pub path: String,
+    pub old_path: Option<String>,
+    pub status: String,
+    /// `None` for binary files.
+    pub insertions: Option<u32>,
+    pub deletions: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BranchCommit {
+    pub hash: String,
+    pub short_hash: String,
+    pub subject: String,
+    pub author: String,
+    pub committed_at: String,
+}
+
+/// Changes on `head` since it diverged from `base`.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct BranchDiff {
+    pub base: String,
+    pub head: String,
+    pub merge_base: String,
+    pub insertions: u32,
+    pub deletions: u32,
+    pub files: Vec<ChangedFile>,
+    pub commits: Vec<BranchCommit>,
+}
+
+impl BranchDiff {
+    pub fn file_names(&self) -> Vec<String> {
+        self.files.iter().map(|file| file.path.clone()).collect()
+    }
+}
+
+/// Runs git in `path` and returns stdout, or its stderr as a `GmuxError::Git` on failure.
+pub async fn git_stdout(path: &Path, args: &[&str]) -> Result<String> {
+    git_stdout_with_env(path, args, &[]).await
+}
 
+async fn git_stdout_with_env(
+    path: &Path,
+    args: &[&str],
+    envs: &[(&str, &OsStr)],
+) -> Result<String> {
+    let output = Command::new("git")
+        .args(args)
+        .envs(envs.iter().copied())
+        .current_dir(path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(GmuxError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+/// Compares `head` against its merge base with `base`, so uncommitted work and commits made on
+/// `base` after branching are not included.
+pub async fn get_branch_diff(path: &Path, base: &str, head: &str) -> Result<BranchDiff> {
+    let merge_base = git_stdout(path, &["merge-base", base, head])
+        .await?
+        .trim()
+        .to_string();
+    let files = get_changed_files(path, &[&merge_base, head]).await?;
+    let log = git_stdout(
+        path,
+        &[
+            "log",
+            "--format=%H%x00%h%x00%s%x00%an%x00%cI%x1e",
+            &format!("{}..{}", merge_base, head),
+        ],
+    )
+    .await?;
+
+    Ok(BranchDiff {
+        base: base.to_string(),
+        head: head.to_string(),
+        merge_base,
+        insertions: files.iter().filter_map(|file| file.insertions).sum(),
+        deletions: files.iter().filter_map(|file| file.deletions).sum(),
+        files,
+        commits: parse_log(&log),
+    })
+}
+
+/// The latest `limit` commits reachable from `rev`, newest first.
+pub async fn get_log(path: &Path, rev: &str, limit: usize) -> Result<Vec<BranchCommit>> {
+    let log = git_stdout(
+        path,
+        &[
+            "log",
+            "--format=%H%x00%h%x00%s%x00%an%x00%cI%x1e",
+            &format!("--max-count={}", limit),
+            rev,
+        ],
+    )
+    .await?;
+    Ok(parse_log(&log))
+}
+
+/// The commit `HEAD` points at, or `None` before the first commit.
+pub async fn get_last_commit(path: &Path) -> Option<BranchCommit> {
+    get_log(path, "HEAD", 1).await.ok()?.into_iter().next()
+}
+
+/// Changes staged in the index, compared against `HEAD`.
+pub async fn get_staged_files(path: &Path) -> Result<Vec<ChangedFile>> {
+    get_changed_files(path, &["--cached"]).await
+}
+
+/// The files a commit would record after `git add <add_args>`, found by staging into a copy of
+/// the index so that the real one is left untouched.
+pub async fn preview_staged_files(path: &Path, add_args: &[&str]) -> Result<Vec<ChangedFile>> {
+    let index_path = path.join(
+        git_stdout(path, &["rev-parse", "--git-path", "index"])
+            .await?
+            .trim(),
+    );
+    let preview_index = tempfile::NamedTempFile::new()?;
+    if index_path.exists() {
+        std::fs::copy(&index_path, preview_index.path())?;
+        // Git trusts cached file stats only when they are older than the index, so the copy
+        // keeps the original's mtime to catch same-second edits.
+        let modified = std::fs::metadata(&index_path)?.modified()?;
+        preview_index.as_file().set_modified(modified)?;
+    }
+
+    let envs = [("GIT_INDEX_FILE", preview_index.path().as_os_str())];
+    let mut args = vec!["add"];
+    args.extend_from_slice(add_args);
+    git_stdout_with_env(path, &args, &envs).await?;
+    get_changed_files_with_env(path, &["--cached"], &envs).await
+}
+
+/// Files reported by `git diff <args>`, with per-file line counts.
+async fn get_changed_files(path: &Path, args: &[&str]) -> Result<Vec<ChangedFile>> {
+    get_changed_files_with_env(path, args, &[]).await
+}
+
+async fn get_changed_files_with_env(
+    path: &Path,
+    args: &[&str],
+    envs: &[(&str, &OsStr)],
+) -> Result<Vec<ChangedFile>> {
+    let mut name_status_args = vec!["diff", "--name-status", "-M", "-z"];
+    name_status_args.extend_from_slice(args);
+    let mut numstat_args = vec!["diff", "--numstat", "-M", "-z"];
+    numstat_args.extend_from_slice(args);
+
+    let mut files = parse_name_status(&git_stdout_with_env(path, &name_status_args, envs).await?);
+    let stats = parse_numstat(&git_stdout_with_env(path, &numstat_args, envs).await?);
+    for file in &mut files {
+        if let Some((insertions, deletions)) = stats.get(&file.path) {
+            file.insertions = *insertions;
+            file.deletions = *deletions;
+        }
+    }
     Ok(files)
 }
 
+fn file_status(code: &str) -> &'static str {
+    match code.chars().next() {
+        Some('A') => "added",
+        Some('D') => "deleted",
+        Some('R') => "renamed",
+        Some('C') => "copied",
+        Some('T') => "type_changed",
+        Some('U') => "unmerged",
+        _ => "modified",
+    }
+}
+
+/// Parses `git diff --name-status -z`, where renames and copies carry two paths.
+fn parse_name_status(output: &str) -> Vec<ChangedFile> {
+    let mut tokens = output.split('\0').filter(|token| !token.is_empty());
+    let mut files = Vec::new();
+    while let Some(code) = tokens.next() {
+        let status = file_status(code);
+        let old_path = if matches!(status, "renamed" | "copied") {
+            tokens.next().map(String::from)
+        } else {
+            None
+        };
+        let Some(path) = tokens.next() else {
+            break;
+        };
+        files.push(ChangedFile {
+            path: path.to_string(),
+            old_path,
+            status: status.to_string(),
+            insertions: None,
+            deletions: None,
+        });
+    }
+    files
+}
+
+type LineStats = (Option<u32>, Option<u32>);
+
+/// Parses `git diff --numstat -z` into stats keyed by the new path. Binary files report `-`.
+fn parse_numstat(output: &str) -> HashMap<String, LineStats> {
+    let mut tokens = output.split('\0');
+    let mut stats = HashMap::new();
+    while let Some(entry) = tokens.next() {
+        let mut fields = entry.splitn(3, '\t');
+        let (Some(insertions), Some(deletions), Some(path)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let path = if path.is_empty() {
+            // Renames are followed by the old and new paths as separate tokens.
+            tokens.next();
+            tokens.next().unwrap_or_default()
+        } else {
+            path
+        };
+        stats.insert(
+            path.to_string(),
+            (insertions.parse().ok(), deletions.parse().ok()),
+        );
+    }
+    stats
+}
+
+fn parse_log(output: &str) -> Vec<BranchCommit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut parts = record.trim_start_matches('\n').split('\0');
+            Some(BranchCommit {
+                hash: parts.next().filter(|hash| !hash.is_empty())?.to_string(),
+                short_hash: parts.next()?.to_string(),
+                subject: parts.next()?.to_string(),
+                author: parts.next()?.to_string(),
+                committed_at: parts.next()?.trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+pub async fn get_remote_url(path: &Path, remote: &str) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args(["remote", "get-url", remote])
+        .current_dir(path)
+        .output()
+        .await?;
+
+    let url = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || url.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some(url))
+}
+
+pub async fn local_branch_exists(path: &Path, branch: &str) -> Result<bool> {
+    let output = Command::new("git")
+        .args([
+            "show-ref",
+            "--verify",
+            "--quiet",
+            &format!("refs/heads/{}", branch),
+        ])
+        .current_dir(path)
+        .output()
+        .await?;
+
+    Ok(output.status.success())
+}
+
+/// Local branch names, optionally restricted to those merged into `merged_into`.
+pub async fn list_local_branches(path: &Path, merged_into: Option<&str>) -> Result<Vec<String>> {
+    let mut args = vec!["for-each-ref", "--format=%(refname:short)"];
+    let merged;
+    if let Some(base) = merged_into {
+        merged = format!("--merged={}", base);
+        args.push(&merged);
+    }
+    args.push("refs/heads");
+
+    Ok(git_stdout(path, &args)
+        .await?
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// A local branch or a branch on `remote`.
+#[derive(Debug, Clone)]
+pub struct BranchRef {
+    /// Short ref name, e.g. `feature` or `origin/feature`.
+    pub name: String,
+    /// Branch name without the remote prefix.
+    pub branch: String,
+    pub remote: Option<String>,
+    pub committed_at: String,
+    pub committed_unix: i64,
+    /// Whether the branch tip is reachable from `merged_into`, when one was given.
+    pub merged: Option<bool>,
+}
+
+/// Local branches and the branches of `remote`, excluding its symbolic `HEAD`.
+pub async fn list_branch_refs(
+    path: &Path,
+    remote: &str,
+    merged_into: Option<&str>,
+) -> Result<Vec<BranchRef>> {
+    let remote_prefix = format!("refs/remotes/{}/", remote);
+    let remote_refs = format!("refs/remotes/{}", remote);
+    let refs = git_stdout(
+        path,
+        &[
+            "for-each-ref",
+            "--format=%(refname)%00%(committerdate:unix)%00%(committerdate:iso-strict)",
+            "refs/heads",
+            &remote_refs,
+        ],
+    )
+    .await?;
+
+    let merged: Option<HashSet<String>> = match merged_into {
+        Some(base) => Some(
+            git_stdout(
+                path,
+                &[
+                    "for-each-ref",
+                    "--format=%(refname)",
+                    &format!("--merged={}", base),
+                    "refs/heads",
+                    &remote_refs,
+                ],
+            )
+            .await?
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        ),
+        None => None,
+    };
+
+    Ok(refs
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\0');
+            let refname = parts.next()?;
+            let committed_unix = parts.next()?.parse().ok()?;
+            let committed_at = parts.next()?.to_string();
+            let (branch, remote) = if let Some(branch) = refname.strip_prefix("refs/heads/") {
+                (branch, None)
+            } else {
+                let branch = refname.strip_prefix(&remote_prefix)?;
+                if branch == "HEAD" {
+                    return None;
+                }
+                (branch, Some(remote.to_string()))
+            };
+
+            Some(BranchRef {
+                name: match &remote {
+                    Some(remote) => format!("{}/{}", remote, branch),
+                    None => branch.to_string(),
+                },
+                branch: branch.to_string(),
+                remote,
+                committed_at,
+                committed_unix,
+                merged: merged.as_ref().map(|merged| merged.contains(refname)),
+            })
+        })
+        .collect())
+}
+
+/// Commits on `head` but not `base`, and on `base` but not `head`.
+pub async fn count_ahead_behind(path: &Path, base: &str, head: &str) -> Result<(u32, u32)> {
+    let counts = git_stdout(
+        path,
+        &[
+            "rev-list",
+            "--left-right",
+            "--count",
+            &format!("{}...{}", base, head),
+        ],
+    )
+    .await?;
+
+    let mut parts = counts.split_whitespace().map(|value| value.parse().ok());
+    match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(behind), Some(ahead)) => Ok((ahead, behind)),
+        _ => Err(GmuxError::Git(format!(
+            "unexpected rev-list output: {}",
+            counts.trim()
+        ))),
+    }
+}
+
+/// Whether tracked files have staged or unstaged changes. Untracked files are ignored since
+/// they do not prevent switching branches.
+pub async fn has_uncommitted_changes(path: &Path) -> Result<bool> {
+    let status = git_stdout(path, &["status", "--porcelain", "--untracked-files=no"]).await?;
+    Ok(!status.trim().is_empty())
+}
+
+/// The `# branch.*` headers of `git status --porcelain=v2 --branch`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct BranchStatus {
+    /// `None` when HEAD is detached.
+    pub head: Option<String>,
+    /// `None` before the first commit.
+    pub oid: Option<String>,
+    pub upstream: Option<String>,
+    /// `None` without an upstream, or when the upstream branch is gone.
+    pub ahead: Option<u32>,
+    pub behind: Option<u32>,
+}
+
+/// Paths in the index and working tree, from `git status --porcelain=v2`. A path with both
+/// staged and unstaged changes is in both lists.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WorkingTreeStatus {
+    pub branch: BranchStatus,
+    /// Every path once, in `git status` order.
+    pub changed: Vec<String>,
+    pub staged: Vec<String>,
+    pub unstaged: Vec<String>,
+    pub untracked: Vec<String>,
+    pub conflicted: Vec<String>,
+}
+
+pub async fn get_working_tree_status(path: &Path) -> Result<WorkingTreeStatus> {
+    let stdout = git_stdout(path, &["status", "--porcelain=v2", "--branch", "-z"]).await?;
+    Ok(parse_status_porcelain_v2(&stdout))
+}
+
+/// Parses NUL-separated `git status --porcelain=v2 -z` output, where paths are never quoted.
+/// Renames report the new path; ignored files are skipped.
+pub fn parse_status_porcelain_v2(output: &str) -> WorkingTreeStatus {
+    let mut status = WorkingTreeStatus::default();
+    let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        let (kind, fields) = entry.split_at(1);
+        // Ordinary, renamed and unmerged entries have 8, 9 and 10 fields before the path.
+        let field_count = match kind {
+            "1" => 8,
+            "2" => 9,
+            "u" => 10,
+            "#" => {
+                parse_branch_header(&mut status.branch, fields.trim_start());
+                continue;
+            }
+            "?" => {
+                let path = fields.trim_start().to_string();
+                status.changed.push(path.clone());
+                status.untracked.push(path);
+                continue;
+            }
+            _ => continue,
+        };
+        if kind == "2" {
+            // The original path follows as its own entry.
+            entries.next();
+        }
+
+        let mut parts = fields.trim_start().splitn(field_count, ' ');
+        let xy = parts.next().unwrap_or_default();
+        let Some(path) = parts.nth(field_count - 2).map(str::to_string) else {
+            continue;
+        };
+        status.changed.push(path.clone());
+        if kind == "u" {
+            status.conflicted.push(path);
+            continue;
+        }
+        let mut xy = xy.chars();
+        if xy.next().is_some_and(|x| x != '.') {
+            status.staged.push(path.clone());
+        }
+        if xy.next().is_some_and(|y| y != '.') {
+            status.unstaged.push(path);
+        }
+    }
+    status
+}
+
+fn parse_branch_header(branch: &mut BranchStatus, header: &str) {
+    let Some((key, value)) = header.split_once(' ') else {
+        return;
+    };
+    match key {
+        "branch.oid" if value != "(initial)" => branch.oid = Some(value.to_string()),
+        "branch.head" if value != "(detached)" => branch.head = Some(value.to_string()),
+        "branch.upstream" => branch.upstream = Some(value.to_string()),
+        "branch.ab" => {
+            let mut counts = value
+                .split(' ')
+                .map(|count| count.trim_start_matches(['+', '-']).parse().ok());
+            branch.ahead = counts.next().flatten();
+            branch.behind = counts.next().flatten();
+        }
+        _ => {}
+    }
+}
+
+/// A repository's git directory and, for linked worktrees, the common directory shared with the
+/// main worktree, found without running git.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitDirs {
+    pub git_dir: PathBuf,
+    pub common_dir: PathBuf,
+}
+
+impl GitDirs {
+    /// Resolves `.git`, which is a directory or, in worktrees and submodules, a `gitdir:` file.
+    pub fn find(path: &Path) -> Option<Self> {
+        let dot_git = path.join(".git");
+        let git_dir = if dot_git.is_dir() {
+            dot_git
+        } else {
+            let content = std::fs::read_to_string(&dot_git).ok()?;
+            path.join(content.strip_prefix("gitdir:")?.trim())
+        };
+        let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common_dir) => git_dir.join(common_dir.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        Some(Self {
+            git_dir,
+            common_dir,
+        })
+    }
+
+    pub fn is_shallow(&self) -> bool {
+        self.common_dir.join("shallow").exists()
+    }
+
+    /// Counts the entries of the stash reflog.
+    pub fn count_stashes(&self) -> usize {
+        std::fs::read_to_string(self.common_dir.join("logs").join("refs").join("stash"))
+            .map(|reflog| reflog.lines().filter(|line| !line.is_empty()).count())
+            .unwrap_or_default()
+    }
+}
+
+/// The operation that has stopped partway, such as a rebase with conflicts, from the state
+/// files git leaves in the git directory.
+pub fn operation_in_progress(git_dir: &Path) -> Option<&'static str> {
+    if git_dir.join("rebase-merge").exists() {
+        Some("rebase")
+    } else if git_dir.join("rebase-apply").join("applying").exists() {
+        Some("am")
+    } else if git_dir.join("rebase-apply").exists() {
+        Some("rebase")
+    } else if git_dir.join("MERGE_HEAD").exists() {
+        Some("merge")
+    } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+        Some("cherry-pick")
+    } else if git_dir.join("REVERT_HEAD").exists() {
+        Some("revert")
+    } else if git_dir.join("BISECT_LOG").exists() {
+        Some("bisect")
+    } else {
+        None
+    }
+}
+
+/// A ref, and the commit it points at, from `git for-each-ref`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefDetails {
+    pub name: String,
+    /// The ref a symbolic ref points at, such as `refs/remotes/origin/main` for
+    /// `refs/remotes/origin/HEAD`.
+    pub symref: Option<String>,
+    pub hash: String,
+    pub short_hash: String,
+    pub subject: String,
+    pub committed_at: String,
+}
+
+/// Details of the refs named exactly by `names`, in one `git for-each-ref`. Refs that do not
+/// exist are left out.
+pub async fn get_ref_details(path: &Path, names: &[String]) -> Result<Vec<RefDetails>> {
+    let mut args = vec![
+        "for-each-ref",
+        "--format=%(refname)%00%(symref)%00%(objectname)%00%(objectname:short)%00%(subject)%00%(committerdate:iso-strict)",
+    ];
+    args.extend(names.iter().map(String::as_str));
+    let stdout = git_stdout(path, &args).await?;
+    Ok(parse_ref_details(&stdout)
+        .into_iter()
+        .filter(|details| names.contains(&details.name))
+        .collect())
+}
+
+fn parse_ref_details(output: &str) -> Vec<RefDetails> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\0');
+            Some(RefDetails {
+                name: parts.next()?.to_string(),
+                symref: Some(parts.next()?.to_string()).filter(|symref| !symref.is_empty()),
+                hash: parts.next()?.to_string(),
+                short_hash: parts.next()?.to_string(),
+                subject: parts.next()?.to_string(),
+                committed_at: parts.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct SubmoduleStatus {
+    pub path: String,
+    pub commit: String,
+    /// One of `current`, `modified` (a different commit is checked out), `uninitialized` or
+    /// `conflict`.
+    pub status: String,
+}
+
+/// Submodule status, without running git when there is no `.gitmodules`.
+pub async fn get_submodule_status(path: &Path) -> Result<Vec<SubmoduleStatus>> {
+    if !path.join(".gitmodules").exists() {
+        return Ok(Vec::new());
+    }
+    let stdout = git_stdout(path, &["submodule", "status"]).await?;
+    Ok(parse_submodule_status(&stdout))
+}
+
+/// Parses `git submodule status` lines: a status character, the commit, the path and an
+/// optional description in parentheses.
+pub fn parse_submodule_status(output: &str) -> Vec<SubmoduleStatus> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut chars = line.chars();
+            let status = match chars.next()? {
+                '-' => "uninitialized",
+                '+' => "modified",
+                'U' => "conflict",
+                _ => "current",
+            };
+            let mut parts = chars.as_str().splitn(2, ' ');
+            let commit = parts.next()?.to_string();
+            let rest = parts.next()?;
+            let path = match rest.rfind(" (") {
+                Some(index) if rest.ends_with(')') => &rest[..index],
+                _ => rest,
+            };
+            Some(SubmoduleStatus {
+                path: path.to_string(),
+                commit,
+                status: status.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct LfsStatus {
+    /// Whether the `git lfs` extension is installed. Without it the counts are unknown.
+    pub installed: bool,
+    pub files: Option<usize>,
+    /// Files checked out as pointers because their content was not downloaded.
+    pub missing: Option<usize>,
+}
+
+/// LFS status for repositories whose `.gitattributes` routes files through LFS.
+pub async fn get_lfs_status(path: &Path) -> Option<LfsStatus> {
+    let attributes = std::fs::read_to_string(path.join(".gitattributes")).ok()?;
+    if !attributes.contains("filter=lfs") {
+        return None;
+    }
+    Some(match git_stdout(path, &["lfs", "ls-files"]).await {
+        Ok(stdout) => {
+            let (files, missing) = parse_lfs_files(&stdout);
+            LfsStatus {
+                installed: true,
+                files: Some(files),
+                missing: Some(missing),
+            }
+        }
+        Err(_) => LfsStatus {
+            installed: false,
+            files: None,
+            missing: None,
+        },
+    })
+}
+
+/// Counts `git lfs ls-files` lines, `<oid> <*|-> <path>`, where `-` marks a pointer file.
+fn parse_lfs_files(output: &str) -> (usize, usize) {
+    output.lines().fold((0, 0), |(files, missing), line| {
+        let pointer = line.split(' ').nth(1) == Some("-");
+        (files + 1, missing + usize::from(pointer))
+    })
+}
+
+/// Fetches `branch` from `remote`, a remote name or URL, without updating any branch or
+/// remote-tracking ref. Returns the fetched commit, or `None` when `remote` has no such branch.
+pub async fn fetch_remote_branch(
+    path: &Path,
+    remote: &str,
+    branch: &str,
+) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args([
+            "fetch",
+            "--quiet",
+            "--no-tags",
+            remote,
+            &format!("refs/heads/{}", branch),
+        ])
+        .current_dir(path)
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("couldn't find remote ref") {
+            return Ok(None);
+        }
+        return Err(GmuxError::Git(stderr.trim().to_string()));
+    }
+    let commit = git_stdout(path, &["rev-parse", "FETCH_HEAD"]).await?;
+    Ok(Some(commit.trim().to_string()))
+}
+
+/// `remote` may be a remote name or a URL.
+pub async fn remote_branch_exists(path: &Path, remote: &str, branch: &str) -> Result<bool> {
+    let output = Command::new("git")
+        .args(["ls-remote", "--heads", remote, branch])
+        .current_dir(path)
+        .output()
+        .await?;
+
+    Ok(!String::from_utf8_lossy(&output.stdout).trim().is_empty())
+}
+
+pub async fn push_branch(path: &Path, remote: &str, branch: &str) -> Result<()> {
+    let output = Command::new("git")
+        .args(["push", "-u", remote, branch])
+        .current_dir(path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(GmuxError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Extracts `(owner, repo)` from HTTPS, `ssh://` and scp-style (`git@host:owner/repo`) remotes.
+pub fn parse_github_remote(remote_url: &str) -> Option<(String, String)> {
+    let url = remote_url.trim().trim_end_matches('/');
+    let path = if let Some((_, rest)) = url.split_once("://") {
+        rest.split_once('/')?.1
+    } else {
+        let (host, rest) = url.split_once(':')?;
+        if host.contains('/') {
+            return None;
+        }
+        rest
+    };
+
+    let path = path.trim_end_matches('/').trim_end_matches(".git");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    if segments.len() < 2 {
+        return None;
+    }
+
+    Some((
+        segments[segments.len() - 2].to_string(),
+        segments[segments.len() - 1].to_string(),
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use crate::test_support::git;
     use tempfile::TempDir;
 
     async fn setup_test_repo() -> (TempDir, PathBuf) {
         let temp_dir = TempDir::new().unwrap();
         let repo_path = temp_dir.path().to_path_buf();
 
-        // Initialize git repository with main as default branch
-        let output = tokio::process::Command::new("git")
-            .args(["init", "-b", "main"])
-            .current_dir(&repo_path)
-            .output()
-            .await
-            .unwrap();
-        assert!(output.status.success());
-
-        // Create initial commit
-        let output = tokio::process::Command::new("git")
-            .args(["commit", "--allow-empty", "-m", "Initial commit"])
-            .current_dir(&repo_path)
-            .output()
-            .await
-            .unwrap();
-        assert!(output.status.success());
+        git(&repo_path, &["init", "-b", "main"]).await;
+        git(
+            &repo_path,
+            &["commit", "--allow-empty", "-m", "Initial commit"],
+        )
+        .await;
 
         (temp_dir, repo_path)
     }
@@ -115,24 +949,338 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_get_diff_files() -> Result<()> {
+    async fn test_detached_head() -> Result<()> {
         let (_temp_dir, repo_path) = setup_test_repo().await;
+        assert!(!get_repository_metadata(&repo_path).await?.unwrap().detached);
 
-        // Create a test file
-        let test_file = repo_path.join("test.txt");
-        std::fs::write(&test_file, "test content").unwrap();
+        git(&repo_path, &["checkout", "--detach"]).await;
+        let metadata = get_repository_metadata(&repo_path).await?.unwrap();
+        assert!(metadata.detached);
+        assert_eq!(metadata.current_branch, "");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_branch_diff() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo().await;
+        std::fs::write(repo_path.join("keep.txt"), "one\ntwo\n").unwrap();
+        std::fs::write(repo_path.join("old.txt"), "rename me\n").unwrap();
+        git(&repo_path, &["add", "."]).await;
+        git(&repo_path, &["commit", "-m", "Base files"]).await;
+
+        git(&repo_path, &["checkout", "-b", "feature"]).await;
+        std::fs::write(repo_path.join("keep.txt"), "one\nthree\n").unwrap();
+        std::fs::write(repo_path.join("new file.txt"), "added\n").unwrap();
+        git(&repo_path, &["mv", "old.txt", "renamed.txt"]).await;
+        git(&repo_path, &["add", "."]).await;
+        git(&repo_path, &["commit", "-m", "Feature work"]).await;
+
+        // Neither uncommitted files nor commits made on the base after branching are included.
+        std::fs::write(repo_path.join("uncommitted.txt"), "wip").unwrap();
+        git(&repo_path, &["checkout", "main"]).await;
+        std::fs::write(repo_path.join("main-only.txt"), "main").unwrap();
+        git(&repo_path, &["add", "main-only.txt"]).await;
+        git(&repo_path, &["commit", "-m", "Main work"]).await;
+        git(&repo_path, &["checkout", "feature"]).await;
+
+        let diff = get_branch_diff(&repo_path, "main", "HEAD").await?;
+        let mut files: Vec<_> = diff
+            .files
+            .iter()
+            .map(|f| {
+                (
+                    f.path.as_str(),
+                    f.status.as_str(),
+                    f.insertions,
+                    f.deletions,
+                )
+            })
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![
+                ("keep.txt", "modified", Some(1), Some(1)),
+                ("new file.txt", "added", Some(1), Some(0)),
+                ("renamed.txt", "renamed", Some(0), Some(0)),
+            ]
+        );
+        let renamed = diff.files.iter().find(|f| f.status == "renamed").unwrap();
+        assert_eq!(renamed.old_path.as_deref(), Some("old.txt"));
+        assert_eq!(diff.insertions, 2);
+        assert_eq!(diff.deletions, 1);
+        assert_eq!(diff.commits.len(), 1);
+        assert_eq!(diff.commits[0].subject, "Feature work");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_list_branch_refs() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo().await;
+        git(&repo_path, &["branch", "merged"]).await;
+        git(&repo_path, &["switch", "-c", "feature"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "One"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "Two"]).await;
+        git(&repo_path, &["switch", "main"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "Three"]).await;
+
+        let refs = list_branch_refs(&repo_path, "origin", Some("main")).await?;
+        let merged: Vec<_> = refs
+            .iter()
+            .map(|branch| (branch.name.as_str(), branch.merged))
+            .collect();
+        assert_eq!(
+            merged,
+            vec![
+                ("feature", Some(false)),
+                ("main", Some(true)),
+                ("merged", Some(true))
+            ]
+        );
+        assert!(refs.iter().all(|branch| branch.remote.is_none()));
+
+        assert_eq!(
+            count_ahead_behind(&repo_path, "main", "feature").await?,
+            (2, 1)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_branch_ref() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo().await;
 
-        // Add the file
         let output = tokio::process::Command::new("git")
-            .args(["add", "test.txt"])
+            .args(["update-ref", "refs/remotes/origin/feature", "HEAD"])
             .current_dir(&repo_path)
             .output()
             .await
             .unwrap();
         assert!(output.status.success());
 
-        let diff_files = get_diff_file_names(&repo_path, "main").await?;
-        assert_eq!(diff_files, vec!["test.txt"]);
+        assert_eq!(
+            resolve_branch_ref(&repo_path, "main").await?,
+            Some("main".to_string())
+        );
+        assert_eq!(
+            resolve_branch_ref(&repo_path, "feature").await?,
+            Some("origin/feature".to_string())
+        );
+        assert_eq!(resolve_branch_ref(&repo_path, "missing").await?, None);
+        assert!(
+            !get_repository_metadata(&repo_path)
+                .await?
+                .unwrap()
+                .has_remote_head
+        );
         Ok(())
     }
+
+    #[test]
+    fn test_parse_github_remote() {
+        let expected = Some(("owner".to_string(), "repo".to_string()));
+        assert_eq!(
+            parse_github_remote("https://github.com/owner/repo.git"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("https://github.com/owner/repo"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("git@github.com:owner/repo.git"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("ssh://git@github.com/owner/repo.git"),
+            expected
+        );
+        assert_eq!(
+            parse_github_remote("git@github.example.com:owner/repo.git"),
+            expected
+        );
+
+        assert_eq!(parse_github_remote(""), None);
+        assert_eq!(parse_github_remote("repo"), None);
+        assert_eq!(parse_github_remote("https://github.com/repo"), None);
+        assert_eq!(parse_github_remote("git@github.com:repo.git"), None);
+        assert_eq!(parse_github_remote("/srv/git/owner/repo.git"), None);
+    }
+
+    #[test]
+    fn test_parse_status_porcelain_v2() {
+        let output = [
+            "# branch.oid 1234",
+            "# branch.head feature/login",
+            "# branch.upstream origin/feature/login",
+            "# branch.ab +2 -1",
+            "1 M. N... 100644 100644 100644 aaaa bbbb staged.txt",
+            "1 MM N... 100644 100644 100644 aaaa bbbb both.txt",
+            "1 .M N... 100644 100644 100644 aaaa bbbb dir/with space.txt",
+            "2 R. N... 100644 100644 100644 aaaa bbbb R100 new name.txt",
+            "old name.txt",
+            "u UU N... 100644 100644 100644 100644 aaaa bbbb cccc conflict.txt",
+            "? caf\u{e9} \"quoted\".txt",
+            "",
+        ]
+        .join("\0");
+
+        let status = parse_status_porcelain_v2(&output);
+        assert_eq!(
+            status.staged,
+            vec!["staged.txt", "both.txt", "new name.txt"]
+        );
+        assert_eq!(status.unstaged, vec!["both.txt", "dir/with space.txt"]);
+        assert_eq!(status.untracked, vec!["caf\u{e9} \"quoted\".txt"]);
+        assert_eq!(status.conflicted, vec!["conflict.txt"]);
+        assert_eq!(status.changed.len(), 6);
+        assert_eq!(
+            status.branch,
+            BranchStatus {
+                head: Some("feature/login".to_string()),
+                oid: Some("1234".to_string()),
+                upstream: Some("origin/feature/login".to_string()),
+                ahead: Some(2),
+                behind: Some(1),
+            }
+        );
+        let status =
+            parse_status_porcelain_v2("# branch.oid (initial)\0# branch.head (detached)\0");
+        assert_eq!(status.branch, BranchStatus::default());
+    }
+
+    #[tokio::test]
+    async fn test_get_working_tree_status() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo().await;
+        std::fs::write(repo_path.join("tab\tname.txt"), "a")?;
+        std::fs::write(repo_path.join("\u{1f600}.txt"), "a")?;
+        git(&repo_path, &["add", "tab\tname.txt"]).await;
+
+        let status = get_working_tree_status(&repo_path).await?;
+        assert_eq!(status.branch.head.as_deref(), Some("main"));
+        assert_eq!(status.branch.upstream, None);
+        assert_eq!(status.staged, vec!["tab\tname.txt"]);
+        assert_eq!(status.untracked, vec!["\u{1f600}.txt"]);
+        assert!(status.unstaged.is_empty());
+
+        let dirs = GitDirs::find(&repo_path).unwrap();
+        assert_eq!(dirs.git_dir, repo_path.join(".git"));
+        assert!(!dirs.is_shallow());
+        assert_eq!(dirs.count_stashes(), 0);
+        git(&repo_path, &["stash"]).await;
+        assert_eq!(dirs.count_stashes(), 1);
+
+        assert_eq!(operation_in_progress(&dirs.git_dir), None);
+        std::fs::write(dirs.git_dir.join("MERGE_HEAD"), "")?;
+        assert_eq!(operation_in_progress(&dirs.git_dir), Some("merge"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_ref_details() -> Result<()> {
+        let (_temp_dir, repo_path) = setup_test_repo().await;
+        git(&repo_path, &["branch", "main-old"]).await;
+        git(
+            &repo_path,
+            &[
+                "symbolic-ref",
+                "refs/remotes/origin/HEAD",
+                "refs/heads/main",
+            ],
+        )
+        .await;
+
+        let names = vec![
+            "refs/heads/main".to_string(),
+            "refs/remotes/origin/HEAD".to_string(),
+            "refs/heads/missing".to_string(),
+        ];
+        let refs = get_ref_details(&repo_path, &names).await?;
+        assert_eq!(refs.len(), 2);
+        assert_eq!(refs[0].name, "refs/heads/main");
+        assert_eq!(refs[0].subject, "Initial commit");
+        assert_eq!(refs[0].symref, None);
+        assert_eq!(refs[1].symref.as_deref(), Some("refs/heads/main"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_default_branch_from_remote() -> Result<()> {
+        let (_origin_dir, origin) = setup_test_repo().await;
+        git(&origin, &["switch", "-c", "trunk"]).await;
+        let (_temp_dir, repo_path) = setup_test_repo().await;
+        git(
+            &repo_path,
+            &["remote", "add", "origin", &origin.display().to_string()],
+        )
+        .await;
+
+        // Without `origin/HEAD`, the remote is asked.
+        assert_eq!(
+            get_default_branch(&repo_path, "origin").await?.as_deref(),
+            Some("trunk")
+        );
+        git(&repo_path, &["fetch", "-q", "origin"]).await;
+        git(&repo_path, &["remote", "set-head", "origin", "main"]).await;
+        assert_eq!(
+            get_default_branch(&repo_path, "origin").await?.as_deref(),
+            Some("main")
+        );
+        assert!(get_default_branch(&repo_path, "missing").await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_ls_remote_symref() {
+        assert_eq!(
+            parse_ls_remote_symref("ref: refs/heads/main\tHEAD\n1111\tHEAD\n").as_deref(),
+            Some("main")
+        );
+        assert_eq!(parse_ls_remote_symref(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_remote_branch() -> Result<()> {
+        let (_parent_dir, parent) = setup_test_repo().await;
+        let (_temp_dir, repo_path) = setup_test_repo().await;
+        git(&parent, &["commit", "--allow-empty", "-m", "Parent commit"]).await;
+        let parent_url = parent.display().to_string();
+
+        let expected = git_stdout(&parent, &["rev-parse", "main"]).await?;
+        let fetched = fetch_remote_branch(&repo_path, &parent_url, "main").await?;
+        assert_eq!(fetched.as_deref(), Some(expected.trim()));
+        assert!(git_stdout(&repo_path, &["cat-file", "-e", expected.trim()])
+            .await
+            .is_ok());
+        assert_eq!(
+            fetch_remote_branch(&repo_path, &parent_url, "missing").await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_submodule_status() {
+        let output =
+            " 1111 libs/core (v1.0-3-g1111)\n-2222 vendor/tool\n+3333 libs/ui (heads/main)\n";
+        let statuses: Vec<(String, String)> = parse_submodule_status(output)
+            .into_iter()
+            .map(|submodule| (submodule.path, submodule.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("libs/core".to_string(), "current".to_string()),
+                ("vendor/tool".to_string(), "uninitialized".to_string()),
+                ("libs/ui".to_string(), "modified".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_lfs_files() {
+        let output = "0a1b2c3d4e * assets/logo.png\n5f6a7b8c9d - assets/video.mp4\n";
+        assert_eq!(parse_lfs_files(output), (2, 1));
+        assert_eq!(parse_lfs_files(""), (0, 0));
+    }
 }
@@ -1,5 +1,11 @@
+use crate::cache::{
+    get_repository_cache_path, load_repository_cache, CacheMode, CachedPage, RepositoryCache,
+};
 use crate::config::Config;
-use crate::error::{GmuxError, Result};
+use crate::error::{ConfigErrorKind, GmuxError, Result};
+use http::header::HeaderMap;
+use http::StatusCode;
+use octocrab::etag::EntityTag;
 use octocrab::Octocrab;
 use std::sync::Arc;
 
@@ -9,7 +15,39 @@ pub struct GitHubClient {
     config: Config,
 }
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+const FORK_CLONE_ATTEMPTS: u32 = 5;
+
+/// The user a token belongs to and its scopes. Fine-grained tokens report no scopes.
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    pub login: String,
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Checks that the GitHub API answers, without a token.
+pub async fn check_api_reachable() -> Result<()> {
+    let client = Octocrab::builder().build()?;
+    let response = client._get("/rate_limit").await?;
+    octocrab::map_github_error(response).await?;
+    Ok(())
+}
+
+/// A repository on GitHub, identified by owner and name.
+#[derive(Debug, Clone)]
+pub struct RepositoryRef {
+    pub owner: String,
+    pub repo: String,
+    pub default_branch: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RepositoryInfo {
+    pub default_branch: Option<String>,
+    /// The repository this one was forked from.
+    pub parent: Option<RepositoryRef>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize, schemars::JsonSchema)]
 pub struct Repository {
     pub name: String,
     pub private: bool,
@@ -35,71 +73,279 @@ impl GitHubClient {
         Ok(())
     }
 
+    /// The token's user and OAuth scopes, from the headers of `GET /user`.
+    pub async fn token_info(&self) -> Result<TokenInfo> {
+        let response = self.client._get("/user").await?;
+        let response = octocrab::map_github_error(response).await?;
+        let scopes = response
+            .headers()
+            .get("x-oauth-scopes")
+            .and_then(|scopes| scopes.to_str().ok())
+            .map(|scopes| {
+                scopes
+                    .split(',')
+                    .map(|scope| scope.trim().to_string())
+                    .filter(|scope| !scope.is_empty())
+                    .collect()
+            });
+        let body = self.client.body_to_string(response).await?;
+        let user: serde_json::Value = serde_json::from_str(&body)?;
+        Ok(TokenInfo {
+            login: user["login"].as_str().unwrap_or_default().to_string(),
+            scopes,
+        })
+    }
+
     pub async fn clone_repository(&self, org: &str, repository: &str) -> Result<()> {
         let url = format!("https://github.com/{}/{}.git", org, repository);
-        tokio::process::Command::new("git")
+        let output = tokio::process::Command::new("git")
             .args(["clone", "--depth=1", &url])
-            .status()
-            .await
-            .map_err(|e| GmuxError::Git(format!("Failed to clone repository: {}", e)))?;
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(GmuxError::Git(format!(
+                "Failed to clone {}: {}",
+                url,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
 
         Ok(())
     }
 
-    pub async fn get_repositories(&self, org: &str) -> Result<Vec<Repository>> {
-        let current_user = self.client.current().user().await?;
-        let current_login = current_user.login;
+    pub async fn get_repository_info(&self, owner: &str, repo: &str) -> Result<RepositoryInfo> {
+        let repository = self.client.repos(owner, repo).get().await?;
+        let parent = repository.parent.and_then(|parent| {
+            Some(RepositoryRef {
+                owner: parent.owner?.login,
+                repo: parent.name,
+                default_branch: parent.default_branch,
+            })
+        });
 
-        let mut page: u32 = 1;
-        let mut all_repos = Vec::new();
+        Ok(RepositoryInfo {
+            default_branch: repository.default_branch,
+            parent,
+        })
+    }
 
-        loop {
-            let items = if org == current_login {
-                self.client
-                    .current()
-                    .list_repos_for_authenticated_user()
-                    .type_("all")
-                    .sort(&self.config.sort)
-                    .direction(&self.config.direction)
-                    .per_page(self.config.per_page)
-                    .page(page as u8)
-                    .send()
-                    .await?
-            } else {
-                self.client
-                    .orgs(org)
-                    .list_repos()
-                    .per_page(self.config.per_page)
-                    .page(page)
-                    .send()
-                    .await?
-            };
+    /// Opens a pull request and returns its URL. `head` may be `owner:branch` for forks.
+    pub async fn create_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        title: &str,
+        head: &str,
+        base: &str,
+        body: &str,
+    ) -> Result<String> {
+        let pull_request = self
+            .client
+            .pulls(owner, repo)
+            .create(title, head, base)
+            .body(body)
+            .send()
+            .await?;
+
+        Ok(pull_request
+            .html_url
+            .map(|url| url.to_string())
+            .unwrap_or_else(|| {
+                format!(
+                    "https://github.com/{}/{}/pull/{}",
+                    owner, repo, pull_request.number
+                )
+            }))
+    }
+
+    /// Open pull requests as `(head label, url)` pairs, where the label is `owner:branch`.
+    pub async fn list_open_pull_requests(
+        &self,
+        owner: &str,
+        repo: &str,
+    ) -> Result<Vec<(String, String)>> {
+        let page = self
+            .client
+            .pulls(owner, repo)
+            .list()
+            .state(octocrab::params::State::Open)
+            .per_page(100)
+            .send()
+            .await?;
+        let pull_requests = self.client.all_pages(page).await?;
+
+        Ok(pull_requests
+            .into_iter()
+            .filter_map(|pull_request| {
+                let label = pull_request.head.label?;
+                let url = pull_request.html_url?.to_string();
+                Some((label, url))
+            })
+            .collect())
+    }
 
-            if items.items.is_empty() {
+    /// Forks `org/repository` into the authenticated user's account and clones the fork, with
+    /// `origin` pointing at the fork and `upstream` at the source repository.
+    pub async fn fork_and_clone_repository(&self, org: &str, repository: &str) -> Result<()> {
+        let fork = self
+            .client
+            .repos(org, repository)
+            .create_fork()
+            .send()
+            .await?;
+        let fork_owner = fork
+            .owner
+            .map(|owner| owner.login)
+            .ok_or_else(|| GmuxError::Validation(format!("Fork of {} has no owner", repository)))?;
+        let fork_url = format!("https://github.com/{}/{}.git", fork_owner, fork.name);
+        let upstream_url = format!("https://github.com/{}/{}.git", org, repository);
+
+        // Forks are created asynchronously, so the first clone attempts may not find it yet.
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let output = tokio::process::Command::new("git")
+                .args(["clone", "--depth=1", &fork_url, repository])
+                .output()
+                .await
+                .map_err(|e| GmuxError::Git(format!("Failed to clone repository: {}", e)))?;
+            if output.status.success() {
                 break;
             }
+            if attempts >= FORK_CLONE_ATTEMPTS {
+                return Err(GmuxError::Git(format!(
+                    "Failed to clone fork {}: {}",
+                    fork_url,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+        }
+
+        let path = std::path::Path::new(repository);
+        for args in [
+            vec!["remote", "add", "upstream", upstream_url.as_str()],
+            vec!["fetch", "--depth=1", "upstream"],
+            vec!["remote", "set-head", "upstream", "--auto"],
+        ] {
+            let output = tokio::process::Command::new("git")
+                .args(&args)
+                .current_dir(path)
+                .output()
+                .await?;
+            if !output.status.success() {
+                return Err(GmuxError::Git(
+                    String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_repositories(&self, org: &str, mode: CacheMode) -> Result<Vec<Repository>> {
+        let cache_path = get_repository_cache_path(org, &self.config);
+        let cached = match mode {
+            CacheMode::Refresh => None,
+            _ => load_repository_cache(&cache_path),
+        };
+
+        match (mode, &cached) {
+            (CacheMode::Offline, Some(cache)) => return Ok(cache.repositories()),
+            (CacheMode::Offline, None) => {
+                return Err(GmuxError::Config(
+                    ConfigErrorKind::MissingCache,
+                    format!(
+                        "No cached repository listing for '{}'. Run without --offline first.",
+                        org
+                    ),
+                ))
+            }
+            (_, Some(cache)) if cache.is_fresh(self.config.cache_ttl_secs) => {
+                return Ok(cache.repositories())
+            }
+            _ => {}
+        }
+
+        let cache = self.fetch_repository_pages(org, cached.as_ref()).await?;
+        cache.save(&cache_path)?;
+        Ok(cache.repositories())
+    }
 
-            all_repos.extend(items.items.iter().map(|repo| {
-                Repository {
-                    name: repo.name.clone(),
-                    private: repo.private.unwrap_or(false),
-                    topics: repo.topics.clone().unwrap_or_default(),
-                    language: repo
-                        .language
-                        .as_ref()
-                        .and_then(|v| v.as_str())
-                        .map(String::from),
+    /// Pages through the listing, sending each page's cached ETag so unchanged pages
+    /// come back as `304 Not Modified` and are reused from `previous`.
+    async fn fetch_repository_pages(
+        &self,
+        org: &str,
+        previous: Option<&RepositoryCache>,
+    ) -> Result<RepositoryCache> {
+        let current_user = self.client.current().user().await?;
+        let route = if org == current_user.login {
+            format!(
+                "/user/repos?type=all&sort={}&direction={}&per_page={}",
+                self.config.sort, self.config.direction, self.config.per_page
+            )
+        } else {
+            format!("/orgs/{}/repos?per_page={}", org, self.config.per_page)
+        };
+
+        let mut pages = Vec::new();
+        loop {
+            let index = pages.len();
+            let previous_page = previous.and_then(|cache| cache.pages.get(index));
+            let mut headers = HeaderMap::new();
+            if let Some(etag) = previous
+                .and_then(|cache| cache.etag_for_page(index))
+                .and_then(|etag| etag.parse::<EntityTag>().ok())
+            {
+                EntityTag::insert_if_none_match_header(&mut headers, etag)?;
+            }
+
+            let response = self
+                .client
+                ._get_with_headers(format!("{}&page={}", route, index + 1), Some(headers))
+                .await?;
+
+            let page = match previous_page {
+                Some(page) if response.status() == StatusCode::NOT_MODIFIED => page.clone(),
+                _ => {
+                    let response = octocrab::map_github_error(response).await?;
+                    let etag = EntityTag::extract_from_response(&response).map(|e| e.to_string());
+                    let body = self.client.body_to_string(response).await?;
+                    let items: Vec<octocrab::models::Repository> = serde_json::from_str(&body)?;
+                    CachedPage {
+                        etag,
+                        repositories: items.iter().map(Repository::from).collect(),
+                    }
                 }
-            }));
+            };
 
-            if items.items.len() < self.config.per_page as usize {
+            let count = page.repositories.len();
+            if count == 0 {
+                break;
+            }
+            pages.push(page);
+            if count < self.config.per_page as usize {
                 break;
             }
-
-            page += 1;
         }
 
-        Ok(all_repos)
+        Ok(RepositoryCache::new(org, pages))
+    }
+}
+
+impl From<&octocrab::models::Repository> for Repository {
+    fn from(repo: &octocrab::models::Repository) -> Self {
+        Repository {
+            name: repo.name.clone(),
+            private: repo.private.unwrap_or(false),
+            topics: repo.topics.clone().unwrap_or_default(),
+            language: repo
+                .language
+                .as_ref()
+                .and_then(|v| v.as_str())
+                .map(String::from),
+        }
     }
 }
 
@@ -0,0 +1,177 @@
+use crate::git::is_git_directory;
+use crate::output::{GrepMatch, GrepRepositoryResult, RepositoryErrorResult};
+use crate::utils::repository_name;
+use clap::Args;
+use std::path::Path;
+use tokio::process::Command;
+
+#[derive(Args, Clone, Debug)]
+pub struct GrepOptions {
+    /// Pattern to search for
+    pub pattern: String,
+    /// Limit the search to these paths
+    #[arg(last = true)]
+    pub pathspec: Vec<String>,
+    /// Ignore case differences
+    #[arg(short = 'i', long)]
+    pub ignore_case: bool,
+    /// Match the pattern only at word boundaries
+    #[arg(short = 'w', long)]
+    pub word_regexp: bool,
+    /// Treat the pattern as a literal string
+    #[arg(short = 'F', long, conflicts_with = "extended_regexp")]
+    pub fixed_strings: bool,
+    /// Treat the pattern as an extended regular expression
+    #[arg(short = 'E', long)]
+    pub extended_regexp: bool,
+}
+
+/// Searches the tracked files of one repository with `git grep`, skipping binary files.
+/// Returns `None` for directories that are not git repositories.
+pub async fn grep_repository(
+    path: &Path,
+    options: &GrepOptions,
+) -> Option<Result<GrepRepositoryResult, RepositoryErrorResult>> {
+    if !is_git_directory(path).await {
+        return None;
+    }
+
+    let mut args = vec![
+        "grep",
+        "--line-number",
+        "--column",
+        "--null",
+        "-I",
+        "--no-color",
+    ];
+    if options.ignore_case {
+        args.push("--ignore-case");
+    }
+    if options.word_regexp {
+        args.push("--word-regexp");
+    }
+    if options.fixed_strings {
+        args.push("--fixed-strings");
+    }
+    if options.extended_regexp {
+        args.push("--extended-regexp");
+    }
+    args.extend(["-e", &options.pattern, "--"]);
+    args.extend(options.pathspec.iter().map(String::as_str));
+
+    let output = match Command::new("git")
+        .args(&args)
+        .current_dir(path)
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(error) => return Some(Err(RepositoryErrorResult::new(path, error.to_string()))),
+    };
+
+    // `git grep` exits with 1 when nothing matched.
+    let matches = match output.status.code() {
+        Some(0) => parse_grep_output(&String::from_utf8_lossy(&output.stdout)),
+        Some(1) => Vec::new(),
+        _ => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Some(Err(RepositoryErrorResult::new(path, stderr.trim())));
+        }
+    };
+
+    let mut files: Vec<&str> = matches.iter().map(|m| m.path.as_str()).collect();
+    files.dedup();
+    Some(Ok(GrepRepositoryResult {
+        repository: repository_name(path),
+        path: path.display().to_string(),
+        count: matches.len(),
+        file_count: files.len(),
+        matches,
+    }))
+}
+
+/// Parses `git grep --line-number --column --null` output: `path\0line\0column\0text`.
+fn parse_grep_output(output: &str) -> Vec<GrepMatch> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(4, '\0');
+            Some(GrepMatch {
+                path: parts.next()?.to_string(),
+                line: parts.next()?.parse().ok()?,
+                column: parts.next()?.parse().ok()?,
+                text: parts.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::TempDir;
+
+    fn options(pattern: &str) -> GrepOptions {
+        GrepOptions {
+            pattern: pattern.to_string(),
+            pathspec: Vec::new(),
+            ignore_case: false,
+            word_regexp: false,
+            fixed_strings: false,
+            extended_regexp: false,
+        }
+    }
+
+    #[test]
+    fn test_parse_grep_output() {
+        let matches =
+            parse_grep_output("a b.txt\x001\x005\x00hello: world\nsrc/lib.rs\x0012\x001\x00fn\n");
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].path, "a b.txt");
+        assert_eq!((matches[0].line, matches[0].column), (1, 5));
+        assert_eq!(matches[0].text, "hello: world");
+        assert_eq!(matches[1].path, "src/lib.rs");
+        assert_eq!(matches[1].line, 12);
+    }
+
+    #[tokio::test]
+    async fn test_grep_repository() {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = temp_dir.path();
+        git(repo_path, &["init", "-b", "main"]).await;
+        std::fs::write(repo_path.join("a.txt"), "Foo bar\nfoobar\n").unwrap();
+        std::fs::write(repo_path.join("b.txt"), "foo.bar\n").unwrap();
+        std::fs::write(repo_path.join("untracked.txt"), "foo\n").unwrap();
+        git(repo_path, &["add", "a.txt", "b.txt"]).await;
+
+        let result = grep_repository(repo_path, &options("foo"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.count, 2);
+        assert_eq!(result.file_count, 2);
+
+        let mut ignore_case_words = options("foo");
+        ignore_case_words.ignore_case = true;
+        ignore_case_words.word_regexp = true;
+        let result = grep_repository(repo_path, &ignore_case_words)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.count, 2);
+        assert_eq!(result.matches[0].text, "Foo bar");
+
+        let mut fixed = options("o.b");
+        fixed.fixed_strings = true;
+        fixed.pathspec = vec!["b.txt".to_string()];
+        let result = grep_repository(repo_path, &fixed).await.unwrap().unwrap();
+        assert_eq!(result.count, 1);
+
+        let result = grep_repository(repo_path, &options("missing"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.count, 0);
+    }
+}
@@ -1,13 +1,44 @@
-use crate::error::Result;
-use crate::output::OutputFormat;
-use clap::{Parser, Subcommand};
+use crate::apply::ApplyOptions;
+use crate::branch::BranchAction;
+use crate::cache::{load_all_repository_caches, CacheMode};
+use crate::commit::CommitOptions;
+use crate::error::{GmuxError, Result};
+use crate::grep::GrepOptions;
+use crate::output::{print_json_line, print_output, OutputFormat};
+use crate::push::PushOptions;
+use crate::query::QueryOptions;
+use crate::replace::ReplaceOptions;
+use crate::report::ReportOptions;
+use crate::schema::SchemaCommand;
+use crate::snapshot::SnapshotAction;
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::CompleteEnv;
 
+mod apply;
+mod backend;
+mod branch;
+mod cache;
 mod commands;
+mod commit;
 mod config;
+mod diagnostics;
+mod doctor;
 mod error;
 mod git;
 mod github;
+mod grep;
 mod output;
+mod pr;
+mod push;
+mod query;
+mod replace;
+mod report;
+mod schema;
+mod snapshot;
+mod template;
+#[cfg(test)]
+mod test_support;
 mod utils;
 
 #[derive(Parser)]
@@ -48,6 +79,20 @@ enum Commands {
         /// Include non-git directories in the output
         #[arg(long)]
         all: bool,
+        /// Include changes and commits since the current branch diverged from the default branch
+        #[arg(long)]
+        diff: bool,
+        /// List local and origin branches with their age, merge state and open pull requests
+        #[arg(long)]
+        branches: bool,
+        /// Days without commits after which a branch is reported as stale
+        #[arg(long, default_value = "90", requires = "branches")]
+        stale_days: u64,
+        /// Maximum number of repositories to inspect concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
+        #[command(flatten)]
+        query: QueryOptions,
     },
     /// Run a command in each repository
     Cmd {
@@ -60,6 +105,10 @@ enum Commands {
         /// Maximum number of repositories to process concurrently
         #[arg(short, long, default_value = "50")]
         concurrency: usize,
+        #[command(flatten)]
+        query: QueryOptions,
+        #[command(flatten)]
+        report: ReportOptions,
     },
     /// Create a pull request for each repository
     Pr {
@@ -75,6 +124,18 @@ enum Commands {
         /// Render the PR plan without pushing or opening a browser
         #[arg(long)]
         dry_run: bool,
+        /// Base branch to open PRs against (defaults to each repository's default branch)
+        #[arg(long)]
+        base: Option<String>,
+        /// Head branch to open PRs from (defaults to each repository's current branch)
+        #[arg(long)]
+        head: Option<String>,
+        /// Create PRs through the GitHub API instead of opening a browser
+        #[arg(long)]
+        api: bool,
+        /// Open PRs against the repository origin was forked from, as reported by the GitHub API
+        #[arg(long)]
+        upstream: bool,
         /// Regex filter for repository names
         #[arg(short, long)]
         filter: Option<String>,
@@ -93,6 +154,103 @@ enum Commands {
         /// Maximum number of repositories to process concurrently
         #[arg(short, long, default_value = "50")]
         concurrency: usize,
+        #[command(flatten)]
+        query: QueryOptions,
+        #[command(flatten)]
+        report: ReportOptions,
+    },
+    /// Create, switch, delete, rename, list or prune branches in each repository
+    Branch {
+        #[command(subcommand)]
+        action: BranchAction,
+        /// Regex filter for repository names
+        #[arg(short, long, global = true)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50", global = true)]
+        concurrency: usize,
+    },
+    /// Stage and commit changes in each repository with a templated message
+    Commit {
+        #[command(flatten)]
+        options: CommitOptions,
+        /// Regex filter for repository names
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
+    },
+    /// Push a branch in each repository
+    Push {
+        #[command(flatten)]
+        options: PushOptions,
+        /// Regex filter for repository names
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
+    },
+    /// Save or restore each repository's branch and uncommitted changes
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+        /// Regex filter for repository names
+        #[arg(short, long, global = true)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50", global = true)]
+        concurrency: usize,
+    },
+    /// Search tracked files in each repository with git grep
+    Grep {
+        #[command(flatten)]
+        options: GrepOptions,
+        /// Regex filter for repository names
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
+    },
+    /// Replace regex matches in the tracked files of each repository
+    Replace {
+        #[command(flatten)]
+        options: ReplaceOptions,
+        /// Regex filter for repository names
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
+    },
+    /// Apply a patch to each repository with `git apply --3way`, `git am` for mbox patches, or
+    /// by merging a git bundle
+    Apply {
+        #[command(flatten)]
+        options: ApplyOptions,
+        /// Regex filter for repository names
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
+    },
+    /// Print the JSON Schema of a command's JSON output
+    Schema {
+        /// Command whose output schema to print
+        #[arg(value_enum)]
+        command: SchemaCommand,
+    },
+    /// Check git, the GitHub token and API, the config and each repository for problems
+    Doctor {
+        /// Regex filter for repository names
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Maximum number of repositories to process concurrently
+        #[arg(short, long, default_value = "50")]
+        concurrency: usize,
     },
     /// Clone repositories from a specified organization or user
     Clone {
@@ -102,14 +260,20 @@ enum Commands {
             long,
             value_name = "ORG",
             required = false,
-            help = "Organization or user name"
+            help = "Organization or user name",
+            add = ArgValueCandidates::new(complete_organizations)
         )]
         org: Option<String>,
         /// Organization or user name (positional)
-        #[arg(index = 1, required = false, help = "Organization or user name")]
+        #[arg(
+            index = 1,
+            required = false,
+            help = "Organization or user name",
+            add = ArgValueCandidates::new(complete_organizations)
+        )]
         org_pos: Option<String>,
         /// Regex filter for repository names
-        #[arg(short, long)]
+        #[arg(short, long, add = ArgValueCandidates::new(complete_repository_names))]
         filter: Option<String>,
         /// Filter repositories by topics (comma-separated list)
         #[arg(short, long, value_delimiter = ',')]
@@ -120,17 +284,68 @@ enum Commands {
         /// Filter repositories by primary language
         #[arg(short, long, value_name = "LANGUAGE")]
         language: Option<String>,
+        /// Fork each repository and clone the fork, adding the source as `upstream`
+        #[arg(long, conflicts_with = "offline")]
+        fork: bool,
+        /// Ignore the cached repository listing and fetch it again
+        #[arg(long, conflicts_with = "offline")]
+        refresh: bool,
+        /// Only use the cached repository listing
+        #[arg(long)]
+        offline: bool,
     },
     /// List repositories for a specified organization or user
     Ls {
         /// Organization or user name (positional)
-        #[arg(index = 1, required = true, help = "Organization or user name")]
+        #[arg(
+            index = 1,
+            required = true,
+            help = "Organization or user name",
+            add = ArgValueCandidates::new(complete_organizations)
+        )]
         org: String,
+        /// Ignore the cached repository listing and fetch it again
+        #[arg(long, conflicts_with = "offline")]
+        refresh: bool,
+        /// Only use the cached repository listing
+        #[arg(long)]
+        offline: bool,
     },
 }
 
+fn complete_organizations() -> Vec<CompletionCandidate> {
+    load_all_repository_caches()
+        .into_iter()
+        .map(|cache| CompletionCandidate::new(cache.organization))
+        .collect()
+}
+
+fn complete_repository_names() -> Vec<CompletionCandidate> {
+    load_all_repository_caches()
+        .iter()
+        .flat_map(|cache| cache.repositories())
+        .map(|repo| CompletionCandidate::new(repo.name))
+        .collect()
+}
+
+/// JSON and ndjson output get the error as a JSON object on stdout, so scripts read one
+/// stream. A failed batch has already printed its result, so only its summary goes to stderr.
+fn report_error(error: &GmuxError, output: OutputFormat) {
+    let printed = match (error, output) {
+        (GmuxError::RepositoriesFailed(_), _) => Ok(false),
+        (_, OutputFormat::Json) => print_output(&error.to_result(), output).map(|_| true),
+        (_, OutputFormat::Ndjson) => print_json_line(&error.to_result()).map(|_| true),
+        _ => Ok(false),
+    };
+    if !matches!(printed, Ok(true)) {
+        eprintln!("{}", error.format_error());
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    CompleteEnv::with_factory(Cli::command).complete();
+
     let cli = Cli::parse();
     let output = if cli.json {
         OutputFormat::Json
@@ -141,25 +356,108 @@ async fn main() -> Result<()> {
     let result = match cli.command {
         Commands::Init { directory } => commands::init(directory, output).await,
         Commands::Setup { token, org } => commands::setup(token, org, output).await,
-        Commands::Inspect { filter, all } => commands::inspect(filter, all, output).await,
+        Commands::Inspect {
+            filter,
+            all,
+            diff,
+            branches,
+            stale_days,
+            concurrency,
+            query,
+        } => {
+            commands::inspect(
+                filter,
+                all,
+                diff,
+                branches,
+                stale_days,
+                concurrency,
+                query,
+                output,
+            )
+            .await
+        }
         Commands::Cmd {
             command,
             filter,
             concurrency,
-        } => commands::cmd(command, filter, concurrency, output).await,
+            query,
+            report,
+        } => commands::cmd(command, filter, concurrency, query, report, output).await,
         Commands::Pr {
             title,
             yes,
             no_input,
             dry_run,
+            base,
+            head,
+            api,
+            upstream,
             filter,
             concurrency,
-        } => commands::pr(title, yes, no_input, dry_run, filter, concurrency, output).await,
+        } => {
+            commands::pr(
+                title,
+                yes,
+                no_input,
+                dry_run,
+                base,
+                head,
+                api,
+                upstream,
+                filter,
+                concurrency,
+                output,
+            )
+            .await
+        }
         Commands::Git {
             command,
             filter,
             concurrency,
-        } => commands::git(command, filter, concurrency, output).await,
+            query,
+            report,
+        } => commands::git(command, filter, concurrency, query, report, output).await,
+        Commands::Branch {
+            action,
+            filter,
+            concurrency,
+        } => commands::branch(action, filter, concurrency, output).await,
+        Commands::Commit {
+            options,
+            filter,
+            concurrency,
+        } => commands::commit(options, filter, concurrency, output).await,
+        Commands::Push {
+            options,
+            filter,
+            concurrency,
+        } => commands::push(options, filter, concurrency, output).await,
+        Commands::Snapshot {
+            action,
+            filter,
+            concurrency,
+        } => commands::snapshot(action, filter, concurrency, output).await,
+        Commands::Grep {
+            options,
+            filter,
+            concurrency,
+        } => commands::grep(options, filter, concurrency, output).await,
+        Commands::Replace {
+            options,
+            filter,
+            concurrency,
+        } => commands::replace(options, filter, concurrency, output).await,
+        Commands::Apply {
+            options,
+            filter,
+            concurrency,
+        } => commands::apply(options, filter, concurrency, output).await,
+        Commands::Schema { command } => commands::schema(command),
+        Commands::Doctor {
+            filter,
+            concurrency,
+        } => commands::doctor(filter, concurrency, output).await,
         Commands::Clone {
             org,
             org_pos,
@@ -167,15 +465,35 @@ async fn main() -> Result<()> {
             topics,
             visibility,
             language,
-        } => commands::clone(org, org_pos, filter, topics, visibility, language, output).await,
-        Commands::Ls { org } => commands::list(org, output).await,
+            fork,
+            refresh,
+            offline,
+        } => {
+            commands::clone(
+                org,
+                org_pos,
+                filter,
+                topics,
+                visibility,
+                language,
+                fork,
+                CacheMode::from_flags(refresh, offline),
+                output,
+            )
+            .await
+        }
+        Commands::Ls {
+            org,
+            refresh,
+            offline,
+        } => commands::list(org, CacheMode::from_flags(refresh, offline), output).await,
     };
 
     match result {
         Ok(_) => Ok(()),
         Err(e) => {
-            eprintln!("{}", e.format_error());
-            std::process::exit(1);
+            report_error(&e, output);
+            std::process::exit(e.exit_code());
         }
     }
 }
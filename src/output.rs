@@ -1,21 +1,31 @@
 use clap::ValueEnum;
+use schemars::JsonSchema;
 use serde::Serialize;
+use serde_json::{Map, Value};
 
 use crate::error::Result;
+use crate::git::{BranchDiff, LfsStatus, SubmoduleStatus};
+use crate::github::Repository;
+use crate::query::QueryOptions;
+use crate::utils::repository_name;
+use std::path::Path;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
 pub enum OutputFormat {
+    #[default]
     Text,
     Json,
+    /// One JSON record per line
+    Ndjson,
+    Csv,
+    Tsv,
+    /// Aligned plain-text columns
+    Table,
+    /// A Markdown table
+    Markdown,
 }
 
-impl Default for OutputFormat {
-    fn default() -> Self {
-        Self::Text
-    }
-}
-
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct RepositoryCommandResult {
     pub repository: String,
     pub path: String,
@@ -26,30 +36,71 @@ pub struct RepositoryCommandResult {
     pub duration_ms: u128,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct RepositoryErrorResult {
     pub repository: String,
     pub path: String,
     pub error: String,
 }
 
-#[derive(Debug, Serialize)]
+impl RepositoryErrorResult {
+    pub fn new(path: &Path, error: impl Into<String>) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            error: error.into(),
+        }
+    }
+}
+
+/// A command that failed as a whole, printed in place of its result.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ErrorResult {
+    /// Stable identifier such as `config.missing_token` or `github.rate_limited`
+    pub code: String,
+    pub message: String,
+    pub hint: Option<String>,
+    pub details: Option<Value>,
+    pub exit_code: i32,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RepositorySkippedResult {
+    pub repository: String,
+    pub path: String,
+    pub reason: String,
+}
+
+impl RepositorySkippedResult {
+    pub fn new(path: &Path, reason: impl Into<String>) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// `failed` counts both non-zero exit codes in `results` and the repositories in `errors`,
+/// where the command could not be run at all.
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct CommandBatchResult {
     pub command: String,
     pub succeeded: usize,
     pub failed: usize,
     pub results: Vec<RepositoryCommandResult>,
+    pub skipped: Vec<RepositorySkippedResult>,
     pub errors: Vec<RepositoryErrorResult>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct CloneResult {
     pub repository: String,
     pub status: String,
     pub error: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct CloneBatchResult {
     pub organization: String,
     pub matched: usize,
@@ -59,7 +110,7 @@ pub struct CloneBatchResult {
     pub results: Vec<CloneResult>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct PullRequestPlan {
     pub repository: String,
     pub path: String,
@@ -67,6 +118,9 @@ pub struct PullRequestPlan {
     pub repo: Option<String>,
     pub base: Option<String>,
     pub head: Option<String>,
+    pub head_owner: Option<String>,
+    pub head_pushed: bool,
+    pub diff: Option<BranchDiff>,
     pub title: String,
     pub body: Option<String>,
     pub url: Option<String>,
@@ -74,7 +128,7 @@ pub struct PullRequestPlan {
     pub reason: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct PullRequestBatchResult {
     pub title: String,
     pub dry_run: bool,
@@ -82,14 +136,239 @@ pub struct PullRequestBatchResult {
     pub errors: Vec<RepositoryErrorResult>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BranchResult {
+    pub repository: String,
+    pub path: String,
+    /// The branch acted on; the new name for renames.
+    pub branch: Option<String>,
+    pub current_branch: Option<String>,
+    pub status: String,
+    pub reason: Option<String>,
+    /// Listed, deleted or pruned branches, depending on the action.
+    pub branches: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct BranchBatchResult {
+    pub action: String,
+    pub succeeded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub results: Vec<BranchResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CommitResult {
+    pub repository: String,
+    pub path: String,
+    pub branch: Option<String>,
+    pub status: String,
+    pub reason: Option<String>,
+    pub commit: Option<String>,
+    pub message: Option<String>,
+    /// Files included in the commit.
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct CommitBatchResult {
+    pub committed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub results: Vec<CommitResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PushResult {
+    pub repository: String,
+    pub path: String,
+    pub branch: Option<String>,
+    pub remote: String,
+    pub status: String,
+    pub reason: Option<String>,
+    /// Commits ahead of and behind the upstream before pushing.
+    pub ahead: Option<u32>,
+    pub behind: Option<u32>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct PushBatchResult {
+    pub pushed: usize,
+    pub up_to_date: usize,
+    pub rejected: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub results: Vec<PushResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SnapshotRepositoryResult {
+    pub repository: String,
+    pub path: String,
+    pub branch: Option<String>,
+    pub head: Option<String>,
+    pub stash: Option<String>,
+    pub status: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SnapshotBatchResult {
+    pub name: String,
+    pub action: String,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<SnapshotRepositoryResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SnapshotSummary {
+    pub name: String,
+    pub workspace: String,
+    pub created_at: u64,
+    pub repositories: usize,
+    pub stashes: usize,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GrepMatch {
+    pub path: String,
+    pub line: u32,
+    pub column: u32,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GrepRepositoryResult {
+    pub repository: String,
+    pub path: String,
+    pub count: usize,
+    pub file_count: usize,
+    pub matches: Vec<GrepMatch>,
+}
+
+/// Only repositories with at least one match are listed in `results`.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct GrepBatchResult {
+    pub pattern: String,
+    pub searched: usize,
+    pub count: usize,
+    pub file_count: usize,
+    pub results: Vec<GrepRepositoryResult>,
+    pub errors: Vec<RepositoryErrorResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReplaceFileResult {
+    pub path: String,
+    pub replacements: usize,
+    /// Unified diff of the change, only rendered in dry-run mode.
+    pub diff: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReplaceRepositoryResult {
+    pub repository: String,
+    pub path: String,
+    pub replacements: usize,
+    pub files: Vec<ReplaceFileResult>,
+}
+
+/// Only repositories with at least one changed file are listed in `results`.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ReplaceBatchResult {
+    pub pattern: String,
+    pub replacement: String,
+    pub dry_run: bool,
+    pub replacements: usize,
+    pub file_count: usize,
+    pub results: Vec<ReplaceRepositoryResult>,
+    pub errors: Vec<RepositoryErrorResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ApplyResult {
+    pub repository: String,
+    pub path: String,
+    pub branch: Option<String>,
+    pub status: String,
+    pub reason: Option<String>,
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ApplyBatchResult {
+    pub patch: String,
+    pub applied: usize,
+    pub already_applied: usize,
+    pub conflict: usize,
+    pub failed: usize,
+    pub results: Vec<ApplyResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct InitResult {
+    pub directory: String,
+    pub template_path: String,
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SetupResult {
+    pub config_path: String,
+    pub default_org: String,
+    pub credential_store: String,
+    pub status: String,
+}
+
+/// One `doctor` check; `status` is `pass`, `warn` or `fail`.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DoctorCheck {
+    pub name: String,
+    pub status: String,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DoctorRepositoryResult {
+    pub repository: String,
+    pub path: String,
+    /// The worst status of the repository's checks
+    pub status: String,
+    pub checks: Vec<DoctorCheck>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct DoctorResult {
+    pub passed: usize,
+    pub warned: usize,
+    pub failed: usize,
+    pub checks: Vec<DoctorCheck>,
+    pub repositories: Vec<DoctorRepositoryResult>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct RepositoryListResult {
+    pub organization: String,
+    pub count: usize,
+    pub repositories: Vec<Repository>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SnapshotListResult {
+    pub snapshots: Vec<SnapshotSummary>,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct InspectWorkspaceResult {
     pub workspace: String,
     pub count: usize,
     pub repositories: Vec<InspectRepositoryResult>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct InspectRepositoryResult {
     pub repository: String,
     pub path: String,
@@ -101,12 +380,47 @@ pub struct InspectRepositoryResult {
     pub ahead: Option<u32>,
     pub behind: Option<u32>,
     pub dirty: Option<bool>,
+    /// Every changed, untracked or conflicted path.
     pub changed_files: Vec<String>,
+    pub staged_files: Vec<String>,
+    pub unstaged_files: Vec<String>,
+    pub untracked_files: Vec<String>,
+    pub conflicted_files: Vec<String>,
+    pub stash_count: Option<usize>,
+    /// `rebase`, `am`, `merge`, `cherry-pick`, `revert` or `bisect` when one has stopped
+    /// partway.
+    pub operation_in_progress: Option<String>,
+    pub detached: Option<bool>,
+    pub shallow: Option<bool>,
+    pub submodules: Vec<SubmoduleStatus>,
+    /// `None` when the repository does not use Git LFS.
+    pub lfs: Option<LfsStatus>,
     pub last_commit: Option<InspectCommitResult>,
+    pub diff: Option<BranchDiff>,
+    pub branches: Option<Vec<InspectBranchResult>>,
     pub error: Option<String>,
 }
 
-#[derive(Debug, Serialize)]
+/// A local or `origin` branch compared against the default branch.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct InspectBranchResult {
+    pub name: String,
+    pub remote: Option<String>,
+    pub current: bool,
+    pub last_commit_at: String,
+    pub age_days: u64,
+    /// `None` when the default branch is unknown.
+    pub ahead: Option<u32>,
+    pub behind: Option<u32>,
+    pub merged: Option<bool>,
+    /// URL of an open pull request from this branch, when GitHub could be queried.
+    pub pull_request: Option<String>,
+    pub stale: bool,
+    /// One of `merged`, `open-pr`, `stale` or `active`.
+    pub status: String,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct InspectCommitResult {
     pub hash: String,
     pub short_hash: String,
@@ -114,7 +428,334 @@ pub struct InspectCommitResult {
     pub committed_at: String,
 }
 
+/// Version of the JSON output contract, reported as `schema_version` in every top-level JSON
+/// result. See "JSON Schema" in the README for when it changes.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// A top-level JSON result, with the schema version ahead of the result's own fields.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct Versioned<T> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub result: T,
+}
+
 pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
     println!("{}", serde_json::to_string_pretty(value)?);
     Ok(())
 }
+
+/// Prints one compact JSON record, for streaming `ndjson` output.
+pub fn print_json_line<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// Renders a result in any structured format. The records of `ndjson` and the rows of the
+/// tabular formats are the elements of the result's top-level arrays of objects, e.g. a batch
+/// result's `results` and `errors`, tagged with the array they came from when there are
+/// several; a result without such arrays is a single record.
+pub fn print_output<T: Serialize>(value: &T, output: OutputFormat) -> Result<()> {
+    print_query_output(value, &QueryOptions::default(), output)
+}
+
+/// Like `print_output`, keeping only the `--fields` of each record. In text mode the selected
+/// fields are shown as a table.
+pub fn print_query_output<T: Serialize>(
+    value: &T,
+    query: &QueryOptions,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut value = if output == OutputFormat::Json {
+        serde_json::to_value(Versioned {
+            schema_version: SCHEMA_VERSION,
+            result: value,
+        })?
+    } else {
+        serde_json::to_value(value)?
+    };
+    if !query.fields.is_empty() {
+        project_records(&mut value, query);
+    }
+    match output {
+        OutputFormat::Json => print_json(&value),
+        OutputFormat::Ndjson => {
+            for record in records(&value) {
+                print_json_line(&record)?;
+            }
+            Ok(())
+        }
+        OutputFormat::Text => {
+            print!("{}", render_rows(&records(&value), OutputFormat::Table));
+            Ok(())
+        }
+        _ => {
+            print!("{}", render_rows(&records(&value), output));
+            Ok(())
+        }
+    }
+}
+
+fn is_object_array(value: &Value) -> bool {
+    matches!(value, Value::Array(items) if items.iter().all(Value::is_object))
+}
+
+fn records(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(items) => items.clone(),
+        Value::Object(fields) if fields.values().any(is_object_array) => {
+            let arrays: Vec<(&String, &Value)> = fields
+                .iter()
+                .filter(|(_, field)| is_object_array(field))
+                .collect();
+            let tagged = arrays.len() > 1;
+            arrays
+                .into_iter()
+                .flat_map(|(name, field)| {
+                    field.as_array().into_iter().flatten().map(move |record| {
+                        if tagged {
+                            tag_record(name, record.clone())
+                        } else {
+                            record.clone()
+                        }
+                    })
+                })
+                .collect()
+        }
+        _ => vec![value.clone()],
+    }
+}
+
+/// Adds a leading `kind` field naming the array of the JSON output a record belongs to, such as
+/// `results`, `skipped` or `errors`, so rows of different shapes can be told apart.
+pub fn tag_record(kind: &str, record: Value) -> Value {
+    match record {
+        Value::Object(fields) => {
+            let mut tagged = Map::new();
+            tagged.insert("kind".to_string(), Value::String(kind.to_string()));
+            tagged.extend(fields);
+            Value::Object(tagged)
+        }
+        record => record,
+    }
+}
+
+fn project_records(value: &mut Value, query: &QueryOptions) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                *item = query.project(item);
+            }
+        }
+        Value::Object(fields) if fields.values().any(is_object_array) => {
+            for field in fields.values_mut().filter(|field| is_object_array(field)) {
+                project_records(field, query);
+            }
+        }
+        record => *record = query.project(record),
+    }
+}
+
+/// Renders records as CSV, TSV, an aligned table or a Markdown table. Nested objects become
+/// dotted columns, and the columns are the union of every record's fields.
+fn render_rows(records: &[Value], output: OutputFormat) -> String {
+    let rows: Vec<Vec<(String, String)>> = records
+        .iter()
+        .map(|record| {
+            let mut cells = Vec::new();
+            flatten_record("", record, &mut cells);
+            cells
+        })
+        .collect();
+    let mut columns: Vec<&str> = Vec::new();
+    for (column, _) in rows.iter().flatten() {
+        if !columns.contains(&column.as_str()) {
+            columns.push(column);
+        }
+    }
+    let table: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| {
+                    let cell = row
+                        .iter()
+                        .find(|(name, _)| name == column)
+                        .map(|(_, cell)| cell.as_str())
+                        .unwrap_or_default();
+                    escape_cell(cell, output)
+                })
+                .collect()
+        })
+        .collect();
+    let header: Vec<String> = columns
+        .iter()
+        .map(|column| escape_cell(column, output))
+        .collect();
+
+    let mut rendered = String::new();
+    match output {
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let separator = if output == OutputFormat::Csv {
+                ","
+            } else {
+                "\t"
+            };
+            for row in std::iter::once(&header).chain(&table) {
+                rendered.push_str(&row.join(separator));
+                rendered.push('\n');
+            }
+        }
+        OutputFormat::Markdown => {
+            let divider = vec!["---".to_string(); header.len()];
+            for row in [&header, &divider].into_iter().chain(&table) {
+                rendered.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+        }
+        _ => {
+            let widths: Vec<usize> = (0..header.len())
+                .map(|index| {
+                    std::iter::once(&header)
+                        .chain(&table)
+                        .map(|row| row[index].chars().count())
+                        .max()
+                        .unwrap_or(0)
+                })
+                .collect();
+            for row in std::iter::once(&header).chain(&table) {
+                let cells: Vec<String> = row
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| format!("{:<width$}", cell))
+                    .collect();
+                rendered.push_str(cells.join("  ").trim_end());
+                rendered.push('\n');
+            }
+        }
+    }
+    rendered
+}
+
+/// Arrays of scalars are joined with commas and arrays of objects are shown as their length.
+fn flatten_record(prefix: &str, value: &Value, cells: &mut Vec<(String, String)>) {
+    let cell = match value {
+        Value::Object(fields) => {
+            flatten_object(prefix, fields, cells);
+            return;
+        }
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) if items.iter().any(|item| item.is_object() || item.is_array()) => {
+            items.len().to_string()
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(text) => text.clone(),
+                Value::Null => String::new(),
+                item => item.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        value => value.to_string(),
+    };
+    cells.push((prefix.to_string(), cell));
+}
+
+fn flatten_object(prefix: &str, fields: &Map<String, Value>, cells: &mut Vec<(String, String)>) {
+    for (name, value) in fields {
+        let column = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        flatten_record(&column, value, cells);
+    }
+}
+
+fn escape_cell(cell: &str, output: OutputFormat) -> String {
+    match output {
+        OutputFormat::Csv if cell.contains([',', '"', '\n', '\r']) => {
+            format!("\"{}\"", cell.replace('"', "\"\""))
+        }
+        OutputFormat::Tsv => cell
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r"),
+        OutputFormat::Markdown => cell
+            .trim_end()
+            .replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace('\n', "<br>"),
+        OutputFormat::Table => cell.trim_end().replace('\n', " ⏎ "),
+        _ => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn batch() -> Value {
+        json!({
+            "command": "make test",
+            "succeeded": 1,
+            "results": [
+                {"repository": "api", "exit_code": 0, "stdout": "ok\n", "files": ["a", "b"]},
+                {"repository": "web, ui", "exit_code": 2, "stdout": "say \"hi\"\nbye"}
+            ],
+            "errors": [
+                {"repository": "docs", "error": "a|b", "last_commit": {"sha": "abc"}}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_records() {
+        let batch = batch();
+        let records = records(&batch);
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0]["kind"], "results");
+        assert_eq!(records[2]["kind"], "errors");
+        assert_eq!(records[2]["repository"], "docs");
+
+        let single = json!({"status": "saved"});
+        assert_eq!(super::records(&single), vec![single.clone()]);
+
+        // Records from a single array are not tagged.
+        let listing = json!({"repositories": [{"name": "api"}]});
+        assert_eq!(super::records(&listing), vec![json!({"name": "api"})]);
+    }
+
+    #[test]
+    fn test_render_rows() {
+        let batch = batch();
+        let records = records(&batch);
+
+        assert_eq!(
+            render_rows(&records, OutputFormat::Csv),
+            "kind,repository,exit_code,stdout,files,error,last_commit.sha\n\
+             results,api,0,\"ok\n\",\"a, b\",,\n\
+             results,\"web, ui\",2,\"say \"\"hi\"\"\nbye\",,,\n\
+             errors,docs,,,,a|b,abc\n"
+        );
+        assert_eq!(
+            render_rows(&records, OutputFormat::Tsv).lines().nth(2),
+            Some("results\tweb, ui\t2\tsay \"hi\"\\nbye\t\t\t")
+        );
+        assert_eq!(
+            render_rows(&records, OutputFormat::Markdown).lines().nth(4),
+            Some("| errors | docs |  |  |  | a\\|b | abc |")
+        );
+        assert_eq!(
+            render_rows(&records, OutputFormat::Table),
+            "kind     repository  exit_code  stdout          files  error  last_commit.sha\n\
+             results  api         0          ok              a, b\n\
+             results  web, ui     2          say \"hi\" ⏎ bye\n\
+             errors   docs                                          a|b    abc\n"
+        );
+    }
+}
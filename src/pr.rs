@@ -0,0 +1,744 @@
+use crate::diagnostics::{with_hint, DETACHED_HEAD, MISSING_REMOTE_HEAD};
+use crate::error::{GmuxError, Result};
+use crate::git::{
+    fetch_remote_branch, get_branch_diff, get_remote_head, get_remote_url, get_repository_metadata,
+    is_git_directory, parse_github_remote, push_branch, remote_branch_exists, resolve_branch_ref,
+    resolve_first_ref, RepositoryMetadata,
+};
+use crate::github::GitHubClient;
+use crate::output::{PullRequestPlan, RepositoryErrorResult};
+use crate::template::{render_template, TemplateContext};
+use crate::utils::repository_name;
+use colored::Colorize;
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+pub const STATUS_READY: &str = "ready";
+pub const STATUS_PLANNED: &str = "planned";
+pub const STATUS_OPENED: &str = "opened";
+pub const STATUS_CREATED: &str = "created";
+pub const STATUS_SKIPPED: &str = "skipped";
+
+const UPSTREAM_REMOTE: &str = "upstream";
+
+pub const REASON_NOT_PUSHED: &str = "branch has not been pushed; pass --yes to push";
+pub const REASON_DESELECTED: &str = "deselected during review";
+pub const REASON_ABORTED: &str = "review aborted";
+
+impl PullRequestPlan {
+    fn new(path: &Path, title: &str) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            owner: None,
+            repo: None,
+            base: None,
+            head: None,
+            head_owner: None,
+            head_pushed: false,
+            diff: None,
+            title: title.to_string(),
+            body: None,
+            url: None,
+            status: STATUS_READY.to_string(),
+            reason: None,
+        }
+    }
+
+    pub fn skip(&mut self, reason: &str) {
+        self.status = STATUS_SKIPPED.to_string();
+        self.reason = Some(reason.to_string());
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.status == STATUS_READY
+    }
+
+    pub fn file_count(&self) -> usize {
+        self.diff.as_ref().map_or(0, |diff| diff.files.len())
+    }
+
+    /// Ready to open, but the head branch still has to be pushed first.
+    pub fn needs_push(&self) -> bool {
+        self.is_ready() && !self.head_pushed
+    }
+
+    /// The head as GitHub expects it: `owner:branch` when opening from a fork.
+    pub fn head_spec(&self) -> Option<String> {
+        let head = self.head.as_ref()?;
+        Some(match &self.head_owner {
+            Some(owner) => format!("{}:{}", owner, head),
+            None => head.clone(),
+        })
+    }
+
+    /// Replaces the title and body, keeping the compare URL in sync.
+    pub fn set_content(&mut self, title: &str, body: &str) {
+        self.title = title.to_string();
+        self.body = Some(body.to_string());
+        if let (Some(owner), Some(repo), Some(base), Some(head)) =
+            (&self.owner, &self.repo, &self.base, self.head_spec())
+        {
+            self.url = Some(compare_url(owner, repo, base, &head, title, body));
+        }
+    }
+}
+
+fn compare_url(owner: &str, repo: &str, base: &str, head: &str, title: &str, body: &str) -> String {
+    format!(
+        "https://github.com/{}/{}/compare/{}...{}?expand=1&title={}&body={}",
+        owner,
+        repo,
+        base,
+        head,
+        urlencoding::encode(title),
+        urlencoding::encode(body)
+    )
+}
+
+pub struct PlanOptions {
+    pub title: String,
+    pub template_content: String,
+    /// Base branch for every repository, e.g. the previous branch of a stack.
+    pub base: Option<String>,
+    /// Head branch to open from instead of the current branch.
+    pub head: Option<String>,
+    /// Per-repository base branches from the workspace config.
+    pub base_overrides: HashMap<String, String>,
+    /// Open pull requests against the repository each `origin` was forked from, as reported
+    /// by the GitHub API, when there is no `upstream` remote.
+    pub upstream: bool,
+    /// Used to look up fork parents, and the default branch when `origin/HEAD` is not set.
+    pub client: Option<GitHubClient>,
+}
+
+/// Plans a pull request for every path without changing anything locally or remotely.
+/// Plans are returned in the same order as `paths`.
+pub async fn plan_pull_requests(
+    paths: Vec<Box<Path>>,
+    options: &PlanOptions,
+    concurrency: usize,
+) -> (Vec<PullRequestPlan>, Vec<RepositoryErrorResult>) {
+    let results: Vec<_> = stream::iter(paths)
+        .map(|path| async move { plan_pull_request(&path, options).await })
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let mut plans = Vec::new();
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(plan) => plans.push(plan),
+            Err(error) => errors.push(error),
+        }
+    }
+    (plans, errors)
+}
+
+async fn plan_pull_request(
+    path: &Path,
+    options: &PlanOptions,
+) -> std::result::Result<PullRequestPlan, RepositoryErrorResult> {
+    let title = options.title.as_str();
+    let mut plan = PullRequestPlan::new(path, title);
+    if !is_git_directory(path).await {
+        plan.skip("not a git repository");
+        return Ok(plan);
+    }
+
+    let error = |error: GmuxError| RepositoryErrorResult::new(path, error.format_error());
+    let metadata = get_repository_metadata(path)
+        .await
+        .map_err(error)?
+        .ok_or_else(|| RepositoryErrorResult::new(path, "not a git repository"))?;
+    let remote_url = get_remote_url(path, "origin")
+        .await
+        .map_err(error)?
+        .unwrap_or_default();
+    let origin = parse_github_remote(&remote_url);
+    let base_override = options
+        .base
+        .clone()
+        .or_else(|| options.base_overrides.get(&plan.repository).cloned());
+    let target = resolve_target(
+        path,
+        origin.as_ref(),
+        &metadata,
+        options,
+        base_override.is_none(),
+    )
+    .await
+    .map_err(error)?;
+
+    let head = options
+        .head
+        .clone()
+        .unwrap_or_else(|| metadata.current_branch.clone());
+    if head.is_empty() {
+        plan.skip(&with_hint(DETACHED_HEAD));
+        return Ok(plan);
+    }
+    plan.head = Some(head.clone());
+
+    let base = base_override.or_else(|| target.default_branch.clone());
+    let Some(base) = base else {
+        plan.skip(&format!(
+            "{}; run `git remote set-head origin --auto` or pass --base",
+            MISSING_REMOTE_HEAD
+        ));
+        return Ok(plan);
+    };
+    plan.base = Some(base.clone());
+
+    if base == head && !target.fork {
+        plan.skip("head and base branches are the same");
+        return Ok(plan);
+    }
+    // For forks, the upstream copy of the base branch is preferred over a possibly stale local
+    // one. Without an `upstream` remote, the parent's base branch is fetched by URL.
+    let mut base_candidates = vec![base.clone(), format!("origin/{}", base)];
+    if target.remote == UPSTREAM_REMOTE {
+        base_candidates.insert(0, format!("{}/{}", UPSTREAM_REMOTE, base));
+    } else if target.fork {
+        match fetch_remote_branch(path, &target.remote, &base)
+            .await
+            .map_err(error)?
+        {
+            Some(commit) => base_candidates = vec![commit],
+            None => {
+                plan.skip(&format!("base branch {} not found upstream", base));
+                return Ok(plan);
+            }
+        }
+    }
+    let Some(base_ref) = resolve_first_ref(path, &base_candidates)
+        .await
+        .map_err(error)?
+    else {
+        plan.skip(&format!("base branch {} not found", base));
+        return Ok(plan);
+    };
+    let head_ref = match &options.head {
+        Some(head) => match resolve_branch_ref(path, head).await.map_err(error)? {
+            Some(head_ref) => head_ref,
+            None => {
+                plan.skip(&format!("head branch {} not found", head));
+                return Ok(plan);
+            }
+        },
+        None => "HEAD".to_string(),
+    };
+
+    let mut diff = get_branch_diff(path, &base_ref, &head_ref)
+        .await
+        .map_err(error)?;
+    diff.base = base.clone();
+    diff.head = head.clone();
+    if diff.files.is_empty() {
+        plan.skip("no changes found");
+        return Ok(plan);
+    }
+
+    let unparseable = || {
+        RepositoryErrorResult::new(
+            path,
+            format!("could not parse GitHub remote URL: {}", remote_url),
+        )
+    };
+    let (owner, repo) = target.repository.ok_or_else(unparseable)?;
+    if target.fork {
+        plan.head_owner = Some(origin.ok_or_else(unparseable)?.0);
+    }
+
+    // A stacked pull request targets another feature branch, which must exist on the remote.
+    if target.default_branch.as_ref() != Some(&base)
+        && !remote_branch_exists(path, &target.remote, &base)
+            .await
+            .map_err(error)?
+    {
+        plan.skip(&format!("base branch {} has not been pushed", base));
+        return Ok(plan);
+    }
+
+    plan.head_pushed = remote_branch_exists(path, "origin", &head)
+        .await
+        .map_err(error)?;
+
+    let body = render_template(
+        &options.template_content,
+        &TemplateContext::new(title, &plan.repository, &diff),
+    )
+    .map_err(error)?;
+    plan.owner = Some(owner);
+    plan.repo = Some(repo);
+    plan.diff = Some(diff);
+    plan.set_content(title, &body);
+
+    Ok(plan)
+}
+
+/// Where a pull request is opened: `origin`, or the repository it was forked from.
+struct PullRequestTarget {
+    repository: Option<(String, String)>,
+    /// Remote name or URL that holds the base branch.
+    remote: String,
+    default_branch: Option<String>,
+    fork: bool,
+}
+
+/// An `upstream` remote marks the repository as a fork. Otherwise, with `--upstream`, the
+/// GitHub API is asked for the repository's parent. The API is also asked for the default
+/// branch when `origin/HEAD` is unset and no base branch was given.
+async fn resolve_target(
+    path: &Path,
+    origin: Option<&(String, String)>,
+    metadata: &RepositoryMetadata,
+    options: &PlanOptions,
+    needs_default_branch: bool,
+) -> Result<PullRequestTarget> {
+    let client = options.client.as_ref();
+    if let Some(upstream) = get_remote_url(path, UPSTREAM_REMOTE)
+        .await?
+        .as_deref()
+        .and_then(parse_github_remote)
+    {
+        let mut default_branch = get_remote_head(path, UPSTREAM_REMOTE).await?;
+        if let (None, true, Some(client)) = (&default_branch, needs_default_branch, client) {
+            default_branch = client
+                .get_repository_info(&upstream.0, &upstream.1)
+                .await?
+                .default_branch;
+        }
+        return Ok(PullRequestTarget {
+            repository: Some(upstream),
+            remote: UPSTREAM_REMOTE.to_string(),
+            default_branch,
+            fork: true,
+        });
+    }
+
+    let mut target = PullRequestTarget {
+        repository: origin.cloned(),
+        remote: "origin".to_string(),
+        default_branch: metadata
+            .has_remote_head
+            .then(|| metadata.default_branch.clone()),
+        fork: false,
+    };
+    let (Some((owner, repo)), Some(client)) = (origin, client) else {
+        return Ok(target);
+    };
+    if !options.upstream && (target.default_branch.is_some() || !needs_default_branch) {
+        return Ok(target);
+    }
+
+    // The lookup is best effort unless it is the only source of the default branch.
+    let info = match client.get_repository_info(owner, repo).await {
+        Ok(info) => info,
+        Err(_) if target.default_branch.is_some() => return Ok(target),
+        Err(error) => return Err(error),
+    };
+    if let (true, Some(parent)) = (options.upstream, info.parent) {
+        target.remote = format!("https://github.com/{}/{}.git", parent.owner, parent.repo);
+        target.repository = Some((parent.owner, parent.repo));
+        target.default_branch = parent.default_branch;
+        target.fork = true;
+    } else if target.default_branch.is_none() {
+        target.default_branch = info.default_branch;
+    }
+    Ok(target)
+}
+
+/// Marks every ready plan as planned; nothing is pushed or opened.
+pub fn mark_planned(plans: &mut [PullRequestPlan]) {
+    for plan in plans.iter_mut().filter(|plan| plan.is_ready()) {
+        plan.status = STATUS_PLANNED.to_string();
+    }
+}
+
+/// Pushes the head branch of every plan that still needs it. Plans whose push fails are
+/// moved into `errors`.
+pub async fn push_pending_branches(
+    plans: &mut Vec<PullRequestPlan>,
+    errors: &mut Vec<RepositoryErrorResult>,
+    concurrency: usize,
+) {
+    let results: Vec<_> = stream::iter(plans.iter().enumerate())
+        .filter(|(_, plan)| std::future::ready(plan.needs_push()))
+        .map(|(index, plan)| async move {
+            let path = Path::new(&plan.path);
+            let head = plan.head.as_deref().unwrap_or_default();
+            (index, push_branch(path, "origin", head).await)
+        })
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let mut failed = Vec::new();
+    for (index, result) in results {
+        match result {
+            Ok(()) => plans[index].head_pushed = true,
+            Err(error) => failed.push((index, error.format_error())),
+        }
+    }
+
+    for (index, error) in failed.into_iter().rev() {
+        let plan = plans.remove(index);
+        errors.push(RepositoryErrorResult::new(Path::new(&plan.path), error));
+    }
+}
+
+/// Creates every ready plan as a pull request through the GitHub API. Plans that fail are
+/// moved into `errors`.
+pub async fn create_pull_requests(
+    plans: &mut Vec<PullRequestPlan>,
+    errors: &mut Vec<RepositoryErrorResult>,
+    client: &GitHubClient,
+    concurrency: usize,
+) {
+    let results: Vec<_> = stream::iter(plans.iter().enumerate())
+        .filter(|(_, plan)| std::future::ready(plan.is_ready()))
+        .map(|(index, plan)| async move {
+            let result = client
+                .create_pull_request(
+                    plan.owner.as_deref().unwrap_or_default(),
+                    plan.repo.as_deref().unwrap_or_default(),
+                    &plan.title,
+                    &plan.head_spec().unwrap_or_default(),
+                    plan.base.as_deref().unwrap_or_default(),
+                    plan.body.as_deref().unwrap_or_default(),
+                )
+                .await;
+            (index, result)
+        })
+        .buffered(concurrency)
+        .collect()
+        .await;
+
+    let mut failed = Vec::new();
+    for (index, result) in results {
+        match result {
+            Ok(url) => {
+                plans[index].url = Some(url);
+                plans[index].status = STATUS_CREATED.to_string();
+            }
+            Err(error) => failed.push((index, error.format_error())),
+        }
+    }
+
+    for (index, error) in failed.into_iter().rev() {
+        let plan = plans.remove(index);
+        errors.push(RepositoryErrorResult::new(Path::new(&plan.path), error));
+    }
+}
+
+/// Opens the compare page of every ready plan in the browser.
+pub fn open_pull_requests(plans: &mut [PullRequestPlan]) {
+    for plan in plans.iter_mut().filter(|plan| plan.is_ready()) {
+        if let Some(url) = &plan.url {
+            let _ = open::that(url);
+            plan.status = STATUS_OPENED.to_string();
+        }
+    }
+}
+
+/// Interactive review of ready plans before anything is pushed or opened. Repositories can be
+/// deselected, and their title and body edited with `edit`. Returns `false` if the user aborts.
+pub fn review_pull_requests<R, W, E>(
+    plans: &mut [PullRequestPlan],
+    input: &mut R,
+    output: &mut W,
+    mut edit: E,
+) -> Result<bool>
+where
+    R: BufRead,
+    W: Write,
+    E: FnMut(&str) -> Result<String>,
+{
+    let indices: Vec<usize> = (0..plans.len()).filter(|&i| plans[i].is_ready()).collect();
+    if indices.is_empty() {
+        return Ok(true);
+    }
+    let mut selected = vec![true; indices.len()];
+
+    writeln!(output, "\n{}", "Review pull requests".bright_white().bold())?;
+    for (number, &index) in indices.iter().enumerate() {
+        write_plan_details(output, number + 1, &plans[index])?;
+    }
+
+    loop {
+        writeln!(output)?;
+        for (number, &index) in indices.iter().enumerate() {
+            write_plan_summary(output, number + 1, &plans[index], selected[number])?;
+        }
+        write!(
+            output,
+            "\n{} ",
+            "[y] confirm, [q] abort, <n> toggle, e <n> edit, s <n> show:".bright_white()
+        )?;
+        output.flush()?;
+
+        let mut line = String::new();
+        // End of input (Ctrl-D or a closed pipe) aborts like `q`.
+        if input.read_line(&mut line)? == 0 {
+            for &index in &indices {
+                plans[index].skip(REASON_ABORTED);
+            }
+            return Ok(false);
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_lowercase();
+        let number = words
+            .next()
+            .or(Some(command.as_str()))
+            .and_then(|value| value.parse::<usize>().ok())
+            .filter(|&number| number >= 1 && number <= indices.len());
+
+        match (command.as_str(), number) {
+            ("y" | "yes", _) => break,
+            ("q" | "quit", _) => {
+                for &index in &indices {
+                    plans[index].skip(REASON_ABORTED);
+                }
+                return Ok(false);
+            }
+            ("e" | "edit", Some(number)) => {
+                let plan = &mut plans[indices[number - 1]];
+                let message = format!("{}\n\n{}", plan.title, plan.body.as_deref().unwrap_or(""));
+                let (title, body) = parse_edited_message(&edit(&message)?);
+                let title = if title.is_empty() {
+                    plan.title.clone()
+                } else {
+                    title
+                };
+                plan.set_content(&title, &body);
+            }
+            ("s" | "show", Some(number)) => {
+                write_plan_details(output, number, &plans[indices[number - 1]])?;
+            }
+            (_, Some(number)) => selected[number - 1] = !selected[number - 1],
+            _ => writeln!(output, "{}", "Unrecognised command".yellow())?,
+        }
+    }
+
+    for (number, &index) in indices.iter().enumerate() {
+        if !selected[number] {
+            plans[index].skip(REASON_DESELECTED);
+        }
+    }
+    Ok(true)
+}
+
+fn write_plan_summary<W: Write>(
+    output: &mut W,
+    number: usize,
+    plan: &PullRequestPlan,
+    selected: bool,
+) -> Result<()> {
+    let marker = if selected {
+        "[x]".green()
+    } else {
+        "[ ]".dimmed()
+    };
+    writeln!(
+        output,
+        "{} {}. {} {} {}",
+        marker,
+        number,
+        plan.repository.bright_white().bold(),
+        plan.title,
+        format!("({} files)", plan.file_count()).dimmed()
+    )?;
+    Ok(())
+}
+
+fn write_plan_details<W: Write>(
+    output: &mut W,
+    number: usize,
+    plan: &PullRequestPlan,
+) -> Result<()> {
+    writeln!(output, "\n{}", "─".repeat(80).dimmed())?;
+    writeln!(
+        output,
+        "{}. {} {} ← {} {}",
+        number,
+        plan.repository.bright_white().bold(),
+        plan.base.as_deref().unwrap_or_default().cyan(),
+        plan.head.as_deref().unwrap_or_default().cyan(),
+        format!("({} files)", plan.file_count()).dimmed()
+    )?;
+    if !plan.head_pushed {
+        writeln!(
+            output,
+            "{}",
+            "Branch has not been pushed and will be pushed on confirm".yellow()
+        )?;
+    }
+    writeln!(output, "{} {}", "Title:".bright_white(), plan.title)?;
+    writeln!(output, "{}", plan.body.as_deref().unwrap_or_default())?;
+    Ok(())
+}
+
+/// Splits an edited message into its first line (the title) and the rest (the body).
+fn parse_edited_message(message: &str) -> (String, String) {
+    let (title, body) = message.split_once('\n').unwrap_or((message, ""));
+    let body = body.strip_prefix('\n').unwrap_or(body);
+    (title.trim().to_string(), body.to_string())
+}
+
+/// Opens `content` in `$VISUAL`/`$EDITOR` (falling back to `vi`) and returns the saved text.
+/// The editor command is split like a shell would, without running one.
+pub fn edit_in_editor(content: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = shlex::split(&editor).unwrap_or_default().into_iter();
+    let Some(program) = words.next() else {
+        return Err(GmuxError::Validation(format!(
+            "Invalid editor command '{}'",
+            editor
+        )));
+    };
+
+    let mut file = tempfile::Builder::new()
+        .prefix("gmux-pr-")
+        .suffix(".md")
+        .tempfile()?;
+    file.write_all(content.as_bytes())?;
+    // Close the handle so editors that replace the file can do so; the file is still
+    // removed when `path` is dropped.
+    let path = file.into_temp_path();
+
+    let status = std::process::Command::new(&program)
+        .args(words)
+        .arg(&path)
+        .status()?;
+    let edited = std::fs::read_to_string(&path);
+
+    if !status.success() {
+        return Err(GmuxError::Validation(format!(
+            "Editor '{}' exited with {}",
+            editor, status
+        )));
+    }
+    Ok(edited?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_status_transitions() {
+        let mut plan = PullRequestPlan::new(Path::new("repo1"), "title");
+        assert!(plan.needs_push());
+
+        plan.head_pushed = true;
+        assert!(!plan.needs_push());
+
+        let mut plans = vec![plan];
+        mark_planned(&mut plans);
+        assert_eq!(plans[0].status, STATUS_PLANNED);
+        assert!(!plans[0].is_ready());
+
+        plans[0].skip("no changes found");
+        assert_eq!(plans[0].status, STATUS_SKIPPED);
+        assert_eq!(plans[0].reason.as_deref(), Some("no changes found"));
+    }
+
+    fn ready_plan(name: &str) -> PullRequestPlan {
+        let mut plan = PullRequestPlan::new(Path::new(name), "title");
+        plan.owner = Some("owner".to_string());
+        plan.repo = Some(name.to_string());
+        plan.base = Some("main".to_string());
+        plan.head = Some("feature".to_string());
+        plan.set_content("title", "body");
+        plan
+    }
+
+    #[test]
+    fn test_fork_compare_url() {
+        let mut plan = ready_plan("repo1");
+        assert!(plan
+            .url
+            .as_ref()
+            .unwrap()
+            .contains("/compare/main...feature?"));
+
+        plan.head_owner = Some("me".to_string());
+        plan.set_content("title", "body");
+        assert_eq!(plan.head_spec().as_deref(), Some("me:feature"));
+        assert!(plan
+            .url
+            .as_ref()
+            .unwrap()
+            .starts_with("https://github.com/owner/repo1/compare/main...me:feature?"));
+    }
+
+    #[test]
+    fn test_review_deselect_and_edit() -> Result<()> {
+        let mut plans = vec![ready_plan("repo1"), ready_plan("repo2")];
+        let mut input = "2\ne 1\ny\n".as_bytes();
+        let mut output = Vec::new();
+
+        let confirmed = review_pull_requests(&mut plans, &mut input, &mut output, |message| {
+            assert_eq!(message, "title\n\nbody");
+            Ok("New title\n\nNew body\n".to_string())
+        })?;
+
+        assert!(confirmed);
+        assert!(plans[0].is_ready());
+        assert_eq!(plans[0].title, "New title");
+        assert_eq!(plans[0].body.as_deref(), Some("New body\n"));
+        assert!(plans[0].url.as_ref().unwrap().contains("title=New%20title"));
+        assert_eq!(plans[1].status, STATUS_SKIPPED);
+        assert_eq!(plans[1].reason.as_deref(), Some(REASON_DESELECTED));
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_abort() -> Result<()> {
+        let mut plans = vec![ready_plan("repo1")];
+        let mut input = "q\n".as_bytes();
+        let mut output = Vec::new();
+
+        let confirmed =
+            review_pull_requests(&mut plans, &mut input, &mut output, |_| unreachable!())?;
+
+        assert!(!confirmed);
+        assert_eq!(plans[0].reason.as_deref(), Some(REASON_ABORTED));
+        Ok(())
+    }
+
+    #[test]
+    fn test_review_end_of_input_aborts() -> Result<()> {
+        let mut plans = vec![ready_plan("repo1"), ready_plan("repo2")];
+        let mut input = "2\n".as_bytes();
+        let mut output = Vec::new();
+
+        let confirmed =
+            review_pull_requests(&mut plans, &mut input, &mut output, |_| unreachable!())?;
+
+        assert!(!confirmed);
+        assert!(plans.iter().all(|plan| !plan.is_ready()));
+        assert!(plans
+            .iter()
+            .all(|plan| plan.reason.as_deref() == Some(REASON_ABORTED)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_edited_message() {
+        assert_eq!(
+            parse_edited_message("Title\n\nBody\nmore"),
+            ("Title".to_string(), "Body\nmore".to_string())
+        );
+        assert_eq!(
+            parse_edited_message("Title only"),
+            ("Title only".to_string(), String::new())
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::diagnostics::{with_hint, DETACHED_HEAD};
+use crate::git::{
+    count_ahead_behind, fetch_remote_branch, get_default_branch, get_repository_metadata,
+    local_branch_exists,
+};
+use crate::output::PushResult;
+use crate::utils::repository_name;
+use clap::Args;
+use std::path::Path;
+use tokio::process::Command;
+
+pub const STATUS_PUSHED: &str = "pushed";
+pub const STATUS_UP_TO_DATE: &str = "up-to-date";
+pub const STATUS_REJECTED: &str = "rejected";
+pub const STATUS_SKIPPED: &str = "skipped";
+pub const STATUS_FAILED: &str = "failed";
+
+pub const REASON_DEFAULT_BRANCH: &str = "default branch; pass --allow-default-branch to push it";
+const REASON_DIVERGED: &str =
+    "branch has diverged from the remote branch; pull first or pass --force-with-lease";
+
+#[derive(Args, Clone, Debug)]
+pub struct PushOptions {
+    /// Branch to push (defaults to each repository's current branch)
+    pub branch: Option<String>,
+    /// Remote to push to
+    #[arg(long, default_value = "origin")]
+    pub remote: String,
+    /// Set the pushed branch as the upstream of the local branch
+    #[arg(short = 'u', long)]
+    pub set_upstream: bool,
+    /// Overwrite the remote branch if it still points where the local remote-tracking ref does
+    #[arg(long)]
+    pub force_with_lease: bool,
+    /// Allow pushing each repository's default branch
+    #[arg(long)]
+    pub allow_default_branch: bool,
+}
+
+impl PushResult {
+    fn new(path: &Path, branch: Option<&str>, remote: &str) -> Self {
+        Self {
+            repository: repository_name(path),
+            path: path.display().to_string(),
+            branch: branch.map(str::to_string),
+            remote: remote.to_string(),
+            status: STATUS_SKIPPED.to_string(),
+            reason: None,
+            ahead: None,
+            behind: None,
+        }
+    }
+
+    fn finish(mut self, status: &str, reason: Option<String>) -> Self {
+        self.status = status.to_string();
+        self.reason = reason;
+        self
+    }
+}
+
+/// Pushes one repository's branch. Returns `None` for directories that are not git
+/// repositories.
+pub async fn push_repository(path: &Path, options: &PushOptions) -> Option<PushResult> {
+    let metadata = match get_repository_metadata(path).await {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return None,
+        Err(error) => {
+            return Some(
+                PushResult::new(path, None, &options.remote)
+                    .finish(STATUS_FAILED, Some(error.format_error())),
+            )
+        }
+    };
+    if options.branch.is_none() && metadata.detached {
+        return Some(
+            PushResult::new(path, None, &options.remote)
+                .finish(STATUS_FAILED, Some(with_hint(DETACHED_HEAD))),
+        );
+    }
+    let branch = options
+        .branch
+        .clone()
+        .unwrap_or_else(|| metadata.current_branch.clone());
+    let mut result = PushResult::new(path, Some(&branch), &options.remote);
+
+    match local_branch_exists(path, &branch).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Some(result.finish(STATUS_SKIPPED, Some(format!("{} not found", branch))))
+        }
+        Err(error) => return Some(result.finish(STATUS_FAILED, Some(error.format_error()))),
+    }
+
+    if !options.allow_default_branch {
+        match get_default_branch(path, &options.remote).await {
+            Ok(Some(default_branch)) if default_branch == branch => {
+                return Some(result.finish(STATUS_SKIPPED, Some(REASON_DEFAULT_BRANCH.to_string())))
+            }
+            Ok(_) => {}
+            Err(error) => return Some(result.finish(STATUS_FAILED, Some(error.format_error()))),
+        }
+    }
+
+    // Compared against the remote branch as it is now, since the remote-tracking ref may be
+    // stale or track a different remote.
+    let remote_commit = match fetch_remote_branch(path, &options.remote, &branch).await {
+        Ok(remote_commit) => remote_commit,
+        Err(error) => return Some(result.finish(STATUS_FAILED, Some(error.format_error()))),
+    };
+    if let Some(remote_commit) = remote_commit {
+        match count_ahead_behind(path, &remote_commit, &branch).await {
+            Ok((ahead, behind)) => {
+                result.ahead = Some(ahead);
+                result.behind = Some(behind);
+                if ahead == 0 {
+                    // Nothing to push, but a branch that is behind is not in sync either.
+                    let reason = (behind > 0).then(|| {
+                        format!(
+                            "local branch is {} commits behind {}",
+                            behind, options.remote
+                        )
+                    });
+                    return Some(result.finish(STATUS_UP_TO_DATE, reason));
+                }
+                if behind > 0 && !options.force_with_lease {
+                    return Some(result.finish(STATUS_REJECTED, Some(REASON_DIVERGED.to_string())));
+                }
+            }
+            Err(error) => return Some(result.finish(STATUS_FAILED, Some(error.format_error()))),
+        }
+    }
+
+    Some(match run_push(path, options, &branch).await {
+        Ok(status) => result.finish(status, None),
+        Err(error) => {
+            let status = if error.contains("[rejected]") || error.contains("[remote rejected]") {
+                STATUS_REJECTED
+            } else {
+                STATUS_FAILED
+            };
+            result.finish(status, Some(with_hint(error)))
+        }
+    })
+}
+
+/// Runs `git push`, never with a bare `--force`, reading the outcome from its porcelain
+/// output.
+async fn run_push(
+    path: &Path,
+    options: &PushOptions,
+    branch: &str,
+) -> std::result::Result<&'static str, String> {
+    let mut args = vec!["push", "--porcelain"];
+    if options.set_upstream {
+        args.push("--set-upstream");
+    }
+    if options.force_with_lease {
+        args.push("--force-with-lease");
+    }
+    args.push(&options.remote);
+    args.push(branch);
+
+    let output = Command::new("git")
+        .args(&args)
+        .current_dir(path)
+        .output()
+        .await
+        .map_err(|error| error.to_string())?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{}\n{}", stdout.trim(), stderr.trim())
+            .trim()
+            .to_string());
+    }
+
+    // In porcelain output, `=` flags a ref that was already up to date.
+    let up_to_date = stdout
+        .lines()
+        .filter(|line| line.contains('\t'))
+        .all(|line| line.starts_with('='));
+    Ok(if up_to_date {
+        STATUS_UP_TO_DATE
+    } else {
+        STATUS_PUSHED
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{git, setup_cloned_repo};
+
+    fn options() -> PushOptions {
+        PushOptions {
+            branch: None,
+            remote: "origin".to_string(),
+            set_upstream: true,
+            force_with_lease: false,
+            allow_default_branch: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_push_repository() {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(
+            &repo_path,
+            &["commit", "--allow-empty", "-m", "Work on main"],
+        )
+        .await;
+
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_SKIPPED);
+        assert_eq!(result.reason.as_deref(), Some(REASON_DEFAULT_BRANCH));
+
+        git(&repo_path, &["switch", "-c", "feature"]).await;
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_PUSHED);
+
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_UP_TO_DATE);
+        assert_eq!((result.ahead, result.behind), (Some(0), Some(0)));
+    }
+
+    #[tokio::test]
+    async fn test_push_compares_against_pushed_remote() {
+        let (temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["switch", "-c", "feature"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "Work"]).await;
+        git(&repo_path, &["push", "-u", "origin", "feature"]).await;
+        git(temp_dir.path(), &["clone", "--bare", "seed", "mirror.git"]).await;
+        git(&repo_path, &["remote", "add", "mirror", "../mirror.git"]).await;
+
+        // The upstream is up to date, but the branch is missing from the mirror.
+        let mut mirror = options();
+        mirror.remote = "mirror".to_string();
+        let result = push_repository(&repo_path, &mirror).await.unwrap();
+        assert_eq!(result.status, STATUS_PUSHED);
+        assert_eq!((result.ahead, result.behind), (None, None));
+
+        // Another clone advances the branch; the stale remote-tracking ref is not trusted.
+        git(temp_dir.path(), &["clone", "origin.git", "other"]).await;
+        let other = temp_dir.path().join("other");
+        git(&other, &["switch", "feature"]).await;
+        git(&other, &["commit", "--allow-empty", "-m", "Other"]).await;
+        git(&other, &["push", "origin", "feature"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "More work"]).await;
+
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_REJECTED);
+        assert_eq!((result.ahead, result.behind), (Some(1), Some(1)));
+    }
+
+    #[tokio::test]
+    async fn test_push_default_branch_without_remote_head() {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["remote", "set-head", "origin", "--delete"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "Work"]).await;
+
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_SKIPPED);
+        assert_eq!(result.reason.as_deref(), Some(REASON_DEFAULT_BRANCH));
+    }
+
+    #[tokio::test]
+    async fn test_push_diverged_branch() {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["switch", "-c", "feature"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "One"]).await;
+        git(&repo_path, &["push", "-u", "origin", "feature"]).await;
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "Two"]).await;
+
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_REJECTED);
+
+        let mut options = options();
+        options.force_with_lease = true;
+        let result = push_repository(&repo_path, &options).await.unwrap();
+        assert_eq!(result.status, STATUS_PUSHED);
+    }
+
+    #[tokio::test]
+    async fn test_push_behind_branch() {
+        let (_temp_dir, repo_path) = setup_cloned_repo().await;
+        git(&repo_path, &["switch", "-c", "feature"]).await;
+        git(&repo_path, &["commit", "--allow-empty", "-m", "One"]).await;
+        git(&repo_path, &["push", "-u", "origin", "feature"]).await;
+        git(&repo_path, &["reset", "--hard", "HEAD~1"]).await;
+
+        let result = push_repository(&repo_path, &options()).await.unwrap();
+        assert_eq!(result.status, STATUS_UP_TO_DATE);
+        assert_eq!((result.ahead, result.behind), (Some(0), Some(1)));
+        assert_eq!(
+            result.reason.as_deref(),
+            Some("local branch is 1 commits behind origin")
+        );
+    }
+}
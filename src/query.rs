@@ -0,0 +1,262 @@
+use crate::error::{GmuxError, Result};
+use clap::Args;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+#[derive(Args, Clone, Debug, Default)]
+pub struct QueryOptions {
+    /// Only output these fields, e.g. `repository,current_branch,last_commit.subject`
+    #[arg(long, value_delimiter = ',')]
+    pub fields: Vec<String>,
+    /// Only output records matching a predicate such as `dirty == true` or `exit_code != 0`;
+    /// repeat to require several
+    #[arg(long = "where", value_name = "PREDICATE")]
+    pub predicates: Vec<Predicate>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches,
+}
+
+/// `<field> <operator> <value>`, where the field may be a dotted path into nested objects.
+#[derive(Clone, Debug)]
+pub struct Predicate {
+    field: String,
+    operator: Operator,
+    value: String,
+    regex: Option<Regex>,
+}
+
+/// Two-character operators come first so `>=` is not read as `>`.
+const OPERATORS: &[(&str, Operator)] = &[
+    ("==", Operator::Eq),
+    ("!=", Operator::Ne),
+    ("<=", Operator::Le),
+    (">=", Operator::Ge),
+    ("=~", Operator::Matches),
+    ("<", Operator::Lt),
+    (">", Operator::Gt),
+];
+
+impl FromStr for Predicate {
+    type Err = String;
+
+    fn from_str(predicate: &str) -> std::result::Result<Self, Self::Err> {
+        let (index, symbol, operator) = OPERATORS
+            .iter()
+            .filter_map(|(symbol, operator)| {
+                predicate
+                    .find(symbol)
+                    .map(|index| (index, *symbol, *operator))
+            })
+            .min_by_key(|(index, symbol, _)| (*index, std::cmp::Reverse(symbol.len())))
+            .ok_or_else(|| {
+                "expected `<field> <operator> <value>` with one of ==, !=, <, <=, >, >=, =~"
+                    .to_string()
+            })?;
+
+        let field = predicate[..index].trim().to_string();
+        if field.is_empty() {
+            return Err("the predicate has no field".to_string());
+        }
+        let value = unquote(predicate[index + symbol.len()..].trim()).to_string();
+        let regex = match operator {
+            Operator::Matches => {
+                Some(Regex::new(&value).map_err(|error| format!("invalid regex: {}", error))?)
+            }
+            _ => None,
+        };
+        Ok(Self {
+            field,
+            operator,
+            value,
+            regex,
+        })
+    }
+}
+
+impl Predicate {
+    /// Missing fields and `null` compare as an empty string, so they only equal `""` or `null`.
+    fn matches(&self, record: &Value) -> bool {
+        let actual = lookup(record, &self.field)
+            .map(scalar_text)
+            .unwrap_or_default();
+        let expected = if self.value == "null" {
+            ""
+        } else {
+            self.value.as_str()
+        };
+
+        match self.operator {
+            Operator::Eq => compare(&actual, expected) == Ordering::Equal,
+            Operator::Ne => compare(&actual, expected) != Ordering::Equal,
+            Operator::Lt => ordered(&actual, expected, Ordering::is_lt),
+            Operator::Le => ordered(&actual, expected, Ordering::is_le),
+            Operator::Gt => ordered(&actual, expected, Ordering::is_gt),
+            Operator::Ge => ordered(&actual, expected, Ordering::is_ge),
+            Operator::Matches => self
+                .regex
+                .as_ref()
+                .is_some_and(|regex| regex.is_match(&actual)),
+        }
+    }
+}
+
+impl QueryOptions {
+    /// Whether a record passes every `--where` predicate.
+    pub fn matches<T: Serialize>(&self, record: &T) -> Result<bool> {
+        if self.predicates.is_empty() {
+            return Ok(true);
+        }
+        let record = serde_json::to_value(record)?;
+        Ok(self
+            .predicates
+            .iter()
+            .all(|predicate| predicate.matches(&record)))
+    }
+
+    /// Keeps only the `--fields` of a record, in the order given. Fields the record does not
+    /// have are left out.
+    pub fn project(&self, record: &Value) -> Value {
+        if self.fields.is_empty() || !record.is_object() {
+            return record.clone();
+        }
+        let mut projected = Map::new();
+        for field in &self.fields {
+            if let Some(value) = lookup(record, field) {
+                projected.insert(field.clone(), value.clone());
+            }
+        }
+        Value::Object(projected)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.fields.iter().any(|field| field.trim().is_empty()) {
+            return Err(GmuxError::Validation(
+                "--fields contains an empty field name".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn lookup<'a>(record: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(record, |value, key| value.as_object()?.get(key))
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value
+            .strip_prefix(quote)
+            .and_then(|value| value.strip_suffix(quote))
+        {
+            return inner;
+        }
+    }
+    value
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(scalar_text).collect::<Vec<_>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+/// Compares numerically when both sides are numbers, otherwise as strings.
+fn compare(actual: &str, expected: &str) -> Ordering {
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(actual), Ok(expected)) => actual.partial_cmp(&expected).unwrap_or(Ordering::Equal),
+        _ => actual.cmp(expected),
+    }
+}
+
+/// Ordering predicates never match a missing field.
+fn ordered(actual: &str, expected: &str, accept: fn(Ordering) -> bool) -> bool {
+    !actual.is_empty() && accept(compare(actual, expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn query(fields: &[&str], predicates: &[&str]) -> QueryOptions {
+        QueryOptions {
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            predicates: predicates
+                .iter()
+                .map(|predicate| predicate.parse().unwrap())
+                .collect(),
+        }
+    }
+
+    fn record() -> Value {
+        json!({
+            "repository": "api",
+            "current_branch": "feature/login",
+            "dirty": true,
+            "ahead": 3,
+            "behind": null,
+            "last_commit": {"subject": "Fix login"}
+        })
+    }
+
+    #[test]
+    fn test_parse_predicate() {
+        let predicate: Predicate = "ahead>=2".parse().unwrap();
+        assert_eq!(predicate.field, "ahead");
+        assert_eq!(predicate.operator, Operator::Ge);
+        assert_eq!(predicate.value, "2");
+
+        let predicate: Predicate = "current_branch == 'a == b'".parse().unwrap();
+        assert_eq!(predicate.operator, Operator::Eq);
+        assert_eq!(predicate.value, "a == b");
+
+        assert!("dirty".parse::<Predicate>().is_err());
+        assert!("== true".parse::<Predicate>().is_err());
+        assert!("repository =~ (".parse::<Predicate>().is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let record = record();
+        let matches = |predicate: &str| query(&[], &[predicate]).matches(&record).unwrap();
+        assert!(matches("dirty == true"));
+        assert!(!matches("dirty != true"));
+        assert!(matches("ahead > 2"));
+        assert!(matches("ahead == 3.0"));
+        assert!(!matches("behind > 0"));
+        assert!(matches("behind == null"));
+        assert!(matches("missing == null"));
+        assert!(matches("current_branch =~ ^feature/"));
+        assert!(matches("last_commit.subject == \"Fix login\""));
+        assert!(query(&[], &["dirty == true", "ahead < 2"])
+            .matches(&record)
+            .is_ok_and(|matched| !matched));
+    }
+
+    #[test]
+    fn test_project() {
+        let projected =
+            query(&["last_commit.subject", "repository", "missing"], &[]).project(&record());
+        assert_eq!(
+            serde_json::to_string(&projected).unwrap(),
+            r#"{"last_commit.subject":"Fix login","repository":"api"}"#
+        );
+        assert_eq!(query(&[], &[]).project(&record()), record());
+    }
+}
@@ -0,0 +1,210 @@
+use crate::error::{GmuxError, Result};
+use crate::git::{git_stdout, is_git_directory};
+use crate::output::{ReplaceFileResult, ReplaceRepositoryResult, RepositoryErrorResult};
+use crate::utils::repository_name;
+use clap::Args;
+use regex::{NoExpand, Regex};
+use similar::TextDiff;
+use std::fs;
+use std::path::Path;
+
+/// Bytes inspected when deciding whether a file is binary, matching git's heuristic.
+const BINARY_CHECK_BYTES: usize = 8000;
+
+#[derive(Args, Clone, Debug)]
+pub struct ReplaceOptions {
+    /// Regular expression matched against whole file contents; use (?m) for line anchors
+    pub pattern: String,
+    /// Replacement text; `$1` and `${name}` refer to capture groups
+    pub replacement: String,
+    /// Limit the replacement to these paths
+    #[arg(last = true)]
+    pub pathspec: Vec<String>,
+    /// Treat the pattern and replacement as literal strings
+    #[arg(short = 'F', long)]
+    pub fixed_strings: bool,
+    /// Show a unified diff of the changes without writing them
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+impl ReplaceOptions {
+    pub fn regex(&self) -> Result<Regex> {
+        let pattern = if self.fixed_strings {
+            regex::escape(&self.pattern)
+        } else {
+            self.pattern.clone()
+        };
+        Regex::new(&pattern)
+            .map_err(|error| GmuxError::Validation(format!("Invalid regex pattern: {}", error)))
+    }
+
+    fn replace<'a>(&self, regex: &Regex, content: &'a str) -> std::borrow::Cow<'a, str> {
+        if self.fixed_strings {
+            regex.replace_all(content, NoExpand(&self.replacement))
+        } else {
+            regex.replace_all(content, self.replacement.as_str())
+        }
+    }
+}
+
+/// Applies the replacement to every tracked text file in one repository, or only renders diffs
+/// in dry-run mode. Changes are left in the working tree. Returns `None` for directories that
+/// are not git repositories.
+pub async fn replace_in_repository(
+    path: &Path,
+    regex: &Regex,
+    options: &ReplaceOptions,
+) -> Option<std::result::Result<ReplaceRepositoryResult, RepositoryErrorResult>> {
+    if !is_git_directory(path).await {
+        return None;
+    }
+
+    Some(
+        replace_tracked_files(path, regex, options)
+            .await
+            .map(|files| ReplaceRepositoryResult {
+                repository: repository_name(path),
+                path: path.display().to_string(),
+                replacements: files.iter().map(|file| file.replacements).sum(),
+                files,
+            })
+            .map_err(|error| RepositoryErrorResult::new(path, error.format_error())),
+    )
+}
+
+async fn replace_tracked_files(
+    path: &Path,
+    regex: &Regex,
+    options: &ReplaceOptions,
+) -> Result<Vec<ReplaceFileResult>> {
+    let mut args = vec!["ls-files", "-z", "--"];
+    args.extend(options.pathspec.iter().map(String::as_str));
+    let tracked = git_stdout(path, &args).await?;
+
+    let mut files = Vec::new();
+    for file in tracked.split('\0').filter(|file| !file.is_empty()) {
+        let file_path = path.join(file);
+        // Skip symlinks, submodules and files deleted from the working tree.
+        if !fs::symlink_metadata(&file_path).is_ok_and(|metadata| metadata.is_file()) {
+            continue;
+        }
+        let bytes = fs::read(&file_path)?;
+        if is_binary(&bytes) {
+            continue;
+        }
+        let Ok(content) = String::from_utf8(bytes) else {
+            continue;
+        };
+
+        let replacements = regex.find_iter(&content).count();
+        if replacements == 0 {
+            continue;
+        }
+        let replaced = options.replace(regex, &content);
+        if replaced == content {
+            continue;
+        }
+
+        let diff = if options.dry_run {
+            Some(
+                TextDiff::from_lines(content.as_str(), replaced.as_ref())
+                    .unified_diff()
+                    .header(&format!("a/{}", file), &format!("b/{}", file))
+                    .to_string(),
+            )
+        } else {
+            fs::write(&file_path, replaced.as_bytes())?;
+            None
+        };
+        files.push(ReplaceFileResult {
+            path: file.to_string(),
+            replacements,
+            diff,
+        });
+    }
+
+    Ok(files)
+}
+
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes[..bytes.len().min(BINARY_CHECK_BYTES)].contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::TempDir;
+
+    fn options(pattern: &str, replacement: &str) -> ReplaceOptions {
+        ReplaceOptions {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            pathspec: Vec::new(),
+            fixed_strings: false,
+            dry_run: false,
+        }
+    }
+
+    async fn setup_repo() -> TempDir {
+        let temp_dir = TempDir::new().unwrap();
+        let repo_path = temp_dir.path();
+        std::fs::write(repo_path.join("a.txt"), "version = \"1.2\"\nname = \"a\"\n").unwrap();
+        std::fs::write(repo_path.join("binary.bin"), b"version = \"1.2\"\0").unwrap();
+        std::fs::write(repo_path.join("untracked.txt"), "version = \"1.2\"\n").unwrap();
+        git(repo_path, &["init", "-b", "main"]).await;
+        git(repo_path, &["add", "a.txt", "binary.bin"]).await;
+        temp_dir
+    }
+
+    #[tokio::test]
+    async fn test_replace_with_capture_groups() -> Result<()> {
+        let temp_dir = setup_repo().await;
+        let repo_path = temp_dir.path();
+        let options = options(r#"version = "(\d+)\.(\d+)""#, r#"version = "$1.${2}.0""#);
+
+        let result = replace_in_repository(repo_path, &options.regex()?, &options)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.replacements, 1);
+        assert_eq!(result.files[0].path, "a.txt");
+        assert!(result.files[0].diff.is_none());
+        assert_eq!(
+            fs::read_to_string(repo_path.join("a.txt"))?,
+            "version = \"1.2.0\"\nname = \"a\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(repo_path.join("untracked.txt"))?,
+            "version = \"1.2\"\n"
+        );
+        assert_eq!(
+            fs::read(repo_path.join("binary.bin"))?,
+            b"version = \"1.2\"\0"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_replace_dry_run() -> Result<()> {
+        let temp_dir = setup_repo().await;
+        let repo_path = temp_dir.path();
+        let mut options = options("1.2", "$1");
+        options.fixed_strings = true;
+        options.dry_run = true;
+
+        let result = replace_in_repository(repo_path, &options.regex()?, &options)
+            .await
+            .unwrap()
+            .unwrap();
+        let diff = result.files[0].diff.as_deref().unwrap();
+        assert!(diff.starts_with("--- a/a.txt\n+++ b/a.txt\n@@"));
+        assert!(diff.contains("-version = \"1.2\"\n+version = \"$1\"\n"));
+        assert_eq!(
+            fs::read_to_string(repo_path.join("a.txt"))?,
+            "version = \"1.2\"\nname = \"a\"\n"
+        );
+        Ok(())
+    }
+}
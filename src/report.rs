@@ -0,0 +1,235 @@
+use crate::error::{GmuxError, Result};
+use crate::output::CommandBatchResult;
+use clap::Args;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Args, Clone, Debug)]
+pub struct ReportOptions {
+    /// Also write a report file, e.g. `junit=results.xml`
+    #[arg(long, value_name = "FORMAT=PATH")]
+    pub report: Option<Report>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReportFormat {
+    Junit,
+}
+
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+impl FromStr for Report {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (format, path) = value
+            .split_once('=')
+            .ok_or_else(|| "expected FORMAT=PATH, e.g. junit=results.xml".to_string())?;
+        let format = match format {
+            "junit" => ReportFormat::Junit,
+            _ => {
+                return Err(format!(
+                    "unknown report format `{}`; expected junit",
+                    format
+                ))
+            }
+        };
+        if path.is_empty() {
+            return Err("the report path is empty".to_string());
+        }
+        Ok(Self {
+            format,
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+impl ReportOptions {
+    pub fn write(&self, batch: &CommandBatchResult) -> Result<()> {
+        let Some(report) = &self.report else {
+            return Ok(());
+        };
+        let content = match report.format {
+            ReportFormat::Junit => render_junit(batch),
+        };
+        std::fs::write(&report.path, content).map_err(|error| {
+            GmuxError::Validation(format!(
+                "cannot write report {}: {}",
+                report.path.display(),
+                error
+            ))
+        })
+    }
+}
+
+/// One test suite for the batch, with a test case per repository. Non-zero exit codes are
+/// failures, repositories where the command could not run are errors.
+pub fn render_junit(batch: &CommandBatchResult) -> String {
+    let failures = batch
+        .results
+        .iter()
+        .filter(|result| result.exit_code != 0)
+        .count();
+    let total_ms: u128 = batch.results.iter().map(|result| result.duration_ms).sum();
+    let tests = batch.results.len() + batch.skipped.len() + batch.errors.len();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"gmux\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">",
+        tests,
+        failures,
+        batch.errors.len(),
+        batch.skipped.len(),
+        seconds(total_ms)
+    );
+    let _ = writeln!(
+        xml,
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">",
+        escape_xml(&batch.command),
+        tests,
+        failures,
+        batch.errors.len(),
+        batch.skipped.len(),
+        seconds(total_ms)
+    );
+
+    for result in &batch.results {
+        let _ = writeln!(
+            xml,
+            "    <testcase classname=\"gmux\" name=\"{}\" file=\"{}\" time=\"{}\">",
+            escape_xml(&result.repository),
+            escape_xml(&result.path),
+            seconds(result.duration_ms)
+        );
+        if result.exit_code != 0 {
+            let _ = writeln!(
+                xml,
+                "      <failure message=\"exit code {code}\" type=\"exit-code\">{} exited with code {code}</failure>",
+                escape_xml(&result.command),
+                code = result.exit_code
+            );
+        }
+        let _ = writeln!(
+            xml,
+            "      <system-out>{}</system-out>",
+            escape_xml(&result.stdout)
+        );
+        let _ = writeln!(
+            xml,
+            "      <system-err>{}</system-err>",
+            escape_xml(&result.stderr)
+        );
+        xml.push_str("    </testcase>\n");
+    }
+    for skipped in &batch.skipped {
+        let _ = writeln!(
+            xml,
+            "    <testcase classname=\"gmux\" name=\"{}\" file=\"{}\" time=\"0\">\n      <skipped message=\"{}\"/>\n    </testcase>",
+            escape_xml(&skipped.repository),
+            escape_xml(&skipped.path),
+            escape_xml(&skipped.reason)
+        );
+    }
+    for error in &batch.errors {
+        let _ = writeln!(
+            xml,
+            "    <testcase classname=\"gmux\" name=\"{}\" file=\"{}\" time=\"0\">\n      <error message=\"{}\"/>\n    </testcase>",
+            escape_xml(&error.repository),
+            escape_xml(&error.path),
+            escape_xml(&error.error)
+        );
+    }
+
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn seconds(duration_ms: u128) -> String {
+    format!("{:.3}", duration_ms as f64 / 1000.0)
+}
+
+/// Escapes markup and drops characters XML 1.0 cannot represent, such as the ANSI escapes in
+/// colored command output.
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c < ' ' || c == '\u{fffe}' || c == '\u{ffff}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{RepositoryCommandResult, RepositoryErrorResult, RepositorySkippedResult};
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_report() {
+        let report: Report = "junit=out/results.xml".parse().unwrap();
+        assert_eq!(report.format, ReportFormat::Junit);
+        assert_eq!(report.path, PathBuf::from("out/results.xml"));
+        assert!("junit".parse::<Report>().is_err());
+        assert!("html=report.html".parse::<Report>().is_err());
+        assert!("junit=".parse::<Report>().is_err());
+    }
+
+    #[test]
+    fn test_render_junit() {
+        let result = |repository: &str, exit_code: i32, stdout: &str| RepositoryCommandResult {
+            repository: repository.to_string(),
+            path: format!("/ws/{}", repository),
+            command: "cargo test".to_string(),
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            duration_ms: 1500,
+        };
+        let batch = CommandBatchResult {
+            command: "cargo test".to_string(),
+            succeeded: 1,
+            failed: 2,
+            results: vec![
+                result("api", 0, "ok"),
+                result("web", 101, "\u{1b}[31mfailed\u{1b}[0m <a & b>"),
+            ],
+            skipped: vec![RepositorySkippedResult::new(
+                Path::new("/ws/docs"),
+                "not a git repository",
+            )],
+            errors: vec![RepositoryErrorResult::new(
+                Path::new("/ws/cli"),
+                "sh: not found",
+            )],
+        };
+
+        let xml = render_junit(&batch);
+        assert!(xml.contains(
+            "<testsuite name=\"cargo test\" tests=\"4\" failures=\"1\" errors=\"1\" skipped=\"1\" time=\"3.000\">"
+        ));
+        assert!(xml.contains(
+            "<testcase classname=\"gmux\" name=\"api\" file=\"/ws/api\" time=\"1.500\">\n      <system-out>ok</system-out>"
+        ));
+        assert!(xml.contains(
+            "<failure message=\"exit code 101\" type=\"exit-code\">cargo test exited with code 101</failure>"
+        ));
+        assert!(xml.contains("<system-out>[31mfailed[0m &lt;a &amp; b&gt;</system-out>"));
+        assert!(xml.contains("<skipped message=\"not a git repository\"/>"));
+        assert!(xml.contains("<error message=\"sh: not found\"/>"));
+    }
+}
@@ -0,0 +1,128 @@
+use crate::output::{
+    ApplyBatchResult, BranchBatchResult, CloneBatchResult, CommandBatchResult, CommitBatchResult,
+    DoctorResult, ErrorResult, GrepBatchResult, InitResult, InspectWorkspaceResult,
+    PullRequestBatchResult, PushBatchResult, ReplaceBatchResult, RepositoryListResult, SetupResult,
+    SnapshotBatchResult, SnapshotListResult, Versioned, SCHEMA_VERSION,
+};
+use clap::ValueEnum;
+use schemars::gen::SchemaSettings;
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+
+/// Commands with JSON output, named after their subcommand.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum SchemaCommand {
+    Init,
+    Setup,
+    Ls,
+    Clone,
+    Inspect,
+    Cmd,
+    Git,
+    Pr,
+    Branch,
+    Commit,
+    Push,
+    /// `snapshot save` and `snapshot restore`
+    Snapshot,
+    /// `snapshot list`
+    SnapshotList,
+    Grep,
+    Replace,
+    Apply,
+    Doctor,
+    /// The error printed in place of any command's result
+    Error,
+}
+
+impl SchemaCommand {
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// The JSON Schema of a command's top-level JSON result, including `schema_version`.
+pub fn output_schema(command: SchemaCommand) -> RootSchema {
+    let mut schema = match command {
+        SchemaCommand::Init => root_schema::<InitResult>(),
+        SchemaCommand::Setup => root_schema::<SetupResult>(),
+        SchemaCommand::Ls => root_schema::<RepositoryListResult>(),
+        SchemaCommand::Clone => root_schema::<CloneBatchResult>(),
+        SchemaCommand::Inspect => root_schema::<InspectWorkspaceResult>(),
+        SchemaCommand::Cmd | SchemaCommand::Git => root_schema::<CommandBatchResult>(),
+        SchemaCommand::Pr => root_schema::<PullRequestBatchResult>(),
+        SchemaCommand::Branch => root_schema::<BranchBatchResult>(),
+        SchemaCommand::Commit => root_schema::<CommitBatchResult>(),
+        SchemaCommand::Push => root_schema::<PushBatchResult>(),
+        SchemaCommand::Snapshot => root_schema::<SnapshotBatchResult>(),
+        SchemaCommand::SnapshotList => root_schema::<SnapshotListResult>(),
+        SchemaCommand::Grep => root_schema::<GrepBatchResult>(),
+        SchemaCommand::Replace => root_schema::<ReplaceBatchResult>(),
+        SchemaCommand::Apply => root_schema::<ApplyBatchResult>(),
+        SchemaCommand::Doctor => root_schema::<DoctorResult>(),
+        SchemaCommand::Error => root_schema::<ErrorResult>(),
+    };
+    let metadata = schema.schema.metadata();
+    metadata.title = Some(format!("gmux {} output", command.name()));
+    metadata.description = Some(format!("schema_version {}", SCHEMA_VERSION));
+    schema
+}
+
+fn root_schema<T: JsonSchema>() -> RootSchema {
+    SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<Versioned<T>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    /// Schemas are recorded per version in `schemas/v<N>/`. A recorded schema is never
+    /// overwritten, so changing an output type requires bumping `SCHEMA_VERSION`; run the tests
+    /// with `GMUX_UPDATE_SCHEMAS=1` to record the new version.
+    #[test]
+    fn test_schemas_match_recorded_version() {
+        let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("schemas")
+            .join(format!("v{}", SCHEMA_VERSION));
+        let update = std::env::var_os("GMUX_UPDATE_SCHEMAS").is_some();
+
+        for command in SchemaCommand::value_variants() {
+            let schema = serde_json::to_string_pretty(&output_schema(*command)).unwrap() + "\n";
+            let path = directory.join(format!("{}.json", command.name()));
+            match std::fs::read_to_string(&path) {
+                Ok(recorded) => assert!(
+                    recorded == schema,
+                    "the `{}` output schema differs from {}; bump SCHEMA_VERSION in src/output.rs and record it with GMUX_UPDATE_SCHEMAS=1 cargo test",
+                    command.name(),
+                    path.display()
+                ),
+                Err(_) if update => {
+                    std::fs::create_dir_all(&directory).unwrap();
+                    std::fs::write(&path, schema).unwrap();
+                }
+                Err(_) => panic!(
+                    "no recorded `{}` schema at {}; record it with GMUX_UPDATE_SCHEMAS=1 cargo test",
+                    command.name(),
+                    path.display()
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_schema_includes_version() {
+        let schema = serde_json::to_value(output_schema(SchemaCommand::Inspect)).unwrap();
+        assert_eq!(schema["title"], "gmux inspect output");
+        assert_eq!(schema["properties"]["schema_version"]["type"], "integer");
+        assert!(schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&"schema_version".into()));
+        assert!(schema["properties"]["repositories"].is_object());
+    }
+}
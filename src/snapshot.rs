@@ -0,0 +1,400 @@
+use crate::config::get_snapshot_dir;
+use crate::error::{ConfigErrorKind, GmuxError, Result};
+use crate::git::{
+    get_last_commit, get_repository_metadata, git_stdout, has_uncommitted_changes,
+    is_git_directory, local_branch_exists,
+};
+use crate::output::SnapshotRepositoryResult;
+use crate::utils::repository_name;
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+pub const STATUS_SAVED: &str = "saved";
+pub const STATUS_STASHED: &str = "stashed";
+pub const STATUS_RESTORED: &str = "restored";
+pub const STATUS_CONFLICT: &str = "conflict";
+pub const STATUS_SKIPPED: &str = "skipped";
+pub const STATUS_FAILED: &str = "failed";
+
+const STASH_MESSAGE_PREFIX: &str = "gmux-snapshot:";
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum SnapshotAction {
+    /// Record each repository's branch and HEAD, stashing uncommitted changes
+    Save {
+        /// Snapshot name
+        name: String,
+        /// Replace an existing snapshot with the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check out the recorded branches and pop the snapshot's stashes
+    Restore {
+        /// Snapshot name
+        name: String,
+    },
+    /// List saved snapshots
+    List,
+}
+
+/// Workspace state recorded by `gmux snapshot save`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub name: String,
+    pub workspace: String,
+    pub created_at: u64,
+    pub repositories: Vec<RepositorySnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RepositorySnapshot {
+    pub repository: String,
+    pub path: String,
+    /// `None` when HEAD was detached.
+    pub branch: Option<String>,
+    pub head: String,
+    /// Commit of the stash holding uncommitted changes, if there were any.
+    pub stash: Option<String>,
+}
+
+impl Snapshot {
+    pub fn new(name: &str, workspace: &Path, repositories: Vec<RepositorySnapshot>) -> Self {
+        Self {
+            name: name.to_string(),
+            workspace: workspace.display().to_string(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            repositories,
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub fn get_snapshot_path(name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(GmuxError::Validation(format!(
+            "Invalid snapshot name: {}",
+            name
+        )));
+    }
+    Ok(get_snapshot_dir().join(format!("{}.json", name)))
+}
+
+pub fn load_snapshot(name: &str) -> Result<Snapshot> {
+    let path = get_snapshot_path(name)?;
+    let content = fs::read_to_string(&path).map_err(|_| {
+        GmuxError::Config(
+            ConfigErrorKind::MissingSnapshot,
+            format!("Snapshot {} not found", name),
+        )
+    })?;
+    serde_json::from_str(&content).map_err(|error| {
+        GmuxError::Config(
+            ConfigErrorKind::InvalidSnapshot,
+            format!("Invalid snapshot {}: {}", name, error),
+        )
+    })
+}
+
+pub fn load_all_snapshots() -> Vec<Snapshot> {
+    let Ok(entries) = fs::read_dir(get_snapshot_dir()) else {
+        return Vec::new();
+    };
+
+    let mut snapshots: Vec<Snapshot> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str(&content).ok())
+        .collect();
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    snapshots
+}
+
+impl SnapshotRepositoryResult {
+    fn new(repository: String, path: String) -> Self {
+        Self {
+            repository,
+            path,
+            branch: None,
+            head: None,
+            stash: None,
+            status: STATUS_FAILED.to_string(),
+            reason: None,
+        }
+    }
+
+    fn finish(mut self, status: &str, reason: Option<String>) -> Self {
+        self.status = status.to_string();
+        self.reason = reason;
+        self
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self.status.as_str(), STATUS_FAILED | STATUS_CONFLICT)
+    }
+}
+
+/// Records one repository's branch and HEAD, stashing uncommitted and untracked changes under
+/// a message naming the snapshot. Returns `None` for directories that are not git repositories.
+pub async fn save_repository(
+    path: &Path,
+    name: &str,
+) -> Option<(SnapshotRepositoryResult, Option<RepositorySnapshot>)> {
+    let metadata = get_repository_metadata(path).await;
+    let mut result =
+        SnapshotRepositoryResult::new(repository_name(path), path.display().to_string());
+    let metadata = match metadata {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return None,
+        Err(error) => {
+            return Some((
+                result.finish(STATUS_FAILED, Some(error.format_error())),
+                None,
+            ))
+        }
+    };
+    let Some(last_commit) = get_last_commit(path).await else {
+        return Some((
+            result.finish(STATUS_SKIPPED, Some("no commits".to_string())),
+            None,
+        ));
+    };
+
+    result.branch = Some(metadata.current_branch).filter(|branch| !branch.is_empty());
+    result.head = Some(last_commit.hash.clone());
+
+    let stash = match stash_changes(path, name).await {
+        Ok(stash) => stash,
+        Err(error) => {
+            return Some((
+                result.finish(STATUS_FAILED, Some(error.format_error())),
+                None,
+            ))
+        }
+    };
+    result.stash = stash.clone();
+
+    let snapshot = RepositorySnapshot {
+        repository: result.repository.clone(),
+        path: result.path.clone(),
+        branch: result.branch.clone(),
+        head: last_commit.hash,
+        stash,
+    };
+    let status = if snapshot.stash.is_some() {
+        STATUS_STASHED
+    } else {
+        STATUS_SAVED
+    };
+    Some((result.finish(status, None), Some(snapshot)))
+}
+
+async fn stash_changes(path: &Path, name: &str) -> Result<Option<String>> {
+    let status = git_stdout(path, &["status", "--porcelain"]).await?;
+    if status.trim().is_empty() {
+        return Ok(None);
+    }
+
+    let message = format!("{}{}", STASH_MESSAGE_PREFIX, name);
+    git_stdout(
+        path,
+        &[
+            "stash",
+            "push",
+            "--include-untracked",
+            "--message",
+            &message,
+        ],
+    )
+    .await?;
+    let stash = git_stdout(path, &["rev-parse", "stash@{0}"]).await?;
+    Ok(Some(stash.trim().to_string()))
+}
+
+/// Checks out the recorded branch (or detached HEAD) and pops the recorded stash. A stash that
+/// does not apply cleanly is reported as a conflict and, as with `git stash pop`, kept.
+pub async fn restore_repository(
+    snapshot: &RepositorySnapshot,
+    name: &str,
+) -> SnapshotRepositoryResult {
+    let path = Path::new(&snapshot.path);
+    let mut result =
+        SnapshotRepositoryResult::new(snapshot.repository.clone(), snapshot.path.clone());
+    result.branch = snapshot.branch.clone();
+    result.head = Some(snapshot.head.clone());
+    result.stash = snapshot.stash.clone();
+
+    if !is_git_directory(path).await {
+        return result.finish(STATUS_FAILED, Some("repository not found".to_string()));
+    }
+
+    match checkout_recorded_head(path, snapshot).await {
+        Ok(()) => {}
+        Err(error) => return result.finish(STATUS_FAILED, Some(error.format_error())),
+    }
+
+    let Some(stash) = &snapshot.stash else {
+        return result.finish(STATUS_RESTORED, None);
+    };
+    let stash_ref = match find_stash(path, stash, name).await {
+        Ok(Some(stash_ref)) => stash_ref,
+        Ok(None) => {
+            return result.finish(
+                STATUS_FAILED,
+                Some(format!("stash {} no longer exists", stash)),
+            )
+        }
+        Err(error) => return result.finish(STATUS_FAILED, Some(error.format_error())),
+    };
+
+    let output = match Command::new("git")
+        .args(["stash", "pop", &stash_ref])
+        .current_dir(path)
+        .output()
+        .await
+    {
+        Ok(output) => output,
+        Err(error) => return result.finish(STATUS_FAILED, Some(error.to_string())),
+    };
+    if output.status.success() {
+        return result.finish(STATUS_RESTORED, None);
+    }
+
+    let message = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout).trim(),
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    let status = if message.contains("CONFLICT") || message.contains("conflict") {
+        STATUS_CONFLICT
+    } else {
+        STATUS_FAILED
+    };
+    result.finish(status, Some(message.trim().to_string()))
+}
+
+async fn checkout_recorded_head(path: &Path, snapshot: &RepositorySnapshot) -> Result<()> {
+    if has_uncommitted_changes(path).await? {
+        return Err(GmuxError::Git(
+            "working tree has uncommitted changes".to_string(),
+        ));
+    }
+
+    match &snapshot.branch {
+        Some(branch) if local_branch_exists(path, branch).await? => {
+            git_stdout(path, &["switch", branch]).await?;
+        }
+        // The branch was deleted since the snapshot; recreate it at the recorded HEAD.
+        Some(branch) => {
+            git_stdout(path, &["switch", "--create", branch, &snapshot.head]).await?;
+        }
+        None => {
+            git_stdout(path, &["switch", "--detach", &snapshot.head]).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the stash entry recorded in the snapshot, falling back to the newest stash carrying
+/// the snapshot's message.
+async fn find_stash(path: &Path, stash: &str, name: &str) -> Result<Option<String>> {
+    let list = git_stdout(path, &["stash", "list", "--format=%H%x00%gd%x00%gs"]).await?;
+    let entries: Vec<(&str, &str, &str)> = list
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split('\0');
+            Some((parts.next()?, parts.next()?, parts.next()?))
+        })
+        .collect();
+
+    let message = format!("{}{}", STASH_MESSAGE_PREFIX, name);
+    Ok(entries
+        .iter()
+        .find(|(hash, _, _)| *hash == stash)
+        .or_else(|| {
+            entries
+                .iter()
+                .find(|(_, _, subject)| subject.ends_with(&message))
+        })
+        .map(|(_, reference, _)| reference.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::git;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_save_and_restore_repository() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        git(repo_path, &["init", "-b", "main"]).await;
+        std::fs::write(repo_path.join("README.md"), "hello\n")?;
+        git(repo_path, &["add", "README.md"]).await;
+        git(repo_path, &["commit", "-m", "Initial commit"]).await;
+        git(repo_path, &["switch", "-c", "feature"]).await;
+        std::fs::write(repo_path.join("README.md"), "changed\n")?;
+        std::fs::write(repo_path.join("notes.txt"), "untracked\n")?;
+
+        let (result, snapshot) = save_repository(repo_path, "campaign").await.unwrap();
+        assert_eq!(result.status, STATUS_STASHED);
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.branch.as_deref(), Some("feature"));
+        assert!(!repo_path.join("notes.txt").exists());
+
+        git(repo_path, &["switch", "main"]).await;
+        let result = restore_repository(&snapshot, "campaign").await;
+        assert_eq!(result.status, STATUS_RESTORED);
+
+        let branch = git_stdout(repo_path, &["symbolic-ref", "--short", "HEAD"]).await?;
+        assert_eq!(branch.trim(), "feature");
+        assert_eq!(
+            std::fs::read_to_string(repo_path.join("README.md"))?,
+            "changed\n"
+        );
+        assert!(repo_path.join("notes.txt").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_restore_conflict() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let repo_path = temp_dir.path();
+        git(repo_path, &["init", "-b", "main"]).await;
+        std::fs::write(repo_path.join("README.md"), "hello\n")?;
+        git(repo_path, &["add", "README.md"]).await;
+        git(repo_path, &["commit", "-m", "Initial commit"]).await;
+        std::fs::write(repo_path.join("README.md"), "stashed\n")?;
+
+        let (_, snapshot) = save_repository(repo_path, "campaign").await.unwrap();
+        let snapshot = snapshot.unwrap();
+        std::fs::write(repo_path.join("README.md"), "committed\n")?;
+        git(repo_path, &["commit", "-am", "Campaign change"]).await;
+
+        let result = restore_repository(&snapshot, "campaign").await;
+        assert_eq!(result.status, STATUS_CONFLICT);
+        assert!(result.is_failed());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_path_rejects_traversal() {
+        assert!(get_snapshot_path("campaign").is_ok());
+        assert!(get_snapshot_path("../config").is_err());
+        assert!(get_snapshot_path("").is_err());
+    }
+}
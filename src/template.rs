@@ -0,0 +1,121 @@
+use crate::error::{GmuxError, Result};
+use crate::git::{BranchCommit, BranchDiff, ChangedFile};
+use minijinja::Environment;
+use serde::Serialize;
+
+/// Variables available to PR templates.
+#[derive(Debug, Serialize)]
+pub struct TemplateContext<'a> {
+    pub title: &'a str,
+    pub repository_name: &'a str,
+    pub base: &'a str,
+    pub head: &'a str,
+    /// Paths of changed files, kept for templates written before `files` existed.
+    pub diff_files: Vec<String>,
+    pub files: &'a [ChangedFile],
+    pub commits: &'a [BranchCommit],
+    pub insertions: u32,
+    pub deletions: u32,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn new(title: &'a str, repository_name: &'a str, diff: &'a BranchDiff) -> Self {
+        Self {
+            title,
+            repository_name,
+            base: &diff.base,
+            head: &diff.head,
+            diff_files: diff.file_names(),
+            files: &diff.files,
+            commits: &diff.commits,
+            insertions: diff.insertions,
+            deletions: diff.deletions,
+        }
+    }
+}
+
+/// Renders a Jinja template. Block tags swallow the newline that follows them, so
+/// `{% for %}` loops laid out one tag per line do not leave blank lines behind.
+/// The template's trailing newline is kept.
+pub fn render_template<S: Serialize>(template: &str, context: &S) -> Result<String> {
+    let mut environment = Environment::new();
+    environment.set_trim_blocks(true);
+    environment.set_keep_trailing_newline(true);
+    environment
+        .render_str(template, context)
+        .map_err(|error| GmuxError::Validation(format!("Invalid template: {}", error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff() -> BranchDiff {
+        BranchDiff {
+            base: "main".to_string(),
+            head: "feature".to_string(),
+            merge_base: "abc".to_string(),
+            insertions: 3,
+            deletions: 1,
+            files: vec![
+                ChangedFile {
+                    path: "README.md".to_string(),
+                    old_path: None,
+                    status: "modified".to_string(),
+                    insertions: Some(3),
+                    deletions: Some(1),
+                },
+                ChangedFile {
+                    path: "logo.png".to_string(),
+                    old_path: None,
+                    status: "added".to_string(),
+                    insertions: None,
+                    deletions: None,
+                },
+            ],
+            commits: vec![BranchCommit {
+                hash: "abcdef".to_string(),
+                short_hash: "abc".to_string(),
+                subject: "Update docs".to_string(),
+                author: "Sam".to_string(),
+                committed_at: "2024-01-01T00:00:00Z".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_render_default_template() -> Result<()> {
+        let diff = diff();
+        let body = render_template(
+            crate::config::DEFAULT_PR_TEMPLATE,
+            &TemplateContext::new("My PR", "repo1", &diff),
+        )?;
+        assert!(body.starts_with("# My PR"));
+        assert!(body.contains("- README.md\n- logo.png\n"));
+        assert!(body.contains("repo1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_change_details() -> Result<()> {
+        let diff = diff();
+        let template =
+            "{{ diff_files|length }} files (+{{ insertions }} -{{ deletions }}) into {{ base }}\n\
+            {% if \"README.md\" in diff_files %}docs\n{% endif %}\
+            {% for file in files %}{{ file.status }} {{ file.path }}\n{% endfor %}\
+            {% for commit in commits %}{{ commit.short_hash }} {{ commit.subject }}{% endfor %}";
+        let body = render_template(template, &TemplateContext::new("t", "repo1", &diff))?;
+        assert_eq!(
+            body,
+            "2 files (+3 -1) into main\ndocs\nmodified README.md\nadded logo.png\nabc Update docs"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_invalid_template() {
+        let diff = diff();
+        let result = render_template("{% for %}", &TemplateContext::new("t", "repo1", &diff));
+        assert!(matches!(result, Err(GmuxError::Validation(_))));
+    }
+}
@@ -0,0 +1,34 @@
+//! Helpers shared by the unit tests of several modules.
+
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use tokio::process::Command;
+
+/// Runs git in `path`, panicking if it fails, and returns its stdout.
+pub async fn git(path: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(path)
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "git {:?} failed", args);
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+/// A clone of a bare repository whose `main` branch has `README.md` committed, so
+/// `origin/HEAD` is set. The bare repository is `origin.git`, cloned from `seed`.
+pub async fn setup_cloned_repo() -> (TempDir, PathBuf) {
+    let temp_dir = TempDir::new().unwrap();
+    let seed = temp_dir.path().join("seed");
+    std::fs::create_dir(&seed).unwrap();
+    git(&seed, &["init", "-b", "main"]).await;
+    std::fs::write(seed.join("README.md"), "hello\n").unwrap();
+    git(&seed, &["add", "README.md"]).await;
+    git(&seed, &["commit", "-m", "Initial commit"]).await;
+    git(temp_dir.path(), &["clone", "--bare", "seed", "origin.git"]).await;
+    git(temp_dir.path(), &["clone", "origin.git", "clone"]).await;
+
+    let repo_path = temp_dir.path().join("clone");
+    (temp_dir, repo_path)
+}
@@ -1,7 +1,9 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use futures::stream::{self, StreamExt};
 use regex::Regex;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 
 #[derive(Debug)]
@@ -30,11 +32,16 @@ pub async fn run_command_capture(cmd: &[&str], cwd: &Path) -> Result<CommandOutp
     })
 }
 
+pub fn repository_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
 pub fn repository_paths(filter: Option<&str>) -> Result<Vec<Box<Path>>> {
     let current_dir = std::env::current_dir()?;
-    let filter_regex = filter
-        .map(|f| Regex::new(f).map_err(|e| anyhow!("Invalid regex pattern: {}", e)))
-        .transpose()?;
+    // Kept as a `regex::Error` so that it is reported as an invalid argument.
+    let filter_regex = filter.map(Regex::new).transpose()?;
     let mut paths = Vec::new();
 
     for entry in std::fs::read_dir(&current_dir)? {
@@ -59,47 +66,107 @@ pub fn repository_paths(filter: Option<&str>) -> Result<Vec<Box<Path>>> {
     Ok(paths)
 }
 
-pub async fn for_each_repository<F>(f: F, filter: Option<&str>, concurrency: usize) -> Result<()>
+/// What running a command in one repository produced.
+#[derive(Debug)]
+pub enum RepositoryStatus {
+    /// The command ran to completion, whatever its exit code.
+    Completed {
+        command: String,
+        output: CommandOutput,
+    },
+    /// The repository was not eligible, e.g. it is not a git repository.
+    Skipped(String),
+    /// The command could not be run.
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub struct RepositoryOutcome {
+    pub path: Box<Path>,
+    pub duration: Duration,
+    pub status: RepositoryStatus,
+}
+
+impl RepositoryOutcome {
+    pub fn exit_code(&self) -> Option<i32> {
+        match &self.status {
+            RepositoryStatus::Completed { output, .. } => Some(output.exit_code),
+            _ => None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.exit_code() == Some(0)
+    }
+}
+
+/// Runs `f` in every repository matching `filter`, returning one outcome per repository in
+/// directory order. `on_outcome` sees each outcome as soon as it finishes. Errors returned by
+/// `f` are recorded as `Failed` outcomes rather than aborting the other repositories.
+pub async fn for_each_repository<F, O>(
+    f: F,
+    filter: Option<&str>,
+    concurrency: usize,
+    mut on_outcome: O,
+) -> Result<Vec<RepositoryOutcome>>
 where
     F: Fn(
-            Box<Path>,
-        )
-            -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'static>>
-        + Sync,
+        Box<Path>,
+    ) -> std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<RepositoryStatus>> + Send + 'static>,
+    >,
+    O: FnMut(&RepositoryOutcome) -> Result<()>,
 {
     let paths = repository_paths(filter)?;
-    let results: Vec<Result<()>> = stream::iter(paths)
-        .map(f)
-        .buffer_unordered(concurrency)
-        .collect()
-        .await;
-
-    for result in results {
-        result?;
-    }
+    let mut outcomes = stream::iter(paths.into_iter().enumerate())
+        .map(|(index, path)| {
+            let run = f(path.clone());
+            async move {
+                let start = Instant::now();
+                let status = run
+                    .await
+                    .unwrap_or_else(|error| RepositoryStatus::Failed(error.to_string()));
+                let outcome = RepositoryOutcome {
+                    path,
+                    duration: start.elapsed(),
+                    status,
+                };
+                (index, outcome)
+            }
+        })
+        .buffer_unordered(concurrency);
 
-    Ok(())
+    let mut results: BTreeMap<usize, RepositoryOutcome> = BTreeMap::new();
+    while let Some((index, outcome)) = outcomes.next().await {
+        on_outcome(&outcome)?;
+        results.insert(index, outcome);
+    }
+    Ok(results.into_values().collect())
 }
 
 pub async fn get_template_content() -> Result<Option<String>> {
-    // Current dir
+    match find_template_path()? {
+        Some(template_path) => Ok(Some(std::fs::read_to_string(template_path)?)),
+        None => Ok(None),
+    }
+}
+
+/// The PR template in use: `PR_TEMPLATE.md` in the current directory, else the one in the
+/// config directory.
+pub fn find_template_path() -> Result<Option<PathBuf>> {
     let current_dir_template = std::env::current_dir()?.join("PR_TEMPLATE.md");
     if current_dir_template.exists() {
-        return Ok(Some(std::fs::read_to_string(current_dir_template)?));
+        return Ok(Some(current_dir_template));
     }
 
-    // Fall back to config directory
     let template_path = crate::config::get_template_path();
-    if !template_path.exists() {
-        return Ok(None);
-    }
-
-    Ok(Some(std::fs::read_to_string(template_path)?))
+    Ok(template_path.exists().then_some(template_path))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::anyhow;
     use std::fs;
     use std::sync::{Arc, Mutex};
     use tempfile::TempDir;
@@ -139,23 +206,37 @@ mod tests {
         std::fs::create_dir("other").unwrap();
 
         let visited = Arc::new(Mutex::new(Vec::new()));
+        let mut reported = Vec::new();
 
         // Test without filter
         let visited_clone = Arc::clone(&visited);
-        for_each_repository(
+        let outcomes = for_each_repository(
             move |path| {
                 let visited_clone = Arc::clone(&visited_clone);
                 Box::pin(async move {
                     let dir_name = path.file_name().unwrap().to_string_lossy().to_string();
-                    visited_clone.lock().unwrap().push(dir_name);
-                    Ok(())
+                    visited_clone.lock().unwrap().push(dir_name.clone());
+                    Ok(match dir_name.as_str() {
+                        "other" => RepositoryStatus::Skipped("not a repository".to_string()),
+                        "repo2" => return Err(anyhow!("could not run")),
+                        _ => RepositoryStatus::Completed {
+                            // Finishes last, so it is reported last.
+                            command: "sleep".to_string(),
+                            output: run_command_capture(&["sleep", "0.2"], &path).await?,
+                        },
+                    })
                 })
             },
             None,
             10,
+            |outcome| {
+                reported.push(repository_name(&outcome.path));
+                Ok(())
+            },
         )
         .await
         .unwrap();
+        assert_eq!(reported.last().map(String::as_str), Some("repo1"));
 
         let visited_vec = visited.lock().unwrap().clone();
         assert_eq!(visited_vec.len(), 3);
@@ -164,23 +245,60 @@ mod tests {
         assert!(visited_vec.contains(&"other".to_string()));
         drop(visited_vec); // Drop the lock before the next await
 
+        assert_eq!(outcomes.len(), 3);
+        for outcome in &outcomes {
+            match repository_name(&outcome.path).as_str() {
+                "repo1" => assert!(outcome.is_success()),
+                "repo2" => assert!(
+                    matches!(&outcome.status, RepositoryStatus::Failed(error) if error == "could not run")
+                ),
+                _ => {
+                    assert!(matches!(outcome.status, RepositoryStatus::Skipped(_)));
+                    assert_eq!(outcome.exit_code(), None);
+                }
+            }
+        }
+
         // Test with filter
         let visited_filtered = Arc::new(Mutex::new(Vec::new()));
         let visited_filtered_clone = Arc::clone(&visited_filtered);
-        for_each_repository(
+        reported.clear();
+        let outcomes = for_each_repository(
             move |path| {
                 let visited_filtered_clone = Arc::clone(&visited_filtered_clone);
                 Box::pin(async move {
                     let dir_name = path.file_name().unwrap().to_string_lossy().to_string();
-                    visited_filtered_clone.lock().unwrap().push(dir_name);
-                    Ok(())
+                    visited_filtered_clone
+                        .lock()
+                        .unwrap()
+                        .push(dir_name.clone());
+                    if dir_name == "repo1" {
+                        run_command_capture(&["sleep", "0.2"], &path).await?;
+                    }
+                    Ok(RepositoryStatus::Skipped("visited".to_string()))
                 })
             },
             Some("repo.*"),
             10,
+            |outcome| {
+                reported.push(repository_name(&outcome.path));
+                Ok(())
+            },
         )
         .await
         .unwrap();
+        assert_eq!(reported.last().map(String::as_str), Some("repo1"));
+        let names: Vec<String> = outcomes
+            .iter()
+            .map(|outcome| repository_name(&outcome.path))
+            .collect();
+        // Outcomes are still returned in directory order.
+        let paths: Vec<String> = repository_paths(Some("repo.*"))
+            .unwrap()
+            .iter()
+            .map(|path| repository_name(path))
+            .collect();
+        assert_eq!(names, paths);
 
         let visited_vec = visited_filtered.lock().unwrap().clone();
         assert_eq!(visited_vec.len(), 2);